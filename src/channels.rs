@@ -0,0 +1,200 @@
+use serde::{Deserialize, Serialize};
+
+/// 一对起止标记，标记之间的内容（如推理过程）单独显示为可折叠的通道
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TagRule {
+    /// 折叠时显示的名称
+    pub name: String,
+    pub start: String,
+    pub end: String,
+    /// 颜色名（`darkgray`、`magenta` 等）或 `#rrggbb`，默认灰色
+    #[serde(default)]
+    pub color: Option<String>,
+    /// 默认展开
+    #[serde(default)]
+    pub expanded: bool,
+}
+
+/// 未配置 `reasoning_tags` 时使用：DeepSeek-R1、QwQ 等模型的 `<think>`
+pub fn default_rules() -> Vec<TagRule> {
+    vec![TagRule {
+        name: "thinking".to_string(),
+        start: "<think>".to_string(),
+        end: "</think>".to_string(),
+        color: None,
+        expanded: false,
+    }]
+}
+
+/// 回复中的一段，`channel` 为所属规则的下标，正文为 None
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Segment {
+    channel: Option<usize>,
+    start: usize,
+    end: usize,
+    /// 还没有出现结束标记（仍在生成中）
+    open: bool,
+}
+
+fn split(content: &str, rules: &[TagRule]) -> Vec<Segment> {
+    let mut segments = Vec::new();
+    let mut pos = 0;
+    while pos < content.len() {
+        let next = rules
+            .iter()
+            .enumerate()
+            .filter(|(_, rule)| !rule.start.is_empty())
+            .filter_map(|(i, rule)| content[pos..].find(&rule.start).map(|at| (pos + at, i)))
+            .min();
+        let Some((at, rule)) = next else {
+            segments.push(Segment {
+                channel: None,
+                start: pos,
+                end: content.len(),
+                open: false,
+            });
+            break;
+        };
+        if at > pos {
+            segments.push(Segment {
+                channel: None,
+                start: pos,
+                end: at,
+                open: false,
+            });
+        }
+        let body = at + rules[rule].start.len();
+        match content[body..].find(&rules[rule].end) {
+            Some(len) if !rules[rule].end.is_empty() => {
+                segments.push(Segment {
+                    channel: Some(rule),
+                    start: body,
+                    end: body + len,
+                    open: false,
+                });
+                pos = body + len + rules[rule].end.len();
+            }
+            _ => {
+                segments.push(Segment {
+                    channel: Some(rule),
+                    start: body,
+                    end: content.len(),
+                    open: true,
+                });
+                break;
+            }
+        }
+    }
+    segments
+}
+
+/// 去掉所有通道内容，只留正文。发回给模型的历史消息不包含推理过程
+pub fn strip(content: &str, rules: &[TagRule]) -> String {
+    let segments = split(content, rules);
+    if segments.iter().all(|s| s.channel.is_none()) {
+        return content.to_string();
+    }
+    segments
+        .iter()
+        .filter(|s| s.channel.is_none())
+        .map(|s| &content[s.start..s.end])
+        .collect::<String>()
+        .trim_start()
+        .to_string()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineKind {
+    Text,
+    /// 通道的标题行（折叠时只有这一行）
+    Header,
+    /// 展开的通道内容
+    Body,
+}
+
+/// 回复按通道拆分后的一行，`offset` 为它在原文中的字节位置，用于搜索高亮
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DisplayLine {
+    pub kind: LineKind,
+    pub channel: Option<usize>,
+    pub text: String,
+    pub offset: Option<usize>,
+}
+
+/// 把回复拆成显示用的行；`expanded` 为 None 时按各规则的默认设置折叠或展开
+pub fn display_lines(content: &str, rules: &[TagRule], expanded: Option<bool>) -> Vec<DisplayLine> {
+    let segments = split(content, rules);
+    let mut lines = Vec::new();
+    for (i, segment) in segments.iter().enumerate() {
+        // 标记前后的空行不显示
+        let mut start = segment.start;
+        let mut end = segment.end;
+        let next_to_channel = |j: Option<usize>| {
+            j.and_then(|j| segments.get(j))
+                .is_some_and(|s| s.channel.is_some())
+        };
+        if segment.channel.is_some() || next_to_channel(i.checked_sub(1)) {
+            start += content[start..end].len()
+                - content[start..end].trim_start_matches(['\n', '\r']).len();
+        }
+        if segment.channel.is_some() || next_to_channel(Some(i + 1)) {
+            end = start + content[start..end].trim_end_matches(['\n', '\r']).len();
+        }
+        let body = &content[start..end];
+
+        let mut body_lines = Vec::new();
+        let mut offset = start;
+        if !body.is_empty() {
+            for line in body.split('\n') {
+                body_lines.push((offset, line.strip_suffix('\r').unwrap_or(line)));
+                offset += line.len() + 1;
+            }
+        }
+
+        match segment.channel {
+            None => lines.extend(body_lines.into_iter().map(|(offset, text)| DisplayLine {
+                kind: LineKind::Text,
+                channel: None,
+                text: text.to_string(),
+                offset: Some(offset),
+            })),
+            Some(rule) => {
+                let open = expanded.unwrap_or(rules[rule].expanded);
+                let header = if open {
+                    format!("▾ {}", rules[rule].name)
+                } else {
+                    format!(
+                        "▸ {} · {} line{}{}",
+                        rules[rule].name,
+                        body_lines.len(),
+                        if body_lines.len() == 1 { "" } else { "s" },
+                        if segment.open { " …" } else { "" }
+                    )
+                };
+                lines.push(DisplayLine {
+                    kind: LineKind::Header,
+                    channel: Some(rule),
+                    text: header,
+                    offset: None,
+                });
+                if open {
+                    lines.extend(body_lines.into_iter().map(|(offset, text)| DisplayLine {
+                        kind: LineKind::Body,
+                        channel: Some(rule),
+                        text: text.to_string(),
+                        offset: Some(offset),
+                    }));
+                }
+            }
+        }
+    }
+    if lines.is_empty() {
+        lines.push(DisplayLine {
+            kind: LineKind::Text,
+            channel: None,
+            text: String::new(),
+            offset: Some(0),
+        });
+    }
+    lines
+}
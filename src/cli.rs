@@ -0,0 +1,567 @@
+use crate::connection;
+use crate::daemon;
+use crate::engine::{self, Backend, BudgetTracker, ChatEngine, ReplyBudget};
+use crate::export;
+use crate::headless;
+use crate::ollama::{ApiError, ChatMessage, OllamaClient};
+use crate::present;
+use std::io::{BufRead, IsTerminal, Read, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// 退出码：0 成功，1 Ollama 返回错误或无法连接，2 参数错误，3 写入输出失败
+const EXIT_API_ERROR: i32 = 1;
+const EXIT_USAGE: i32 = 2;
+const EXIT_IO_ERROR: i32 = 3;
+
+const USAGE: &str = "\
+Usage: ollama-cli chat [-m MODEL] [-s SYSTEM] [-o FILE] [-q] [-t SECS] [-n TOKENS] [PROMPT...]
+       ollama-cli --json-repl [-m MODEL]
+       ollama-cli show CONVERSATION [--style plain|slides] [--no-pager]
+       ollama-cli export CONVERSATION [--format FORMAT] [-o PATH]
+       ollama-cli daemon [status|stop]
+       ollama-cli --oneshot -m MODEL [-s SYSTEM]
+
+Sends one message and streams the reply to stdout. Piped stdin is appended
+to the prompt, e.g.  git diff | ollama-cli chat -m qwen2.5-coder \"review this\" > review.md
+
+Options:
+  -m, --model MODEL    model to use (default: first installed model)
+  -s, --system TEXT    system prompt
+  -o, --output FILE    write the reply to FILE instead of stdout
+  -q, --quiet          no spinner or stats on stderr
+  -t, --timeout SECS   stop the reply after SECS seconds, keeping what was generated
+  -n, --max-tokens N   stop the reply after N tokens
+  -h, --help           show this help
+
+--json-repl reads JSON commands from stdin, one per line, and writes
+streaming JSON events to stdout (see the README for the protocol).
+
+show prints a saved conversation (session id, part of its title, \"last\" or a
+session .json file) through $PAGER; --style slides puts each question and its
+answers on a page of its own.
+
+export writes a saved conversation as md, json (Open WebUI), html, scripts (code
+blocks as files) or flashcards (Anki); the format defaults to the extension of
+-o, then json.
+
+--oneshot starts the TUI as a kiosk for demos: a single conversation with
+MODEL, no other tabs, commands or model switching. Exit with Ctrl+C.
+
+daemon runs the background generation daemon in the foreground (the TUI
+starts it on demand when \"daemon\": true is set in the config); status lists
+its jobs and stop shuts it down.
+
+Run without arguments to start the TUI.";
+
+fn show_command(args: impl Iterator<Item = String>) -> i32 {
+    let mut query = None;
+    let mut style = present::Style::default();
+    let mut use_pager = true;
+    let mut args = args;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--style" => match args.next().as_deref().and_then(present::Style::parse) {
+                Some(parsed) => style = parsed,
+                None => {
+                    eprintln!("--style expects plain or slides\n\n{}", USAGE);
+                    return EXIT_USAGE;
+                }
+            },
+            "--no-pager" => use_pager = false,
+            flag if flag.starts_with("--") => {
+                eprintln!("unknown option: {}\n\n{}", flag, USAGE);
+                return EXIT_USAGE;
+            }
+            _ => query = Some(arg),
+        }
+    }
+    let Some(query) = query else {
+        eprintln!("show requires a conversation\n\n{}", USAGE);
+        return EXIT_USAGE;
+    };
+    let Some(session) = present::find_session(&query) else {
+        eprintln!("error: no saved conversation matches {:?}", query);
+        return EXIT_USAGE;
+    };
+
+    let (width, height) = crossterm::terminal::size()
+        .map(|(w, h)| (w as usize, h as usize))
+        .unwrap_or((80, 24));
+    let color = std::io::stdout().is_terminal();
+    let pages = present::render(&session, style, width.min(100), color);
+    match present::display(pages, style, height.saturating_sub(1), use_pager) {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            EXIT_IO_ERROR
+        }
+    }
+}
+
+fn export_command(args: impl Iterator<Item = String>) -> i32 {
+    let mut query = None;
+    let mut format = None;
+    let mut output = None;
+    let mut args = args;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-f" | "--format" => format = args.next(),
+            "-o" | "--output" => output = args.next().map(PathBuf::from),
+            flag if flag.starts_with('-') => {
+                eprintln!("unknown option: {}\n\n{}", flag, USAGE);
+                return EXIT_USAGE;
+            }
+            _ => query = Some(arg),
+        }
+    }
+    let exporter = match format.as_deref() {
+        Some(name) => export::find(name),
+        None => output
+            .as_deref()
+            .and_then(export::find_by_path)
+            .or_else(|| export::find("json")),
+    };
+    let Some(exporter) = exporter else {
+        eprintln!("unknown format; available formats:");
+        for exporter in export::exporters() {
+            eprintln!("  {:<12}{}", exporter.name(), exporter.description());
+        }
+        return EXIT_USAGE;
+    };
+    let Some(query) = query else {
+        eprintln!("export requires a conversation\n\n{}", USAGE);
+        return EXIT_USAGE;
+    };
+    let Some(session) = present::find_session(&query) else {
+        eprintln!("error: no saved conversation matches {:?}", query);
+        return EXIT_USAGE;
+    };
+
+    let path = output.unwrap_or_else(|| exporter.default_path());
+    match exporter.export(&session, &path) {
+        Ok(what) => {
+            eprintln!("Exported {} to {}", what, path.display());
+            0
+        }
+        Err(e) => {
+            eprintln!("error: {}", e);
+            EXIT_IO_ERROR
+        }
+    }
+}
+
+fn daemon_command(action: Option<&str>) -> i32 {
+    let result = match action {
+        None => daemon::serve(),
+        Some("stop") => daemon::shutdown(),
+        Some("status") => daemon::list().map(|jobs| {
+            println!("daemon listening on {}", daemon::socket_path().display());
+            for job in jobs {
+                println!(
+                    "{:>4}  {}  {:<24} {}",
+                    job.id,
+                    crate::timefmt::datetime(job.started_at),
+                    job.model,
+                    if job.finished { "finished" } else { "running" }
+                );
+            }
+        }),
+        Some(other) => {
+            eprintln!("unknown daemon action: {}\n\n{}", other, USAGE);
+            return EXIT_USAGE;
+        }
+    };
+    match result {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            EXIT_API_ERROR
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct ChatArgs {
+    model: Option<String>,
+    system: Option<String>,
+    output: Option<PathBuf>,
+    quiet: bool,
+    budget: ReplyBudget,
+    prompt: Vec<String>,
+}
+
+/// 命令行模式入口，返回进程退出码
+pub fn run(args: Vec<String>) -> i32 {
+    let mut args = args.into_iter();
+    match args.next().as_deref() {
+        Some("chat") => {}
+        Some("--json-repl") => {
+            let model = match args.next().as_deref() {
+                Some("-m") | Some("--model") => args.next(),
+                None => None,
+                Some(other) => {
+                    eprintln!("unknown option: {}\n\n{}", other, USAGE);
+                    return EXIT_USAGE;
+                }
+            };
+            return headless::run(model);
+        }
+        Some("daemon") => return daemon_command(args.next().as_deref()),
+        Some("show") => return show_command(args),
+        Some("export") => return export_command(args),
+        Some("-h") | Some("--help") | Some("help") => {
+            println!("{}", USAGE);
+            return 0;
+        }
+        Some(other) => {
+            eprintln!("unknown command: {}\n\n{}", other, USAGE);
+            return EXIT_USAGE;
+        }
+        None => return 0,
+    }
+
+    let args = match parse_chat_args(args) {
+        Ok(Some(args)) => args,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return 0;
+        }
+        Err(message) => {
+            eprintln!("{}\n\n{}", message, USAGE);
+            return EXIT_USAGE;
+        }
+    };
+
+    match chat(args) {
+        Ok(()) => 0,
+        Err(e) => match e.downcast_ref::<CliError>() {
+            Some(cli_error) => {
+                eprintln!("error: {}", cli_error);
+                match cli_error {
+                    CliError::Usage(_) => EXIT_USAGE,
+                    CliError::Io(_) => EXIT_IO_ERROR,
+                }
+            }
+            None => {
+                eprintln!("error: {}", connection::describe_error(&e));
+                EXIT_API_ERROR
+            }
+        },
+    }
+}
+
+/// 与 Ollama 无关的错误，和 API 错误使用不同的退出码
+#[derive(Debug)]
+enum CliError {
+    Usage(String),
+    Io(std::io::Error),
+}
+
+impl std::fmt::Display for CliError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CliError::Usage(message) => write!(f, "{}", message),
+            CliError::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for CliError {}
+
+fn parse_chat_args(mut args: impl Iterator<Item = String>) -> Result<Option<ChatArgs>, String> {
+    let mut parsed = ChatArgs::default();
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| {
+            args.next()
+                .ok_or_else(|| format!("{} requires a value", name))
+        };
+        match arg.as_str() {
+            "-m" | "--model" => parsed.model = Some(value(&arg)?),
+            "-s" | "--system" => parsed.system = Some(value(&arg)?),
+            "-o" | "--output" => parsed.output = Some(PathBuf::from(value(&arg)?)),
+            "-q" | "--quiet" => parsed.quiet = true,
+            "-t" | "--timeout" => parsed.budget.seconds = Some(number(&arg, value(&arg)?)?),
+            "-n" | "--max-tokens" => parsed.budget.tokens = Some(number(&arg, value(&arg)?)?),
+            "-h" | "--help" => return Ok(None),
+            "--" => {
+                parsed.prompt.extend(args.by_ref());
+            }
+            flag if flag.starts_with('-') && flag.len() > 1 => {
+                return Err(format!("unknown option: {}", flag));
+            }
+            _ => parsed.prompt.push(arg),
+        }
+    }
+    Ok(Some(parsed))
+}
+
+/// `--oneshot` 展示模式的参数
+#[derive(Debug, Default)]
+pub struct Oneshot {
+    pub model: String,
+    pub system: Option<String>,
+}
+
+/// 解析 `--oneshot` 之后的参数，出错时返回带用法说明的错误信息
+pub fn parse_oneshot(args: &[String]) -> Result<Oneshot, String> {
+    let mut model = None;
+    let mut system = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| {
+            args.next()
+                .cloned()
+                .ok_or_else(|| format!("{} requires a value\n\n{}", name, USAGE))
+        };
+        match arg.as_str() {
+            "-m" | "--model" => model = Some(value(arg)?),
+            "-s" | "--system" => system = Some(value(arg)?),
+            other => return Err(format!("unknown option: {}\n\n{}", other, USAGE)),
+        }
+    }
+    let model = model.ok_or_else(|| format!("--oneshot requires -m MODEL\n\n{}", USAGE))?;
+    Ok(Oneshot { model, system })
+}
+
+fn number(name: &str, value: String) -> Result<u64, String> {
+    value
+        .parse()
+        .ok()
+        .filter(|n| *n > 0)
+        .ok_or_else(|| format!("{} expects a positive number, got {}", name, value))
+}
+
+fn chat(args: ChatArgs) -> anyhow::Result<()> {
+    let mut prompt = args.prompt.join(" ");
+    let stdin = std::io::stdin();
+    if !stdin.is_terminal() {
+        let mut piped = String::new();
+        stdin
+            .lock()
+            .read_to_string(&mut piped)
+            .map_err(CliError::Io)?;
+        if !piped.trim().is_empty() {
+            if !prompt.is_empty() {
+                prompt.push_str("\n\n");
+            }
+            prompt.push_str(&piped);
+        }
+    }
+    if prompt.trim().is_empty() {
+        return Err(CliError::Usage(
+            "no prompt given (pass it as an argument or via stdin)".to_string(),
+        )
+        .into());
+    }
+
+    let rt = tokio::runtime::Runtime::new()?;
+    let model = match args.model {
+        Some(model) => model,
+        None => rt
+            .block_on(OllamaClient::new(None).list_models())?
+            .models
+            .first()
+            .map(|m| m.name.clone())
+            .ok_or_else(|| ApiError("no models installed; pass one with --model".into()))?,
+    };
+
+    let messages = engine::request_messages(
+        args.system.as_deref().unwrap_or_default(),
+        [ChatMessage {
+            role: "user".to_string(),
+            content: prompt,
+        }],
+    );
+
+    let out: Box<dyn Write + Send> = match &args.output {
+        Some(path) => Box::new(std::fs::File::create(path).map_err(CliError::Io)?),
+        None => Box::new(std::io::stdout()),
+    };
+    let writer = Arc::new(Mutex::new(StreamWriter::new(out)));
+
+    let show_progress = !args.quiet && std::io::stderr().is_terminal();
+    let first_chunk = Arc::new(AtomicBool::new(false));
+    let spinner = show_progress.then(|| spawn_spinner(first_chunk.clone()));
+
+    let started = Instant::now();
+    let chunk_writer = writer.clone();
+    let chunk_flag = first_chunk.clone();
+    let tracker = Arc::new(BudgetTracker::new(args.budget));
+    let chunk_tracker = tracker.clone();
+    let handle = tracker.watch(engine::stream_chat(
+        &Backend::Direct,
+        model.clone(),
+        messages,
+        tracker.options(None),
+        tracker.deadline(),
+        move |content| {
+            if chunk_tracker.stopped().is_some() {
+                return false;
+            }
+            chunk_flag.store(true, Ordering::SeqCst);
+            if let Ok(mut writer) = chunk_writer.lock() {
+                writer.update(&content);
+            }
+            chunk_tracker.tick().is_none()
+        },
+    ));
+
+    let result = handle
+        .join()
+        .map_err(|_| anyhow::anyhow!("chat thread panicked"))?;
+    first_chunk.store(true, Ordering::SeqCst);
+    if let Some(spinner) = spinner {
+        let _ = spinner.join();
+    }
+    let completion = result?;
+
+    let mut writer = writer
+        .lock()
+        .map_err(|_| anyhow::anyhow!("output lock poisoned"))?;
+    writer.finish().map_err(CliError::Io)?;
+
+    if let Some(reason) = tracker.stopped().filter(|_| !args.quiet) {
+        eprintln!("stopped at the {}", reason);
+    }
+    if show_progress {
+        let size = match completion.stats.eval_count {
+            Some(tokens) => format!("{} tokens", tokens),
+            None => format!("{} chars", completion.content.chars().count()),
+        };
+        eprintln!(
+            "{} · {} in {:.1}s{}",
+            model,
+            size,
+            started.elapsed().as_secs_f64(),
+            args.output
+                .as_ref()
+                .map(|p| format!(" · saved to {}", p.display()))
+                .unwrap_or_default()
+        );
+    }
+    Ok(())
+}
+
+const REPL_HELP: &str = "\
+/model NAME   switch model
+/clear        forget the conversation
+/quit         exit";
+
+/// 终端无法绘制 TUI 时改用的逐行对话模式，沿用 TUI 中的模型、系统提示词和对话历史
+pub fn repl(mut engine: ChatEngine) -> anyhow::Result<()> {
+    println!("Type a message and press Enter. /help lists commands.");
+
+    let stdin = std::io::stdin();
+    let mut lines = stdin.lock().lines();
+    loop {
+        print!("{}> ", engine.model.as_deref().unwrap_or(""));
+        std::io::stdout().flush()?;
+        let Some(line) = lines.next() else {
+            println!();
+            return Ok(());
+        };
+        let line = line?;
+        let line = line.trim();
+
+        match line.split_once(' ').unwrap_or((line, "")) {
+            ("", _) => continue,
+            ("/quit" | "/exit" | "/q", _) => return Ok(()),
+            ("/help", _) => println!("{}", REPL_HELP),
+            ("/clear", _) => {
+                engine.clear();
+                println!("Conversation cleared.");
+            }
+            ("/model", name) if !name.trim().is_empty() => {
+                engine.model = Some(name.trim().to_string());
+            }
+            ("/model", _) => println!("usage: /model NAME"),
+            _ => {
+                let ends_with_newline = Arc::new(AtomicBool::new(true));
+                let flag = ends_with_newline.clone();
+                let result = engine.send(line, move |delta| {
+                    let mut stdout = std::io::stdout();
+                    let _ = stdout.write_all(delta.as_bytes());
+                    let _ = stdout.flush();
+                    flag.store(delta.ends_with('\n'), Ordering::SeqCst);
+                });
+                if !ends_with_newline.load(Ordering::SeqCst) {
+                    println!();
+                }
+                if let Err(e) = result {
+                    eprintln!("error: {}", connection::describe_error(&e));
+                }
+            }
+        }
+    }
+}
+
+/// 把流式回调中不断增长的完整回复按增量写出，与传输层无关
+struct StreamWriter {
+    out: Box<dyn Write + Send>,
+    written: usize,
+    ends_with_newline: bool,
+    error: Option<std::io::Error>,
+}
+
+impl StreamWriter {
+    fn new(out: Box<dyn Write + Send>) -> Self {
+        Self {
+            out,
+            written: 0,
+            ends_with_newline: true,
+            error: None,
+        }
+    }
+
+    fn update(&mut self, content: &str) {
+        if self.error.is_some() {
+            return;
+        }
+        let Some(delta) = content.get(self.written..).filter(|d| !d.is_empty()) else {
+            return;
+        };
+        let result = self
+            .out
+            .write_all(delta.as_bytes())
+            .and_then(|_| self.out.flush());
+        match result {
+            Ok(()) => {
+                self.written = content.len();
+                self.ends_with_newline = delta.ends_with('\n');
+            }
+            Err(e) => self.error = Some(e),
+        }
+    }
+
+    /// 补上结尾换行，返回流式写入期间的错误（如管道被关闭）
+    fn finish(&mut self) -> std::io::Result<()> {
+        if let Some(e) = self.error.take() {
+            return Err(e);
+        }
+        if !self.ends_with_newline {
+            self.out.write_all(b"\n")?;
+        }
+        self.out.flush()
+    }
+}
+
+/// 等待首个分块时在 stderr 上显示的转圈动画
+fn spawn_spinner(done: Arc<AtomicBool>) -> std::thread::JoinHandle<()> {
+    std::thread::spawn(move || {
+        let frames = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+        let mut stderr = std::io::stderr();
+        for frame in frames.iter().cycle() {
+            if done.load(Ordering::SeqCst) {
+                break;
+            }
+            let _ = write!(stderr, "\r{} waiting for the model...", frame);
+            let _ = stderr.flush();
+            std::thread::sleep(Duration::from_millis(80));
+        }
+        let _ = write!(stderr, "\r\x1b[2K");
+        let _ = stderr.flush();
+    })
+}
@@ -0,0 +1,120 @@
+use crate::hostdetect;
+use crate::transport::{Endpoint, DEFAULT_HOST};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+/// ~/.config/ollama-cli/config.json 中的用户配置，缺省字段使用默认值
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Ollama 地址：`host:port`、`http://host:port/prefix` 或 `unix:///path/to/ollama.sock`
+    pub host: Option<String>,
+    /// 未设置 `host` 和 `OLLAMA_HOST` 且 localhost 连不上时，自动查找 WSL 与 Windows 之间的地址，默认开启
+    pub detect_host: Option<bool>,
+    /// 所有 API 路径的前缀，如反向代理下的 `/ollama`
+    pub base_path: Option<String>,
+    /// 生成会话标题和摘要使用的模型，未设置时使用当前模型
+    pub title_model: Option<String>,
+    /// 无法连接本机 Ollama 时自动启动 `ollama serve`
+    pub auto_start_server: bool,
+    /// 退出时保留由本程序启动的 `ollama serve`，默认一并关闭
+    pub keep_server_running: bool,
+    /// 启用长期记忆：从对话中提取事实并在之后的对话中引用
+    pub memory: bool,
+    /// 提取记忆使用的模型，未设置时使用当前模型
+    pub memory_model: Option<String>,
+    /// 显示的标签页及顺序，如 `["chat", "models", "downloads"]`，未设置时显示全部
+    pub tabs: Option<Vec<String>>,
+    /// 两次 Enter 间隔小于此值（毫秒）时忽略后一次，防止按键重复导致重复发送，默认 250，0 为关闭
+    pub send_debounce_ms: Option<u64>,
+    /// 在此时间（毫秒）内再次发送相同内容时忽略，默认 2000，0 为关闭
+    pub duplicate_send_window_ms: Option<u64>,
+    /// 回复因禁用短语被中止后，追加更明确的要求自动重试一次
+    pub retry_banned: bool,
+    /// 发送前检查提示词（无系统提示词的角色扮演、过长的段落、疑似密钥），有问题时先提示
+    pub prompt_lint: bool,
+    /// 单条回复的时间上限（秒），超出后停止生成并保留已生成的部分
+    pub max_reply_seconds: Option<u64>,
+    /// 单条回复的 token 上限
+    pub max_reply_tokens: Option<u64>,
+    /// 时间的显示格式（strftime），`iso` 为 RFC 3339，默认 `%Y-%m-%d %H:%M:%S`
+    pub time_format: Option<String>,
+    /// 只显示日期时的格式，默认 `%Y-%m-%d`
+    pub date_format: Option<String>,
+    /// 显示时间使用的时区：`local`（默认）、`utc` 或 `+08:00` 形式的偏移
+    pub timezone: Option<String>,
+    /// 对话回复交给后台守护进程生成，TUI 退出后继续，重新启动后用 `:attach` 取回（仅 Unix）
+    pub daemon: bool,
+    /// 回复完成或模型下载结束时 POST JSON 到此地址（如 ntfy 主题），失败时重试
+    pub webhook_url: Option<String>,
+    /// 回复中单独显示为可折叠通道的起止标记，未设置时折叠 `<think>...</think>`
+    pub reasoning_tags: Option<Vec<crate::channels::TagRule>>,
+    /// 在线搜索最多显示的结果数，默认 50
+    pub search_limit: Option<usize>,
+    /// 自动检测的结果，每次读取配置后只探测一次
+    #[serde(skip)]
+    detected: OnceLock<Option<hostdetect::Detected>>,
+}
+
+impl Config {
+    pub fn path() -> PathBuf {
+        config_dir().join("config.json")
+    }
+
+    pub fn load() -> Self {
+        std::fs::read_to_string(Self::path())
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn send_debounce(&self) -> Duration {
+        Duration::from_millis(self.send_debounce_ms.unwrap_or(250))
+    }
+
+    pub fn duplicate_send_window(&self) -> Duration {
+        Duration::from_millis(self.duplicate_send_window_ms.unwrap_or(2000))
+    }
+
+    pub fn search_limit(&self) -> usize {
+        self.search_limit.unwrap_or(50)
+    }
+
+    /// 配置文件中的 host 优先，其次是 OLLAMA_HOST 环境变量
+    pub fn endpoint(&self) -> Endpoint {
+        let host = self
+            .host
+            .clone()
+            .or_else(|| std::env::var("OLLAMA_HOST").ok())
+            .filter(|h| !h.trim().is_empty())
+            .or_else(|| self.detected_host().map(|d| d.host))
+            .unwrap_or_else(|| DEFAULT_HOST.to_string());
+        Endpoint::parse(&host, self.base_path.as_deref())
+    }
+
+    /// 没有手动指定地址时自动检测到的地址；连接地址和状态栏提示共用同一次探测的结果
+    pub fn detected_host(&self) -> Option<hostdetect::Detected> {
+        self.detected
+            .get_or_init(|| {
+                let configured = self
+                    .host
+                    .clone()
+                    .or_else(|| std::env::var("OLLAMA_HOST").ok())
+                    .is_some_and(|h| !h.trim().is_empty());
+                if configured || !self.detect_host.unwrap_or(true) {
+                    return None;
+                }
+                hostdetect::detect()
+            })
+            .clone()
+    }
+}
+
+pub fn config_dir() -> PathBuf {
+    let home = std::env::var("HOME").unwrap_or_else(|_| ".".to_string());
+    let config_dir = Path::new(&home).join(".config").join("ollama-cli");
+    let _ = std::fs::create_dir_all(&config_dir);
+    config_dir
+}
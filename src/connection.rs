@@ -0,0 +1,196 @@
+use crate::ollama::{ApiError, OllamaClient};
+use crate::transport::{self, Endpoint};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+// 已连接时的轮询间隔，断开后从 1 秒开始指数退避直到 MAX_BACKOFF
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum ConnectionStatus {
+    #[default]
+    Checking,
+    Connected(String),
+    Unreachable(String),
+    Reconnecting(u32),
+}
+
+/// 后台轮询 /api/version 维护与 Ollama 的连接状态
+#[derive(Clone, Default)]
+pub struct ConnectionMonitor {
+    status: Arc<Mutex<ConnectionStatus>>,
+    running: Arc<AtomicBool>,
+    wake: Arc<AtomicBool>,
+    needs_refresh: Arc<AtomicBool>,
+    restarted: Arc<AtomicBool>,
+    /// 请求失败后由 report_failure 设置，轮询线程据此把下一次成功视为重新连上
+    reported_failure: Arc<AtomicBool>,
+}
+
+impl ConnectionMonitor {
+    pub fn status(&self) -> ConnectionStatus {
+        self.status.lock().map(|s| s.clone()).unwrap_or_default()
+    }
+
+    pub fn is_connected(&self) -> bool {
+        matches!(self.status(), ConnectionStatus::Connected(_))
+    }
+
+    /// 启动轮询线程（只启动一次）
+    pub fn start(&self) {
+        if self.running.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        let monitor = self.clone();
+        std::thread::spawn(move || monitor.run());
+    }
+
+    /// 断线后重新连上时返回 true（只返回一次），用于重新加载模型列表
+    pub fn take_needs_refresh(&self) -> bool {
+        self.needs_refresh.swap(false, Ordering::SeqCst)
+    }
+
+    /// 检测到服务重启（曾经连上、断开后又连上，或版本号变化）时返回 true（只返回一次）
+    pub fn take_restarted(&self) -> bool {
+        self.restarted.swap(false, Ordering::SeqCst)
+    }
+
+    /// 请求因连接问题失败时立即标记为不可达，并让轮询线程马上重试
+    pub fn report_failure(&self, err: &anyhow::Error) {
+        if is_connection_error(err) {
+            self.set(ConnectionStatus::Unreachable(describe_error(err)));
+            self.reported_failure.store(true, Ordering::SeqCst);
+            self.wake.store(true, Ordering::SeqCst);
+        }
+    }
+
+    /// 服务刚启动时立即重新检查连接
+    pub fn wake(&self) {
+        self.set(ConnectionStatus::Reconnecting(0));
+        self.wake.store(true, Ordering::SeqCst);
+    }
+
+    fn set(&self, status: ConnectionStatus) {
+        if let Ok(mut current) = self.status.lock() {
+            *current = status;
+        }
+    }
+
+    fn run(&self) {
+        let mut failures = 0u32;
+        let mut last_version: Option<String> = None;
+        loop {
+            match OllamaClient::version() {
+                Ok(version) => {
+                    // 轮询间隔内完成的重启（如升级）只能从版本号看出来
+                    let upgraded = last_version.as_ref().is_some_and(|v| *v != version);
+                    // 两次轮询之间完成的重启只会表现为一次失败的请求
+                    let reported = self.reported_failure.swap(false, Ordering::SeqCst);
+                    if failures > 0 || upgraded || reported {
+                        self.needs_refresh.store(true, Ordering::SeqCst);
+                        if last_version.is_some() {
+                            self.restarted.store(true, Ordering::SeqCst);
+                        }
+                    }
+                    failures = 0;
+                    last_version = Some(version.clone());
+                    self.set(ConnectionStatus::Connected(version));
+                    self.sleep(POLL_INTERVAL);
+                }
+                Err(e) => {
+                    failures += 1;
+                    self.set(ConnectionStatus::Unreachable(describe_error(&e)));
+                    let backoff = Duration::from_secs(1 << failures.min(5)).min(MAX_BACKOFF);
+                    self.sleep(backoff);
+                    self.set(ConnectionStatus::Reconnecting(failures));
+                }
+            }
+        }
+    }
+
+    /// 可被 wake 打断的等待
+    fn sleep(&self, duration: Duration) {
+        let step = Duration::from_millis(100);
+        let mut waited = Duration::ZERO;
+        while waited < duration {
+            if self.wake.swap(false, Ordering::SeqCst) {
+                return;
+            }
+            std::thread::sleep(step);
+            waited += step;
+        }
+    }
+}
+
+/// 地址是否指向本机，只有本机地址才能由 TUI 启动服务
+pub fn is_local(endpoint: &Endpoint) -> bool {
+    match endpoint {
+        Endpoint::Unix { .. } => true,
+        Endpoint::Http(url) => {
+            let host = url.split("://").nth(1).unwrap_or(url);
+            ["localhost", "127.0.0.1", "[::1]", "0.0.0.0"]
+                .iter()
+                .any(|local| host.starts_with(local))
+        }
+    }
+}
+
+/// 连接被拒绝、超时或在流式响应中途断开
+pub fn is_connection_error(err: &anyhow::Error) -> bool {
+    err.chain().any(|cause| {
+        if let Some(e) = cause.downcast_ref::<reqwest::Error>() {
+            return e.is_connect() || e.is_timeout() || e.is_body();
+        }
+        if let Some(e) = cause.downcast_ref::<std::io::Error>() {
+            return matches!(
+                e.kind(),
+                std::io::ErrorKind::ConnectionRefused
+                    | std::io::ErrorKind::ConnectionReset
+                    | std::io::ErrorKind::NotFound
+                    | std::io::ErrorKind::TimedOut
+                    | std::io::ErrorKind::WouldBlock
+            );
+        }
+        false
+    })
+}
+
+/// 把底层错误转换为可操作的提示
+pub fn describe_error(err: &anyhow::Error) -> String {
+    if let Some(ApiError(message)) = err.downcast_ref::<ApiError>() {
+        if message.contains("not found") {
+            return format!("{} — pull it from the Search tab", message);
+        }
+        return message.clone();
+    }
+
+    let endpoint = transport::endpoint();
+    let timed_out = err.chain().any(|cause| {
+        cause
+            .downcast_ref::<reqwest::Error>()
+            .is_some_and(|e| e.is_timeout())
+            || cause.downcast_ref::<std::io::Error>().is_some_and(|e| {
+                matches!(
+                    e.kind(),
+                    std::io::ErrorKind::TimedOut | std::io::ErrorKind::WouldBlock
+                )
+            })
+    });
+
+    if timed_out {
+        format!("Ollama at {} is not responding (timed out)", endpoint)
+    } else if is_connection_error(err) {
+        if is_local(&endpoint) {
+            "Ollama not running — press S to start `ollama serve`".to_string()
+        } else {
+            format!(
+                "Cannot reach Ollama at {} — check the server and `host` in config.json",
+                endpoint
+            )
+        }
+    } else {
+        err.to_string()
+    }
+}
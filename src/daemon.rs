@@ -0,0 +1,546 @@
+//! 可选的后台守护进程：代替 TUI 执行对话生成，TUI 退出（如 SSH 断开）后生成继续，
+//! 重新启动 TUI 后可以重新连接并取回缓冲的输出。通过 `~/.config/ollama-cli/daemon.sock` 通信，
+//! 每个连接发送一行 JSON 请求，之后按行返回 JSON 事件。
+
+use crate::config;
+use crate::ollama::{ApiError, ChatMessage, Completion, GenerationStats, ModelOptions};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// 没有任务且这么久没有连接时守护进程自动退出
+#[cfg(unix)]
+const IDLE_EXIT: std::time::Duration = std::time::Duration::from_secs(30 * 60);
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Request {
+    /// 开始生成，随后和 attach 一样返回流式事件
+    Start {
+        model: String,
+        messages: Vec<ChatMessage>,
+        #[serde(default)]
+        options: Option<ModelOptions>,
+        #[serde(default)]
+        session: Option<String>,
+    },
+    Attach {
+        job: u64,
+    },
+    Cancel {
+        job: u64,
+    },
+    /// 客户端已取回结果，丢弃任务
+    Forget {
+        job: u64,
+    },
+    List,
+    Shutdown,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Event {
+    Started {
+        job: u64,
+    },
+    /// 新增的文本；重新连接时第一条包含目前为止的全部内容
+    Chunk {
+        content: String,
+    },
+    Done {
+        #[serde(default)]
+        stats: GenerationStats,
+        #[serde(default)]
+        cancelled: bool,
+    },
+    Error {
+        message: String,
+    },
+    Jobs {
+        jobs: Vec<JobInfo>,
+    },
+    Ok,
+}
+
+/// 守护进程中的一次生成，`messages` 为发送时的完整上下文，用于重新连接时恢复对话
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobInfo {
+    pub id: u64,
+    pub model: String,
+    #[serde(default)]
+    pub session: Option<String>,
+    pub messages: Vec<ChatMessage>,
+    pub started_at: i64,
+    pub finished: bool,
+}
+
+pub fn socket_path() -> PathBuf {
+    config::config_dir().join("daemon.sock")
+}
+
+#[cfg(unix)]
+pub use unix::*;
+
+#[cfg(not(unix))]
+pub use unsupported::*;
+
+#[cfg(unix)]
+mod unix {
+    use super::*;
+    use crate::ollama::OllamaClient;
+    use std::collections::BTreeMap;
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::{UnixListener, UnixStream};
+    use std::os::unix::process::CommandExt;
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+
+    #[derive(Debug)]
+    struct Job {
+        info: JobInfo,
+        content: String,
+        stats: GenerationStats,
+        cancelled: bool,
+        error: Option<String>,
+    }
+
+    #[derive(Default)]
+    struct Jobs {
+        next_id: u64,
+        jobs: BTreeMap<u64, Job>,
+        last_activity: Option<Instant>,
+    }
+
+    type SharedJobs = Arc<Mutex<Jobs>>;
+
+    /// `ollama-cli daemon`：在前台运行守护进程，直到收到 shutdown 或长时间空闲
+    pub fn serve() -> anyhow::Result<()> {
+        let path = socket_path();
+        if UnixStream::connect(&path).is_ok() {
+            anyhow::bail!("daemon is already running ({})", path.display());
+        }
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixListener::bind(&path)?;
+        let jobs = SharedJobs::default();
+
+        let watchdog = jobs.clone();
+        std::thread::spawn(move || loop {
+            std::thread::sleep(Duration::from_secs(30));
+            let idle = watchdog.lock().is_ok_and(|jobs| {
+                jobs.jobs.is_empty()
+                    && jobs
+                        .last_activity
+                        .is_none_or(|last| last.elapsed() > IDLE_EXIT)
+            });
+            if idle {
+                let _ = std::fs::remove_file(socket_path());
+                std::process::exit(0);
+            }
+        });
+
+        for stream in listener.incoming() {
+            let Ok(stream) = stream else {
+                continue;
+            };
+            if let Ok(mut jobs) = jobs.lock() {
+                jobs.last_activity = Some(Instant::now());
+            }
+            let jobs = jobs.clone();
+            std::thread::spawn(move || {
+                let _ = handle(stream, &jobs);
+            });
+        }
+        Ok(())
+    }
+
+    fn send(stream: &mut UnixStream, event: &Event) -> std::io::Result<()> {
+        let line = serde_json::to_string(event)?;
+        writeln!(stream, "{}", line)?;
+        stream.flush()
+    }
+
+    fn handle(mut stream: UnixStream, jobs: &SharedJobs) -> anyhow::Result<()> {
+        let mut line = String::new();
+        BufReader::new(stream.try_clone()?).read_line(&mut line)?;
+        let request: Request = match serde_json::from_str(&line) {
+            Ok(request) => request,
+            Err(e) => {
+                send(
+                    &mut stream,
+                    &Event::Error {
+                        message: format!("invalid request: {}", e),
+                    },
+                )?;
+                return Ok(());
+            }
+        };
+
+        match request {
+            Request::Start {
+                model,
+                messages,
+                options,
+                session,
+            } => {
+                let id = start(jobs, model, messages, options, session);
+                send(&mut stream, &Event::Started { job: id })?;
+                stream_job(&mut stream, jobs, id)?;
+            }
+            Request::Attach { job } => stream_job(&mut stream, jobs, job)?,
+            Request::Cancel { job } => {
+                if let Ok(mut jobs) = jobs.lock() {
+                    if let Some(job) = jobs.jobs.get_mut(&job) {
+                        job.cancelled = true;
+                    }
+                }
+                send(&mut stream, &Event::Ok)?;
+            }
+            Request::Forget { job } => {
+                if let Ok(mut jobs) = jobs.lock() {
+                    if jobs.jobs.get(&job).is_some_and(|j| j.info.finished) {
+                        jobs.jobs.remove(&job);
+                    }
+                }
+                send(&mut stream, &Event::Ok)?;
+            }
+            Request::List => {
+                let list = jobs
+                    .lock()
+                    .map(|jobs| jobs.jobs.values().map(|j| j.info.clone()).collect())
+                    .unwrap_or_default();
+                send(&mut stream, &Event::Jobs { jobs: list })?;
+            }
+            Request::Shutdown => {
+                send(&mut stream, &Event::Ok)?;
+                let _ = std::fs::remove_file(socket_path());
+                std::process::exit(0);
+            }
+        }
+        Ok(())
+    }
+
+    fn start(
+        jobs: &SharedJobs,
+        model: String,
+        messages: Vec<ChatMessage>,
+        options: Option<ModelOptions>,
+        session: Option<String>,
+    ) -> u64 {
+        let id = {
+            let Ok(mut jobs) = jobs.lock() else {
+                return 0;
+            };
+            jobs.next_id += 1;
+            let id = jobs.next_id;
+            jobs.jobs.insert(
+                id,
+                Job {
+                    info: JobInfo {
+                        id,
+                        model: model.clone(),
+                        session,
+                        messages: messages.clone(),
+                        started_at: chrono::Local::now().timestamp(),
+                        finished: false,
+                    },
+                    content: String::new(),
+                    stats: GenerationStats::default(),
+                    cancelled: false,
+                    error: None,
+                },
+            );
+            id
+        };
+
+        let for_chunks = jobs.clone();
+        let handle = OllamaClient::chat_streaming(model, messages, options, None, move |content| {
+            let Ok(mut jobs) = for_chunks.lock() else {
+                return false;
+            };
+            match jobs.jobs.get_mut(&id) {
+                Some(job) => {
+                    job.content = content;
+                    !job.cancelled
+                }
+                None => false,
+            }
+        });
+
+        let for_result = jobs.clone();
+        std::thread::spawn(move || {
+            let result = handle.join();
+            let Ok(mut jobs) = for_result.lock() else {
+                return;
+            };
+            jobs.last_activity = Some(Instant::now());
+            let Some(job) = jobs.jobs.get_mut(&id) else {
+                return;
+            };
+            match result {
+                Ok(Ok(completion)) => {
+                    job.content = completion.content;
+                    job.stats = completion.stats;
+                    job.cancelled |= completion.cancelled;
+                }
+                Ok(Err(e)) => job.error = Some(e.to_string()),
+                Err(_) => job.error = Some("generation thread panicked".to_string()),
+            }
+            job.info.finished = true;
+            // 取消的任务没有人再取回；客户端发来的 Forget 早于任务结束时不会生效，在这里清理
+            if job.cancelled {
+                jobs.jobs.remove(&id);
+            }
+        });
+        id
+    }
+
+    /// 把任务的输出写给客户端，直到结束或客户端断开（断开后任务继续）
+    fn stream_job(stream: &mut UnixStream, jobs: &SharedJobs, id: u64) -> anyhow::Result<()> {
+        let mut sent = 0;
+        loop {
+            let progress = jobs.lock().ok().and_then(|j| {
+                j.jobs.get(&id).map(|job| {
+                    let delta = job.content.get(sent..).unwrap_or_default().to_string();
+                    let finished = job.info.finished.then(|| match &job.error {
+                        Some(message) => Event::Error {
+                            message: message.clone(),
+                        },
+                        None => Event::Done {
+                            stats: job.stats.clone(),
+                            cancelled: job.cancelled,
+                        },
+                    });
+                    (delta, finished)
+                })
+            });
+            let Some((delta, finished)) = progress else {
+                send(
+                    stream,
+                    &Event::Error {
+                        message: format!("no such job: {}", id),
+                    },
+                )?;
+                return Ok(());
+            };
+            if !delta.is_empty() {
+                sent += delta.len();
+                send(stream, &Event::Chunk { content: delta })?;
+            }
+            if let Some(event) = finished {
+                send(stream, &event)?;
+                return Ok(());
+            }
+            std::thread::sleep(Duration::from_millis(50));
+        }
+    }
+
+    fn request(request: &Request) -> anyhow::Result<BufReader<UnixStream>> {
+        let mut stream = UnixStream::connect(socket_path())
+            .map_err(|e| anyhow::anyhow!("daemon is not running: {}", e))?;
+        writeln!(stream, "{}", serde_json::to_string(request)?)?;
+        Ok(BufReader::new(stream))
+    }
+
+    fn read_event(reader: &mut BufReader<UnixStream>) -> anyhow::Result<Event> {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            anyhow::bail!("daemon closed the connection");
+        }
+        Ok(serde_json::from_str(&line)?)
+    }
+
+    pub fn is_running() -> bool {
+        UnixStream::connect(socket_path()).is_ok()
+    }
+
+    /// 在后台启动守护进程（新的进程组，终端关闭时不会一起退出），等待套接字可用
+    pub fn spawn() -> anyhow::Result<()> {
+        if is_running() {
+            return Ok(());
+        }
+        std::process::Command::new(std::env::current_exe()?)
+            .arg("daemon")
+            .stdin(std::process::Stdio::null())
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .process_group(0)
+            .spawn()?;
+        for _ in 0..40 {
+            if is_running() {
+                return Ok(());
+            }
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        anyhow::bail!("daemon did not start")
+    }
+
+    pub fn list() -> anyhow::Result<Vec<JobInfo>> {
+        match read_event(&mut request(&Request::List)?)? {
+            Event::Jobs { jobs } => Ok(jobs),
+            Event::Error { message } => Err(ApiError(message).into()),
+            _ => anyhow::bail!("unexpected reply from daemon"),
+        }
+    }
+
+    pub fn forget(job: u64) {
+        if let Ok(mut reader) = request(&Request::Forget { job }) {
+            let _ = read_event(&mut reader);
+        }
+    }
+
+    pub fn shutdown() -> anyhow::Result<()> {
+        read_event(&mut request(&Request::Shutdown)?)?;
+        Ok(())
+    }
+
+    /// 和 `OllamaClient::chat_streaming` 相同的接口，但由守护进程生成；
+    /// 未运行时先启动守护进程，`callback` 返回 false 或到了 `deadline` 时取消任务
+    pub fn chat_streaming<F>(
+        model: String,
+        messages: Vec<ChatMessage>,
+        options: Option<ModelOptions>,
+        session: Option<String>,
+        deadline: Option<Instant>,
+        callback: F,
+    ) -> std::thread::JoinHandle<anyhow::Result<Completion>>
+    where
+        F: Fn(String) -> bool + Send + 'static,
+    {
+        std::thread::spawn(move || {
+            spawn()?;
+            let mut reader = request(&Request::Start {
+                model,
+                messages,
+                options,
+                session,
+            })?;
+            let job = match read_event(&mut reader)? {
+                Event::Started { job } => job,
+                Event::Error { message } => return Err(ApiError(message).into()),
+                _ => anyhow::bail!("unexpected reply from daemon"),
+            };
+            follow(reader, job, deadline, callback)
+        })
+    }
+
+    /// 重新连接到仍在运行或已结束但未取回的任务
+    pub fn attach<F>(job: u64, callback: F) -> std::thread::JoinHandle<anyhow::Result<Completion>>
+    where
+        F: Fn(String) -> bool + Send + 'static,
+    {
+        std::thread::spawn(move || follow(request(&Request::Attach { job })?, job, None, callback))
+    }
+
+    fn follow<F>(
+        mut reader: BufReader<UnixStream>,
+        job: u64,
+        deadline: Option<Instant>,
+        callback: F,
+    ) -> anyhow::Result<Completion>
+    where
+        F: Fn(String) -> bool,
+    {
+        let cancel = |content: String| {
+            if let Ok(mut reader) = request(&Request::Cancel { job }) {
+                let _ = read_event(&mut reader);
+            }
+            forget(job);
+            Completion {
+                content,
+                cancelled: true,
+                ..Completion::default()
+            }
+        };
+        let mut content = String::new();
+        loop {
+            if let Some(deadline) = deadline {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                if remaining.is_zero() {
+                    return Ok(cancel(content));
+                }
+                reader.get_ref().set_read_timeout(Some(remaining))?;
+            }
+            let event = match read_event(&mut reader) {
+                Err(_) if deadline.is_some_and(|d| Instant::now() >= d) => {
+                    return Ok(cancel(content));
+                }
+                event => event?,
+            };
+            match event {
+                Event::Chunk { content: delta } => {
+                    content.push_str(&delta);
+                    if !callback(content.clone()) {
+                        return Ok(cancel(content));
+                    }
+                }
+                Event::Done { stats, cancelled } => {
+                    forget(job);
+                    return Ok(Completion {
+                        content,
+                        stats,
+                        cancelled,
+                    });
+                }
+                Event::Error { message } => {
+                    forget(job);
+                    return Err(ApiError(message).into());
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(not(unix))]
+mod unsupported {
+    use super::*;
+
+    fn unsupported() -> anyhow::Error {
+        anyhow::anyhow!(
+            "daemon mode needs Unix domain sockets and is not available on this platform"
+        )
+    }
+
+    pub fn serve() -> anyhow::Result<()> {
+        Err(unsupported())
+    }
+
+    pub fn is_running() -> bool {
+        false
+    }
+
+    pub fn spawn() -> anyhow::Result<()> {
+        Err(unsupported())
+    }
+
+    pub fn list() -> anyhow::Result<Vec<JobInfo>> {
+        Err(unsupported())
+    }
+
+    pub fn forget(_job: u64) {}
+
+    pub fn shutdown() -> anyhow::Result<()> {
+        Err(unsupported())
+    }
+
+    pub fn chat_streaming<F>(
+        _model: String,
+        _messages: Vec<ChatMessage>,
+        _options: Option<ModelOptions>,
+        _session: Option<String>,
+        _deadline: Option<std::time::Instant>,
+        _callback: F,
+    ) -> std::thread::JoinHandle<anyhow::Result<Completion>>
+    where
+        F: Fn(String) -> bool + Send + 'static,
+    {
+        std::thread::spawn(|| Err(unsupported()))
+    }
+
+    pub fn attach<F>(_job: u64, _callback: F) -> std::thread::JoinHandle<anyhow::Result<Completion>>
+    where
+        F: Fn(String) -> bool + Send + 'static,
+    {
+        std::thread::spawn(|| Err(unsupported()))
+    }
+}
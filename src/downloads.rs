@@ -0,0 +1,398 @@
+use crate::ollama::{ApiError, OllamaClient};
+use crate::search::ModelSearch;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+const MAX_RETRIES: u32 = 5;
+
+/// 分组 id → 层的 digest → (completed, total)
+type GroupLayers = HashMap<u64, HashMap<String, (u64, u64)>>;
+
+/// `next_batch` 取出的一批传输：(id, model, group)
+type Batch = Vec<(u64, String, Option<u64>)>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TransferStatus {
+    Queued,
+    Checking,
+    Downloading,
+    Retrying(u32),
+    Completed,
+    Failed(String),
+    Cancelled,
+}
+
+impl TransferStatus {
+    pub fn is_finished(&self) -> bool {
+        matches!(
+            self,
+            TransferStatus::Completed | TransferStatus::Failed(_) | TransferStatus::Cancelled
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Transfer {
+    pub id: u64,
+    pub model: String,
+    pub status: TransferStatus,
+    pub detail: String,
+    pub completed: u64,
+    pub total: u64,
+    pub expected_size: Option<u64>,
+    /// 一起选择的多个标签属于同一分组，分组内的传输并行拉取
+    pub group: Option<u64>,
+}
+
+impl Transfer {
+    pub fn percent(&self) -> u16 {
+        if self.total == 0 {
+            0
+        } else {
+            ((self.completed as f64 / self.total as f64) * 100.0).min(100.0) as u16
+        }
+    }
+}
+
+/// 模型下载队列：后台单线程按顺序拉取（同一分组的标签并行拉取），网络错误时自动续传重试
+#[derive(Clone, Default)]
+pub struct DownloadManager {
+    transfers: Arc<Mutex<Vec<Transfer>>>,
+    // 每个分组各层的 (completed, total)，按 digest 去重，共享的层只计一次
+    group_layers: Arc<Mutex<GroupLayers>>,
+    worker_running: Arc<AtomicBool>,
+    next_id: Arc<AtomicU64>,
+    needs_refresh: Arc<AtomicBool>,
+}
+
+impl DownloadManager {
+    pub fn snapshot(&self) -> Vec<Transfer> {
+        self.transfers.lock().map(|t| t.clone()).unwrap_or_default()
+    }
+
+    pub fn enqueue(&self, model: &str) {
+        self.push(model, None);
+        self.ensure_worker();
+    }
+
+    /// 同一模型的多个标签作为一组并行拉取，Ollama 只会下载一次共享的层
+    pub fn enqueue_group(&self, models: &[String]) {
+        let group = self.next_id.fetch_add(1, Ordering::SeqCst);
+        for model in models {
+            self.push(model, Some(group));
+        }
+        self.ensure_worker();
+    }
+
+    /// 分组的合并进度 (completed, total)，共享的层只计一次
+    pub fn group_progress(&self, group: u64) -> Option<(u64, u64)> {
+        let groups = self.group_layers.lock().ok()?;
+        let layers = groups.get(&group)?;
+        Some((
+            layers.values().map(|(c, _)| *c).sum(),
+            layers.values().map(|(_, t)| *t).sum(),
+        ))
+    }
+
+    fn push(&self, model: &str, group: Option<u64>) {
+        let model = model.trim().to_string();
+        if model.is_empty() {
+            return;
+        }
+        if let Ok(mut transfers) = self.transfers.lock() {
+            let pending = transfers
+                .iter()
+                .any(|t| t.model == model && !t.status.is_finished());
+            if pending {
+                return;
+            }
+            transfers.push(Transfer {
+                id: self.next_id.fetch_add(1, Ordering::SeqCst),
+                model,
+                status: TransferStatus::Queued,
+                detail: String::new(),
+                completed: 0,
+                total: 0,
+                expected_size: None,
+                group,
+            });
+        }
+    }
+
+    pub fn cancel(&self, id: u64) {
+        self.update(id, |t| {
+            if !t.status.is_finished() {
+                t.status = TransferStatus::Cancelled;
+            }
+        });
+    }
+
+    pub fn retry(&self, id: u64) {
+        self.update(id, |t| {
+            if matches!(
+                t.status,
+                TransferStatus::Failed(_) | TransferStatus::Cancelled
+            ) {
+                t.status = TransferStatus::Queued;
+                t.detail.clear();
+            }
+        });
+        self.ensure_worker();
+    }
+
+    pub fn clear_finished(&self) {
+        if let Ok(mut transfers) = self.transfers.lock() {
+            transfers.retain(|t| !t.status.is_finished());
+            if let Ok(mut groups) = self.group_layers.lock() {
+                groups.retain(|group, _| transfers.iter().any(|t| t.group == Some(*group)));
+            }
+        }
+    }
+
+    /// 有下载完成后返回 true（只返回一次），用于刷新已安装模型列表
+    pub fn take_needs_refresh(&self) -> bool {
+        self.needs_refresh.swap(false, Ordering::SeqCst)
+    }
+
+    fn update<F: FnOnce(&mut Transfer)>(&self, id: u64, f: F) {
+        if let Ok(mut transfers) = self.transfers.lock() {
+            if let Some(t) = transfers.iter_mut().find(|t| t.id == id) {
+                f(t);
+            }
+        }
+    }
+
+    fn status_of(&self, id: u64) -> Option<TransferStatus> {
+        let transfers = self.transfers.lock().ok()?;
+        let status = transfers
+            .iter()
+            .find(|t| t.id == id)
+            .map(|t| t.status.clone());
+        status
+    }
+
+    /// 取出下一个排队的传输；属于分组时连同分组内其他排队的传输一起取出
+    fn next_batch(&self) -> Option<Batch> {
+        let mut transfers = self.transfers.lock().ok()?;
+        let first = transfers
+            .iter()
+            .position(|t| t.status == TransferStatus::Queued)?;
+        let group = transfers[first].group;
+        let mut batch = Vec::new();
+        for (i, t) in transfers.iter_mut().enumerate() {
+            let claimed = i == first || (group.is_some() && t.group == group);
+            if claimed && t.status == TransferStatus::Queued {
+                t.status = TransferStatus::Checking;
+                batch.push((t.id, t.model.clone(), t.group));
+            }
+        }
+        Some(batch)
+    }
+
+    fn ensure_worker(&self) {
+        if self
+            .worker_running
+            .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+            .is_err()
+        {
+            return;
+        }
+        let manager = self.clone();
+        std::thread::spawn(move || manager.run_worker());
+    }
+
+    fn run_worker(&self) {
+        loop {
+            while let Some(batch) = self.next_batch() {
+                if let Err(message) = self.preflight(&batch) {
+                    for (id, _, _) in &batch {
+                        self.update(*id, |t| {
+                            if t.status == TransferStatus::Checking {
+                                t.status = TransferStatus::Failed(message.clone());
+                            }
+                        });
+                    }
+                    continue;
+                }
+                std::thread::scope(|scope| {
+                    for (id, model, group) in &batch {
+                        scope.spawn(move || self.process(*id, model, *group));
+                    }
+                });
+            }
+            self.worker_running.store(false, Ordering::SeqCst);
+
+            // 退出前再检查一次，避免刚入队的任务无人处理
+            let has_queued = self
+                .transfers
+                .lock()
+                .map(|t| t.iter().any(|t| t.status == TransferStatus::Queued))
+                .unwrap_or(false);
+            if !has_queued
+                || self
+                    .worker_running
+                    .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+                    .is_err()
+            {
+                return;
+            }
+        }
+    }
+
+    fn process(&self, id: u64, model: &str, group: Option<u64>) {
+        // 每层的 (completed, total)，跨重试保留，服务端会从断点续传
+        let mut layers: HashMap<String, (u64, u64)> = HashMap::new();
+        let mut attempt = 0;
+
+        loop {
+            if self.status_of(id) == Some(TransferStatus::Cancelled) {
+                return;
+            }
+            self.update(id, |t| t.status = TransferStatus::Downloading);
+
+            let result = OllamaClient::pull_model(model, |progress| {
+                if let (Some(digest), Some(total)) = (&progress.digest, progress.total) {
+                    let layer = (progress.completed.unwrap_or(0), total);
+                    layers.insert(digest.clone(), layer);
+                    if let (Some(group), Ok(mut groups)) = (group, self.group_layers.lock()) {
+                        groups
+                            .entry(group)
+                            .or_default()
+                            .insert(digest.clone(), layer);
+                    }
+                }
+                let completed: u64 = layers.values().map(|(c, _)| *c).sum();
+                let total: u64 = layers.values().map(|(_, t)| *t).sum();
+
+                let mut keep_going = true;
+                self.update(id, |t| {
+                    if t.status == TransferStatus::Cancelled {
+                        keep_going = false;
+                        return;
+                    }
+                    t.status = TransferStatus::Downloading;
+                    t.detail = progress.status.clone();
+                    t.completed = completed;
+                    t.total = total;
+                });
+                keep_going
+            });
+
+            match result {
+                Ok(()) => {
+                    if self.status_of(id) != Some(TransferStatus::Cancelled) {
+                        self.update(id, |t| {
+                            t.status = TransferStatus::Completed;
+                            t.detail = "success".to_string();
+                            t.completed = t.total;
+                        });
+                        self.needs_refresh.store(true, Ordering::SeqCst);
+                    }
+                    return;
+                }
+                Err(e) if e.downcast_ref::<ApiError>().is_some() => {
+                    self.update(id, |t| t.status = TransferStatus::Failed(e.to_string()));
+                    return;
+                }
+                Err(e) => {
+                    attempt += 1;
+                    if attempt > MAX_RETRIES {
+                        self.update(id, |t| t.status = TransferStatus::Failed(e.to_string()));
+                        return;
+                    }
+                    self.update(id, |t| {
+                        t.status = TransferStatus::Retrying(attempt);
+                        t.detail = e.to_string();
+                    });
+                    // 指数退避，期间仍可取消
+                    for _ in 0..(2u64 << attempt) {
+                        if self.status_of(id) == Some(TransferStatus::Cancelled) {
+                            return;
+                        }
+                        std::thread::sleep(Duration::from_secs(1));
+                    }
+                }
+            }
+        }
+    }
+
+    /// 下载前检查磁盘空间：registry manifest 给出各层大小，与模型目录所在磁盘的可用空间比较。
+    /// 分组内的标签一起检查，共享的层按 digest 只计一次；已下载或下载了一部分的层扣除已占用的大小
+    fn preflight(&self, batch: &[(u64, String, Option<u64>)]) -> Result<(), String> {
+        let search = ModelSearch::new();
+        let mut blobs: HashMap<String, u64> = HashMap::new();
+        for (id, model, _) in batch {
+            self.update(*id, |t| t.detail = "checking disk space".to_string());
+            match search.manifest_blobs(model) {
+                Ok(layers) => {
+                    let size = layers.iter().map(|(_, size)| size).sum();
+                    self.update(*id, |t| t.expected_size = Some(size));
+                    blobs.extend(layers);
+                }
+                // 无法获取 manifest 时不阻止下载，交给 Ollama 报错
+                Err(_) => self.update(*id, |t| t.detail = "size unknown".to_string()),
+            }
+        }
+        let size: u64 = blobs
+            .iter()
+            .map(|(digest, size)| size.saturating_sub(downloaded_size(digest, *size)))
+            .sum();
+
+        if let Some(available) = available_space() {
+            if size > available {
+                return Err(format!(
+                    "needs {:.1} GB but only {:.1} GB free",
+                    size as f64 / 1_073_741_824.0,
+                    available as f64 / 1_073_741_824.0
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Ollama 存放模型的目录，遵循 OLLAMA_MODELS 环境变量
+pub fn models_dir() -> PathBuf {
+    if let Ok(dir) = std::env::var("OLLAMA_MODELS") {
+        return PathBuf::from(dir);
+    }
+    dirs::home_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join(".ollama")
+        .join("models")
+}
+
+/// 层在本地已占用的字节数：完整的 blob 按全部大小计，续传中的 `-partial` 文件按实际分配的空间计
+/// （Ollama 会预先把它扩展到完整大小，但未写入的部分不占用磁盘）
+fn downloaded_size(digest: &str, size: u64) -> u64 {
+    let blob = models_dir().join("blobs").join(digest.replace(':', "-"));
+    if blob.exists() {
+        return size;
+    }
+    let mut partial = blob.into_os_string();
+    partial.push("-partial");
+    std::fs::metadata(partial)
+        .map(|m| allocated_size(&m).min(size))
+        .unwrap_or(0)
+}
+
+#[cfg(unix)]
+fn allocated_size(metadata: &std::fs::Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    metadata.blocks() * 512
+}
+
+#[cfg(not(unix))]
+fn allocated_size(metadata: &std::fs::Metadata) -> u64 {
+    metadata.len()
+}
+
+fn available_space() -> Option<u64> {
+    let dir = models_dir();
+    // 目录可能尚未创建，向上查找第一个存在的祖先目录
+    let existing = dir.ancestors().find(|p| p.exists())?;
+    fs2::available_space(existing).ok()
+}
@@ -0,0 +1,299 @@
+use crate::daemon;
+use crate::ollama::{ApiError, ChatMessage, Completion, ModelOptions, OllamaClient};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// 单条回复的时间和长度上限，先到者为准；长度上限作为 `num_predict` 交给 Ollama，按实际生成的 token 计
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ReplyBudget {
+    pub seconds: Option<u64>,
+    pub tokens: Option<u64>,
+}
+
+impl ReplyBudget {
+    pub fn is_unlimited(&self) -> bool {
+        self.seconds.is_none() && self.tokens.is_none()
+    }
+
+    /// 超出时间上限时返回原因
+    pub fn exceeded(&self, elapsed: Duration) -> Option<String> {
+        self.seconds
+            .filter(|s| elapsed.as_secs() >= *s)
+            .map(|s| format!("time budget of {}s", s))
+    }
+
+    /// 生成的 token 数达到上限时返回原因
+    pub fn tokens_exceeded(&self, eval_count: Option<u64>) -> Option<String> {
+        let limit = self.tokens?;
+        (eval_count? >= limit).then(|| format!("token budget of {}", limit))
+    }
+
+    /// 把 token 上限写入请求参数，已设置更小的 `num_predict` 时保留
+    pub fn options(&self, options: Option<ModelOptions>) -> Option<ModelOptions> {
+        let Some(limit) = self.tokens else {
+            return options;
+        };
+        let limit = i32::try_from(limit).unwrap_or(i32::MAX);
+        let mut options = options.unwrap_or_default();
+        options.num_predict = Some(
+            options
+                .num_predict
+                .filter(|n| *n > 0)
+                .map_or(limit, |n| n.min(limit)),
+        );
+        Some(options)
+    }
+
+    /// 解析 `30s`、`500t`、`30s 500t` 或 `off`
+    pub fn parse(text: &str) -> Option<Self> {
+        let mut budget = Self::default();
+        for part in text.split_whitespace() {
+            if part == "off" {
+                return Some(Self::default());
+            } else if let Some(seconds) = part.strip_suffix('s') {
+                budget.seconds = Some(seconds.parse().ok().filter(|s| *s > 0)?);
+            } else if let Some(tokens) = part.strip_suffix('t') {
+                budget.tokens = Some(tokens.parse().ok().filter(|t| *t > 0)?);
+            } else {
+                return None;
+            }
+        }
+        Some(budget)
+    }
+
+    pub fn label(&self) -> String {
+        match (self.seconds, self.tokens) {
+            (Some(s), Some(t)) => format!("{}s/{}t", s, t),
+            (Some(s), None) => format!("{}s", s),
+            (None, Some(t)) => format!("{}t", t),
+            (None, None) => "off".to_string(),
+        }
+    }
+}
+
+/// 在流式回调中计时，回复结束后按服务端统计的 token 数检查长度，超出预算时记录原因
+pub struct BudgetTracker {
+    budget: ReplyBudget,
+    started: Instant,
+    stopped: Mutex<Option<String>>,
+}
+
+impl BudgetTracker {
+    pub fn new(budget: ReplyBudget) -> Self {
+        Self {
+            budget,
+            started: Instant::now(),
+            stopped: Mutex::new(None),
+        }
+    }
+
+    /// 每收到一个分块调用一次；超出时间上限时返回原因
+    pub fn tick(&self) -> Option<String> {
+        let mut stopped = self.stopped.lock().ok()?;
+        if stopped.is_none() {
+            *stopped = self.budget.exceeded(self.started.elapsed());
+        }
+        stopped.clone()
+    }
+
+    pub fn stopped(&self) -> Option<String> {
+        self.stopped.lock().ok()?.clone()
+    }
+
+    /// 时间上限对应的时刻：请求在此时断开连接，服务端卡住不发数据时也能按时结束
+    pub fn deadline(&self) -> Option<Instant> {
+        self.budget
+            .seconds
+            .map(|s| self.started + Duration::from_secs(s))
+    }
+
+    /// 带上 token 上限的请求参数
+    pub fn options(&self, options: Option<ModelOptions>) -> Option<ModelOptions> {
+        self.budget.options(options)
+    }
+
+    /// 等待生成结束，记录因到时间断开或达到 token 上限而停止的原因
+    pub fn watch(
+        self: &Arc<Self>,
+        handle: JoinHandle<anyhow::Result<Completion>>,
+    ) -> JoinHandle<anyhow::Result<Completion>> {
+        let tracker = self.clone();
+        std::thread::spawn(move || {
+            let result = handle
+                .join()
+                .unwrap_or_else(|panic| std::panic::resume_unwind(panic));
+            if let (Ok(completion), Ok(mut stopped)) = (&result, tracker.stopped.lock()) {
+                if stopped.is_none() {
+                    *stopped = tracker
+                        .budget
+                        .tokens_exceeded(completion.stats.eval_count)
+                        .or_else(|| {
+                            completion
+                                .cancelled
+                                .then(|| tracker.budget.exceeded(tracker.started.elapsed()))
+                                .flatten()
+                        });
+                }
+            }
+            result
+        })
+    }
+}
+
+/// 回复由谁生成：直接请求 Ollama，或交给守护进程（`session` 用于之后重新接上）
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Backend {
+    #[default]
+    Direct,
+    Daemon {
+        session: Option<String>,
+    },
+}
+
+/// 发送给 `/api/chat` 的消息：系统提示词加上对话历史
+pub fn request_messages(
+    system: &str,
+    history: impl IntoIterator<Item = ChatMessage>,
+) -> Vec<ChatMessage> {
+    let mut messages = Vec::new();
+    if !system.is_empty() {
+        messages.push(ChatMessage {
+            role: "system".to_string(),
+            content: system.to_string(),
+        });
+    }
+    messages.extend(history);
+    messages
+}
+
+/// 流式生成一条回复，`callback` 收到目前为止的完整回复，返回 false 时停止生成，到 `deadline` 时断开。
+/// TUI、纯文本模式和 `--json-repl` 都经由这里发出请求
+pub fn stream_chat<F>(
+    backend: &Backend,
+    model: String,
+    messages: Vec<ChatMessage>,
+    options: Option<ModelOptions>,
+    deadline: Option<Instant>,
+    callback: F,
+) -> JoinHandle<anyhow::Result<Completion>>
+where
+    F: Fn(String) -> bool + Send + 'static,
+{
+    match backend {
+        Backend::Direct => {
+            OllamaClient::chat_streaming(model, messages, options, deadline, callback)
+        }
+        Backend::Daemon { session } => daemon::chat_streaming(
+            model,
+            messages,
+            options,
+            session.clone(),
+            deadline,
+            callback,
+        ),
+    }
+}
+
+/// 与界面无关的对话核心：保存模型、参数和历史，供纯文本模式和 `--json-repl` 使用
+#[derive(Debug, Clone, Default)]
+pub struct ChatEngine {
+    pub model: Option<String>,
+    pub system: String,
+    pub options: Option<ModelOptions>,
+    pub budget: ReplyBudget,
+    pub backend: Backend,
+    history: Vec<ChatMessage>,
+}
+
+impl ChatEngine {
+    pub fn new(
+        model: Option<String>,
+        system: String,
+        options: Option<ModelOptions>,
+        mut history: Vec<ChatMessage>,
+    ) -> Self {
+        // 丢弃流式回复中途留下的空占位
+        history.retain(|m| !m.content.is_empty());
+        Self {
+            model,
+            system,
+            options,
+            budget: ReplyBudget::default(),
+            backend: Backend::default(),
+            history,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.history.clear();
+    }
+
+    /// 当前模型，未设置时使用第一个已安装的模型
+    pub fn resolve_model(&mut self) -> anyhow::Result<String> {
+        if let Some(model) = &self.model {
+            return Ok(model.clone());
+        }
+        let rt = tokio::runtime::Runtime::new()?;
+        let model = rt
+            .block_on(OllamaClient::new(None).list_models())?
+            .models
+            .first()
+            .map(|m| m.name.clone())
+            .ok_or_else(|| ApiError("no models installed".into()))?;
+        self.model = Some(model.clone());
+        Ok(model)
+    }
+
+    /// 发送一条消息并阻塞到回复结束（或超出预算），`on_delta` 收到每次新增的文本；失败时不保留这条消息
+    pub fn send<F>(&mut self, content: &str, on_delta: F) -> anyhow::Result<Completion>
+    where
+        F: Fn(&str) + Send + 'static,
+    {
+        let model = self.resolve_model()?;
+
+        let user = ChatMessage {
+            role: "user".to_string(),
+            content: content.to_string(),
+        };
+        let messages = request_messages(
+            &self.system,
+            self.history.iter().cloned().chain([user.clone()]),
+        );
+
+        let sent = Mutex::new(0);
+        let tracker = Arc::new(BudgetTracker::new(self.budget));
+        let chunk_tracker = tracker.clone();
+        let handle = stream_chat(
+            &self.backend,
+            model,
+            messages,
+            tracker.options(self.options.clone()),
+            tracker.deadline(),
+            move |full| {
+                if chunk_tracker.stopped().is_some() {
+                    return false;
+                }
+                let Ok(mut sent) = sent.lock() else {
+                    return true;
+                };
+                if let Some(delta) = full.get(*sent..).filter(|d| !d.is_empty()) {
+                    on_delta(delta);
+                    *sent = full.len();
+                }
+                chunk_tracker.tick().is_none()
+            },
+        );
+        let completion = tracker
+            .watch(handle)
+            .join()
+            .map_err(|_| anyhow::anyhow!("chat thread panicked"))??;
+
+        self.history.push(user);
+        self.history.push(ChatMessage {
+            role: "assistant".to_string(),
+            content: completion.content.clone(),
+        });
+        Ok(completion)
+    }
+}
@@ -0,0 +1,619 @@
+use crate::channels::{self, TagRule};
+use crate::config::Config;
+use crate::ollama::ChatMessage;
+use crate::openwebui;
+use crate::session::{self, ChatEntry, Session};
+use crate::timefmt;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+/// 一种导出格式。新增格式时实现这个 trait 并加入 `EXPORTERS`
+pub trait Exporter: Sync {
+    /// `:export` 和 `--format` 中使用的名称
+    fn name(&self) -> &'static str;
+    /// 名称的别名
+    fn aliases(&self) -> &'static [&'static str] {
+        &[]
+    }
+    fn description(&self) -> &'static str;
+    /// 文件扩展名，导出为目录时为 None
+    fn extension(&self) -> Option<&'static str>;
+    /// 写入 `path`，返回导出内容的简短说明（如 `12 messages`）
+    fn export(&self, session: &Session, path: &Path) -> anyhow::Result<String>;
+
+    /// 没有指定路径时：当前目录下带时间戳的文件或目录
+    fn default_path(&self) -> PathBuf {
+        match self.extension() {
+            Some(ext) => PathBuf::from(format!(
+                "{}.{}",
+                default_export_dir("ollama-cli-chat").display(),
+                ext
+            )),
+            None => default_export_dir(&format!("ollama-cli-{}", self.name())),
+        }
+    }
+}
+
+static EXPORTERS: &[&dyn Exporter] = &[&Markdown, &Json, &Html, &Scripts, &Flashcards];
+
+/// 所有导出格式
+pub fn exporters() -> &'static [&'static dyn Exporter] {
+    EXPORTERS
+}
+
+/// 按名称或别名查找导出格式
+pub fn find(name: &str) -> Option<&'static dyn Exporter> {
+    let name = name.to_lowercase();
+    EXPORTERS
+        .iter()
+        .copied()
+        .find(|e| e.name() == name || e.aliases().contains(&name.as_str()))
+}
+
+/// 按文件扩展名推断导出格式，如 `chat.md`
+pub fn find_by_path(path: &Path) -> Option<&'static dyn Exporter> {
+    let ext = path.extension()?.to_str()?.to_lowercase();
+    EXPORTERS
+        .iter()
+        .copied()
+        .find(|e| e.extension() == Some(ext.as_str()))
+}
+
+/// 可用格式的名称列表，用于提示
+pub fn format_names() -> String {
+    EXPORTERS
+        .iter()
+        .map(|e| e.name())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[derive(Debug, Clone)]
+pub struct CodeBlock {
+    pub lang: String,
+    pub file_name: Option<String>,
+    pub heading: Option<String>,
+    pub content: String,
+    pub message: usize,
+}
+
+/// 从助手回复中提取所有 fenced code block，记录语言、文件名提示和最近的标题
+pub fn extract_code_blocks(messages: &[ChatMessage]) -> Vec<CodeBlock> {
+    let mut blocks = Vec::new();
+
+    for (index, msg) in messages.iter().enumerate() {
+        if msg.role != "assistant" {
+            continue;
+        }
+
+        let mut heading: Option<String> = None;
+        let mut current: Option<(String, Option<String>, String)> = None;
+
+        for line in msg.content.lines() {
+            let trimmed = line.trim_start();
+            match current.take() {
+                Some((lang, file_name, mut content)) => {
+                    if trimmed.trim_end() == "```" {
+                        blocks.push(CodeBlock {
+                            lang,
+                            file_name,
+                            heading: heading.clone(),
+                            content,
+                            message: index,
+                        });
+                    } else {
+                        content.push_str(line);
+                        content.push('\n');
+                        current = Some((lang, file_name, content));
+                    }
+                }
+                None => {
+                    if let Some(info) = trimmed.strip_prefix("```") {
+                        let (lang, file_name) = parse_info_string(info);
+                        current = Some((lang, file_name, String::new()));
+                    } else if trimmed.starts_with('#') {
+                        let text = trimmed.trim_start_matches('#').trim();
+                        if !text.is_empty() {
+                            heading = Some(text.to_string());
+                        }
+                    } else if let Some(name) = file_name_hint(trimmed) {
+                        heading = Some(name);
+                    }
+                }
+            }
+        }
+    }
+
+    blocks
+}
+
+/// 把代码块写入 `dir` 下的独立文件，并生成 INDEX.md 索引，返回写入的文件数
+pub fn export_code_blocks(messages: &[ChatMessage], dir: &Path) -> anyhow::Result<usize> {
+    let blocks = extract_code_blocks(messages);
+    if blocks.is_empty() {
+        anyhow::bail!("no code blocks in this conversation");
+    }
+
+    std::fs::create_dir_all(dir)?;
+
+    let mut used: HashSet<PathBuf> = HashSet::new();
+    let mut index = String::from(
+        "# Exported code blocks\n\n| File | Language | Message |\n|------|----------|---------|\n",
+    );
+
+    for (n, block) in blocks.iter().enumerate() {
+        let path = unique_path(&mut used, block_path(block, n + 1));
+        let full_path = dir.join(&path);
+        if let Some(parent) = full_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&full_path, &block.content)?;
+
+        let lang = if block.lang.is_empty() {
+            "-"
+        } else {
+            block.lang.as_str()
+        };
+        index.push_str(&format!(
+            "| [{0}]({0}) | {1} | #{2} |\n",
+            path.display(),
+            lang,
+            block.message + 1
+        ));
+    }
+
+    std::fs::write(dir.join("INDEX.md"), index)?;
+    Ok(blocks.len())
+}
+
+/// 默认导出目录：当前目录下带时间戳的子目录
+pub fn default_export_dir(prefix: &str) -> PathBuf {
+    let secs = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    PathBuf::from(format!("{}-{}", prefix, secs))
+}
+
+/// 当前分支中要导出的消息，回复中的推理过程已去掉
+fn transcript(session: &Session) -> Vec<(&ChatEntry, String)> {
+    let rules: Vec<TagRule> = Config::load()
+        .reasoning_tags
+        .unwrap_or_else(channels::default_rules);
+    session
+        .messages
+        .iter()
+        .map(|entry| {
+            let content = if entry.role() == "assistant" {
+                channels::strip(entry.content(), &rules)
+            } else {
+                entry.content().to_string()
+            };
+            (entry, content)
+        })
+        .collect()
+}
+
+fn speaker(entry: &ChatEntry) -> String {
+    match entry.role() {
+        "user" => "You".to_string(),
+        "assistant" => match &entry.meta {
+            Some(meta) => format!("AI ({})", meta.model),
+            None => "AI".to_string(),
+        },
+        "system" => "System".to_string(),
+        role => role.to_string(),
+    }
+}
+
+fn plural(count: usize, noun: &str) -> String {
+    format!("{} {}{}", count, noun, if count == 1 { "" } else { "s" })
+}
+
+struct Markdown;
+
+impl Exporter for Markdown {
+    fn name(&self) -> &'static str {
+        "md"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &["markdown"]
+    }
+
+    fn description(&self) -> &'static str {
+        "Markdown transcript"
+    }
+
+    fn extension(&self) -> Option<&'static str> {
+        Some("md")
+    }
+
+    fn export(&self, session: &Session, path: &Path) -> anyhow::Result<String> {
+        let messages = transcript(session);
+        let mut out = format!("# {}\n\n", session.display_title());
+        if let Some(model) = &session.model {
+            out.push_str(&format!("- Model: `{}`\n", model));
+        }
+        out.push_str(&format!(
+            "- Date: {}\n",
+            timefmt::datetime(session.created_at)
+        ));
+        if let Some(summary) = session.summary.as_deref().filter(|s| !s.is_empty()) {
+            out.push_str(&format!("\n> {}\n", summary.replace('\n', "\n> ")));
+        }
+        for (entry, content) in &messages {
+            out.push_str(&format!(
+                "\n## {}\n\n{}\n",
+                speaker(entry),
+                content.trim_end()
+            ));
+        }
+        std::fs::write(path, out)?;
+        Ok(plural(messages.len(), "message"))
+    }
+}
+
+/// Open WebUI 可导入的 JSON，保留所有分支
+struct Json;
+
+impl Exporter for Json {
+    fn name(&self) -> &'static str {
+        "json"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &["webui", "openwebui"]
+    }
+
+    fn description(&self) -> &'static str {
+        "Open WebUI format"
+    }
+
+    fn extension(&self) -> Option<&'static str> {
+        Some("json")
+    }
+
+    fn export(&self, session: &Session, path: &Path) -> anyhow::Result<String> {
+        openwebui::export_session(session, path)?;
+        Ok(plural(session.conversation().nodes().len(), "message"))
+    }
+}
+
+/// 单个 HTML 文件，不依赖外部资源，可直接在浏览器中打开或打印
+struct Html;
+
+const HTML_STYLE: &str = "body{max-width:48rem;margin:2rem auto;padding:0 1rem;font-family:system-ui,sans-serif;line-height:1.6;color:#222}\
+header p{color:#777;margin:0}\
+section{margin:1.5rem 0;padding:.75rem 1rem;border-radius:8px}\
+section.user{background:#eef6fb}\
+section.assistant{background:#f3f8ef}\
+section.system{background:#f4f4f4;color:#555}\
+h2{font-size:.9rem;margin:0 0 .5rem;color:#555}\
+p{white-space:pre-wrap;margin:.5rem 0}\
+pre{background:#272822;color:#f8f8f2;padding:.75rem;border-radius:6px;overflow-x:auto}";
+
+impl Exporter for Html {
+    fn name(&self) -> &'static str {
+        "html"
+    }
+
+    fn description(&self) -> &'static str {
+        "standalone HTML page"
+    }
+
+    fn extension(&self) -> Option<&'static str> {
+        Some("html")
+    }
+
+    fn export(&self, session: &Session, path: &Path) -> anyhow::Result<String> {
+        let messages = transcript(session);
+        let title = escape_html(&session.display_title());
+        let mut info = vec![timefmt::datetime(session.created_at)];
+        if let Some(model) = &session.model {
+            info.insert(0, model.clone());
+        }
+        let mut out = format!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{0}</title>\n<style>{1}</style>\n</head>\n<body>\n<header><h1>{0}</h1><p>{2}</p></header>\n",
+            title,
+            HTML_STYLE,
+            escape_html(&info.join(" · "))
+        );
+        for (entry, content) in &messages {
+            out.push_str(&format!(
+                "<section class=\"{}\">\n<h2>{}</h2>\n{}</section>\n",
+                escape_html(entry.role()),
+                escape_html(&speaker(entry)),
+                html_body(content)
+            ));
+        }
+        out.push_str("</body>\n</html>\n");
+        std::fs::write(path, out)?;
+        Ok(plural(messages.len(), "message"))
+    }
+}
+
+/// 代码块放进 `<pre>`，其余按空行分段
+fn html_body(content: &str) -> String {
+    let mut out = String::new();
+    let mut paragraph = String::new();
+    let mut code: Option<String> = None;
+    let flush = |paragraph: &mut String, out: &mut String| {
+        let text = paragraph.trim();
+        if !text.is_empty() {
+            out.push_str(&format!("<p>{}</p>\n", escape_html(text)));
+        }
+        paragraph.clear();
+    };
+    for line in content.lines() {
+        let fence = line.trim_start().starts_with("```");
+        match code.take() {
+            Some(block) if fence => {
+                out.push_str(&format!(
+                    "<pre><code>{}</code></pre>\n",
+                    escape_html(&block)
+                ));
+            }
+            Some(mut block) => {
+                block.push_str(line);
+                block.push('\n');
+                code = Some(block);
+            }
+            None if fence => {
+                flush(&mut paragraph, &mut out);
+                code = Some(String::new());
+            }
+            None if line.trim().is_empty() => flush(&mut paragraph, &mut out),
+            None => {
+                paragraph.push_str(line);
+                paragraph.push('\n');
+            }
+        }
+    }
+    // 没有闭合的代码块（被中止的回复）照样输出
+    if let Some(block) = code {
+        out.push_str(&format!(
+            "<pre><code>{}</code></pre>\n",
+            escape_html(&block)
+        ));
+    }
+    flush(&mut paragraph, &mut out);
+    out
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// 回复中的代码块写成独立文件，附带 INDEX.md
+struct Scripts;
+
+impl Exporter for Scripts {
+    fn name(&self) -> &'static str {
+        "scripts"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &["code"]
+    }
+
+    fn description(&self) -> &'static str {
+        "code blocks as files"
+    }
+
+    fn extension(&self) -> Option<&'static str> {
+        None
+    }
+
+    fn default_path(&self) -> PathBuf {
+        default_export_dir("ollama-cli-code")
+    }
+
+    fn export(&self, session: &Session, path: &Path) -> anyhow::Result<String> {
+        let count = export_code_blocks(&session::to_messages(&session.messages), path)?;
+        Ok(plural(count, "code block"))
+    }
+}
+
+/// 每个问题和它的回复作为一张卡片，Anki 可直接导入的制表符分隔文本
+struct Flashcards;
+
+impl Exporter for Flashcards {
+    fn name(&self) -> &'static str {
+        "flashcards"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &["anki"]
+    }
+
+    fn description(&self) -> &'static str {
+        "Anki flashcards"
+    }
+
+    fn extension(&self) -> Option<&'static str> {
+        Some("tsv")
+    }
+
+    fn export(&self, session: &Session, path: &Path) -> anyhow::Result<String> {
+        let messages = transcript(session);
+        let mut cards = Vec::new();
+        for (i, (entry, question)) in messages.iter().enumerate() {
+            if entry.role() != "user" {
+                continue;
+            }
+            let answer = messages[i + 1..]
+                .iter()
+                .take_while(|(next, _)| next.role() != "user")
+                .find(|(next, content)| next.role() == "assistant" && !content.trim().is_empty());
+            if let Some((_, answer)) = answer {
+                cards.push((question.trim(), answer.trim()));
+            }
+        }
+        if cards.is_empty() {
+            anyhow::bail!("no answered questions in this conversation");
+        }
+        // 字段中不能有制表符和换行，换行改为 <br>（导入时启用 HTML）
+        let field = |text: &str| {
+            escape_html(text)
+                .replace('\t', "    ")
+                .replace('\n', "<br>")
+        };
+        let mut out = String::from("#separator:tab\n#html:true\n");
+        for (question, answer) in &cards {
+            out.push_str(&format!("{}\t{}\n", field(question), field(answer)));
+        }
+        std::fs::write(path, out)?;
+        Ok(plural(cards.len(), "card"))
+    }
+}
+
+fn parse_info_string(info: &str) -> (String, Option<String>) {
+    let mut parts = info.split_whitespace();
+    let first = parts.next().unwrap_or_default();
+
+    // ```src/main.rs 或 ```rust title="src/main.rs"
+    if first.contains('.') || first.contains('/') {
+        let lang = Path::new(first)
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        return (lang, sanitize_path(first));
+    }
+
+    let file_name = parts
+        .map(|p| {
+            p.trim_start_matches("title=")
+                .trim_start_matches("file=")
+                .trim_matches('"')
+        })
+        .find(|p| p.contains('.'))
+        .and_then(sanitize_path);
+
+    (first.to_lowercase(), file_name)
+}
+
+/// 识别类似 `src/main.rs` 或 **Cargo.toml** 这样单独成行的文件名
+fn file_name_hint(line: &str) -> Option<String> {
+    let candidate = line
+        .trim()
+        .trim_end_matches(':')
+        .trim_matches(|c| c == '`' || c == '*');
+    if candidate.is_empty() || candidate.contains(' ') {
+        return None;
+    }
+    let ext = Path::new(candidate)
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or_default();
+    if !ext.chars().any(|c| c.is_ascii_alphabetic())
+        || !ext.chars().all(|c| c.is_ascii_alphanumeric())
+    {
+        return None;
+    }
+    sanitize_path(candidate)
+}
+
+fn sanitize_path(raw: &str) -> Option<String> {
+    let parts: Vec<&str> = raw
+        .split(['/', '\\'])
+        // Windows 的盘符（`C:`）也会让 join 得到绝对路径
+        .filter(|p| !p.is_empty() && *p != "." && *p != ".." && !p.contains(':'))
+        .collect();
+    if parts.is_empty() {
+        None
+    } else {
+        Some(parts.join("/"))
+    }
+}
+
+fn block_path(block: &CodeBlock, n: usize) -> PathBuf {
+    if let Some(name) = &block.file_name {
+        return PathBuf::from(name);
+    }
+    let ext = extension_for(&block.lang);
+    if let Some(heading) = &block.heading {
+        // 标题来自模型输出，和文件名提示一样去掉绝对路径和 `..`，防止写到导出目录之外
+        if heading.contains('.') && !heading.contains(' ') {
+            if let Some(path) = sanitize_path(heading) {
+                return PathBuf::from(path);
+            }
+        }
+        let slug = slugify(heading);
+        if !slug.is_empty() {
+            return PathBuf::from(format!("{:02}-{}.{}", n, slug, ext));
+        }
+    }
+    PathBuf::from(format!("block-{:02}.{}", n, ext))
+}
+
+fn unique_path(used: &mut HashSet<PathBuf>, path: PathBuf) -> PathBuf {
+    if used.insert(path.clone()) {
+        return path;
+    }
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let ext = path
+        .extension()
+        .map(|e| format!(".{}", e.to_string_lossy()))
+        .unwrap_or_default();
+    let mut n = 2;
+    loop {
+        let candidate = path.with_file_name(format!("{}-{}{}", stem, n, ext));
+        if used.insert(candidate.clone()) {
+            return candidate;
+        }
+        n += 1;
+    }
+}
+
+fn slugify(text: &str) -> String {
+    let mut slug = String::new();
+    for c in text.chars() {
+        if c.is_alphanumeric() {
+            slug.extend(c.to_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    slug.trim_end_matches('-').chars().take(40).collect()
+}
+
+fn extension_for(lang: &str) -> &str {
+    match lang {
+        "rust" | "rs" => "rs",
+        "python" | "py" => "py",
+        "javascript" | "js" => "js",
+        "typescript" | "ts" => "ts",
+        "tsx" => "tsx",
+        "jsx" => "jsx",
+        "go" | "golang" => "go",
+        "java" => "java",
+        "kotlin" | "kt" => "kt",
+        "c" => "c",
+        "cpp" | "c++" | "cc" => "cpp",
+        "csharp" | "cs" | "c#" => "cs",
+        "ruby" | "rb" => "rb",
+        "php" => "php",
+        "swift" => "swift",
+        "bash" | "sh" | "shell" | "zsh" | "console" => "sh",
+        "powershell" | "ps1" => "ps1",
+        "sql" => "sql",
+        "html" => "html",
+        "css" => "css",
+        "json" => "json",
+        "yaml" | "yml" => "yaml",
+        "toml" => "toml",
+        "xml" => "xml",
+        "markdown" | "md" => "md",
+        "dockerfile" => "Dockerfile",
+        "makefile" | "make" => "mk",
+        "lua" => "lua",
+        _ => "txt",
+    }
+}
@@ -0,0 +1,58 @@
+use crate::session::ChatEntry;
+
+/// 一次最多生成的回复数，对应数字键 1-9
+pub const MAX_REPLIES: usize = 9;
+pub const DEFAULT_REPLIES: usize = 3;
+
+/// `/fanout`：同一个问题用不同的 seed 生成的几条候选回复，选定一条之前不加入对话
+#[derive(Debug, Clone)]
+pub struct Fanout {
+    /// 区分先后几次 fanout，放弃后仍在生成的回复不再写入
+    pub id: i64,
+    /// 这些回复所回答的用户消息（对话中的最后一条）
+    prompt: String,
+    prompt_timestamp: i64,
+    pub replies: Vec<ChatEntry>,
+    pub seeds: Vec<i64>,
+    /// 还在生成的回复数
+    pub running: usize,
+}
+
+impl Fanout {
+    pub fn new(prompt: &ChatEntry, count: usize) -> Self {
+        let id = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_nanos() as i64)
+            .unwrap_or_default();
+        // seed 取当前时间，每条回复依次加一
+        let base = id.rem_euclid(1_000_000_000);
+        Self {
+            id,
+            prompt: prompt.content().to_string(),
+            prompt_timestamp: prompt.timestamp,
+            replies: Vec::with_capacity(count),
+            seeds: (0..count as i64).map(|i| base + i).collect(),
+            running: count,
+        }
+    }
+
+    /// 对话的最后一条仍是生成这些回复的用户消息时才有效（切换会话、`:new` 等之后失效）
+    pub fn belongs_to(&self, messages: &[ChatEntry]) -> bool {
+        messages.last().is_some_and(|m| {
+            m.role() == "user" && m.timestamp == self.prompt_timestamp && m.content() == self.prompt
+        })
+    }
+}
+
+/// `:fanout [N] [prompt]` 的参数：开头的数字为回复数，其余为问题
+pub fn parse_args(arg: &str) -> Result<(usize, &str), String> {
+    let (first, rest) = arg.split_once(' ').unwrap_or((arg, ""));
+    let (count, prompt) = match first.parse::<usize>() {
+        Ok(count) => (count, rest.trim()),
+        Err(_) => (DEFAULT_REPLIES, arg.trim()),
+    };
+    if !(2..=MAX_REPLIES).contains(&count) {
+        return Err(format!("Fan out to 2-{} replies", MAX_REPLIES));
+    }
+    Ok((count, prompt))
+}
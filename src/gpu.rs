@@ -0,0 +1,138 @@
+use crate::ollama::ShowResponse;
+use once_cell::sync::Lazy;
+use std::process::Command;
+
+const GIB: u64 = 1024 * 1024 * 1024;
+const MIB: u64 = 1024 * 1024;
+/// Ollama 在显存中为计算图等预留的空间，按经验取值
+const RESERVED: u64 = 512 * MIB;
+/// 未设置 num_ctx 时 Ollama 使用的上下文长度
+const DEFAULT_CONTEXT: u64 = 2048;
+
+/// 检测到的显存，多块显卡时为总和
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Vram {
+    pub name: String,
+    pub total: u64,
+    pub free: u64,
+}
+
+// 检测需要运行外部命令，只做一次
+static DETECTED: Lazy<Option<Vram>> = Lazy::new(|| nvidia().or_else(apple));
+
+pub fn detect() -> Option<Vram> {
+    DETECTED.clone()
+}
+
+/// nvidia-smi 输出每块显卡一行：`名称, 总显存, 可用显存`（MiB）
+fn nvidia() -> Option<Vram> {
+    let output = Command::new("nvidia-smi")
+        .args([
+            "--query-gpu=name,memory.total,memory.free",
+            "--format=csv,noheader,nounits",
+        ])
+        .output()
+        .ok()
+        .filter(|o| o.status.success())?;
+    let mut vram: Option<Vram> = None;
+    let mut count = 0;
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+        let [name, total, free] = fields.as_slice() else {
+            continue;
+        };
+        let (Ok(total), Ok(free)) = (total.parse::<u64>(), free.parse::<u64>()) else {
+            continue;
+        };
+        count += 1;
+        let v = vram.get_or_insert_with(|| Vram {
+            name: name.to_string(),
+            total: 0,
+            free: 0,
+        });
+        v.total += total * MIB;
+        v.free += free * MIB;
+        if count > 1 {
+            v.name = format!("{} NVIDIA GPUs", count);
+        }
+    }
+    vram
+}
+
+/// Apple Silicon 使用统一内存，Metal 默认最多可用约 2/3
+fn apple() -> Option<Vram> {
+    if !cfg!(all(target_os = "macos", target_arch = "aarch64")) {
+        return None;
+    }
+    let output = Command::new("sysctl")
+        .args(["-n", "hw.memsize"])
+        .output()
+        .ok()?;
+    let memory: u64 = String::from_utf8_lossy(&output.stdout)
+        .trim()
+        .parse()
+        .ok()?;
+    let usable = memory / 3 * 2;
+    Some(Vram {
+        name: "Apple unified memory".to_string(),
+        total: usable,
+        free: usable,
+    })
+}
+
+/// 预计放进显存的层数
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Offload {
+    pub gpu_layers: u64,
+    pub total_layers: u64,
+    /// 放进显存的部分占用的空间
+    pub vram_used: u64,
+}
+
+impl Offload {
+    /// 按模型大小平均到每层，再加上每层的 KV cache 估算；
+    /// 输出层单独算一层，与 Ollama 日志中的层数一致
+    pub fn estimate(
+        size: u64,
+        show: &ShowResponse,
+        num_ctx: Option<u32>,
+        vram: &Vram,
+    ) -> Option<Self> {
+        let blocks = show.info_u64("block_count").filter(|n| *n > 0)?;
+        let total_layers = blocks + 1;
+        let weights = size / total_layers;
+
+        let context = num_ctx.map(u64::from).unwrap_or(DEFAULT_CONTEXT);
+        let embedding = show.info_u64("embedding_length").unwrap_or(0);
+        let heads = show
+            .info_u64("attention.head_count")
+            .filter(|n| *n > 0)
+            .unwrap_or(1);
+        let kv_heads = show.info_u64("attention.head_count_kv").unwrap_or(heads);
+        // K 和 V 各一份，f16 每个元素 2 字节
+        let kv_cache = 2 * context * embedding * kv_heads / heads * 2;
+
+        let per_layer = (weights + kv_cache).max(1);
+        let available = vram.free.saturating_sub(RESERVED);
+        let gpu_layers = (available / per_layer).min(total_layers);
+        Some(Self {
+            gpu_layers,
+            total_layers,
+            vram_used: gpu_layers * per_layer + if gpu_layers > 0 { RESERVED } else { 0 },
+        })
+    }
+
+    pub fn label(&self) -> String {
+        if self.gpu_layers == self.total_layers {
+            format!("all {} layers on GPU", self.total_layers)
+        } else if self.gpu_layers == 0 {
+            format!("0/{} layers on GPU (CPU only)", self.total_layers)
+        } else {
+            format!("{}/{} layers on GPU", self.gpu_layers, self.total_layers)
+        }
+    }
+}
+
+pub fn format_gib(bytes: u64) -> String {
+    format!("{:.1} GB", bytes as f64 / GIB as f64)
+}
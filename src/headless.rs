@@ -0,0 +1,193 @@
+use crate::connection;
+use crate::engine::ChatEngine;
+use crate::ollama::{GenerationStats, OllamaClient};
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, Write};
+
+/// stdin 上的一行命令，`id` 会原样带回到对应的事件中
+#[derive(Debug, Deserialize)]
+struct Request {
+    #[serde(default)]
+    id: Option<serde_json::Value>,
+    #[serde(flatten)]
+    command: Command,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Command {
+    Send {
+        content: String,
+    },
+    SetModel {
+        model: String,
+    },
+    /// 只修改给出的字段
+    SetOptions {
+        #[serde(default)]
+        system: Option<String>,
+        #[serde(default)]
+        num_predict: Option<i32>,
+        /// 单条回复的时间上限（秒），0 为不限
+        #[serde(default)]
+        max_seconds: Option<u64>,
+        /// 单条回复的 token 上限，0 为不限
+        #[serde(default)]
+        max_tokens: Option<u64>,
+    },
+    ListModels,
+    Clear,
+    Quit,
+}
+
+#[derive(Debug, Serialize)]
+struct Reply<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<&'a serde_json::Value>,
+    #[serde(flatten)]
+    event: Event,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Event {
+    Ready,
+    Delta {
+        content: String,
+    },
+    Done {
+        model: String,
+        content: String,
+        /// 因超出预算提前结束
+        #[serde(skip_serializing_if = "std::ops::Not::not")]
+        truncated: bool,
+        #[serde(flatten)]
+        stats: GenerationStats,
+    },
+    Models {
+        models: Vec<String>,
+    },
+    Ok,
+    Error {
+        message: String,
+    },
+}
+
+fn emit(id: Option<&serde_json::Value>, event: Event) {
+    let Ok(line) = serde_json::to_string(&Reply { id, event }) else {
+        return;
+    };
+    let mut stdout = std::io::stdout().lock();
+    let _ = writeln!(stdout, "{}", line);
+    let _ = stdout.flush();
+}
+
+/// `--json-repl`：按行读取 JSON 命令，以 JSON 行输出流式事件，供编辑器等工具嵌入
+pub fn run(model: Option<String>) -> i32 {
+    let mut engine = ChatEngine::new(model, String::new(), None, Vec::new());
+    emit(None, Event::Ready);
+
+    for line in std::io::stdin().lock().lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let request = match serde_json::from_str::<Request>(&line) {
+            Ok(request) => request,
+            Err(e) => {
+                emit(
+                    None,
+                    Event::Error {
+                        message: format!("invalid command: {}", e),
+                    },
+                );
+                continue;
+            }
+        };
+        let id = request.id.as_ref();
+
+        match request.command {
+            Command::Send { content } => {
+                let delta_id = request.id.clone();
+                let result = engine.send(&content, move |delta| {
+                    emit(
+                        delta_id.as_ref(),
+                        Event::Delta {
+                            content: delta.to_string(),
+                        },
+                    );
+                });
+                match result {
+                    Ok(completion) => emit(
+                        id,
+                        Event::Done {
+                            model: engine.model.clone().unwrap_or_default(),
+                            content: completion.content,
+                            truncated: completion.cancelled,
+                            stats: completion.stats,
+                        },
+                    ),
+                    Err(e) => emit(
+                        id,
+                        Event::Error {
+                            message: connection::describe_error(&e),
+                        },
+                    ),
+                }
+            }
+            Command::SetModel { model } => {
+                engine.model = Some(model);
+                emit(id, Event::Ok);
+            }
+            Command::SetOptions {
+                system,
+                num_predict,
+                max_seconds,
+                max_tokens,
+            } => {
+                if let Some(seconds) = max_seconds {
+                    engine.budget.seconds = Some(seconds).filter(|s| *s > 0);
+                }
+                if let Some(tokens) = max_tokens {
+                    engine.budget.tokens = Some(tokens).filter(|t| *t > 0);
+                }
+                if let Some(system) = system {
+                    engine.system = system;
+                }
+                if num_predict.is_some() {
+                    let mut options = engine.options.take().unwrap_or_default();
+                    options.num_predict = num_predict.filter(|n| *n != 0);
+                    engine.options = (!options.is_empty()).then_some(options);
+                }
+                emit(id, Event::Ok);
+            }
+            Command::ListModels => {
+                let models = tokio::runtime::Runtime::new()
+                    .map_err(anyhow::Error::from)
+                    .and_then(|rt| rt.block_on(OllamaClient::new(None).list_models()));
+                match models {
+                    Ok(list) => emit(
+                        id,
+                        Event::Models {
+                            models: list.models.into_iter().map(|m| m.name).collect(),
+                        },
+                    ),
+                    Err(e) => emit(
+                        id,
+                        Event::Error {
+                            message: connection::describe_error(&e),
+                        },
+                    ),
+                }
+            }
+            Command::Clear => {
+                engine.clear();
+                emit(id, Event::Ok);
+            }
+            Command::Quit => break,
+        }
+    }
+    0
+}
@@ -0,0 +1,93 @@
+//! 没有配置 `host` 和 `OLLAMA_HOST` 时自动找到 Ollama：WSL 中的 localhost 不是 Windows，
+//! 反过来 Windows 上也不一定能通过 localhost 访问 WSL 里的 Ollama。
+
+use std::net::{Ipv4Addr, SocketAddr, TcpStream};
+use std::time::Duration;
+
+const PORT: u16 = 11434;
+const PROBE_TIMEOUT: Duration = Duration::from_millis(300);
+
+/// 自动检测到的地址和来源说明
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Detected {
+    pub host: String,
+    pub source: &'static str,
+}
+
+/// localhost 可以连上时返回 None（使用默认地址），否则尝试 WSL 和 Windows 之间的地址。
+/// 每次调用都会探测端口，结果由 `Config::detected_host` 缓存
+pub fn detect() -> Option<Detected> {
+    if reachable(Ipv4Addr::LOCALHOST) {
+        return None;
+    }
+    if is_wsl() {
+        // WSL2 默认的 NAT 网络中，Windows 是默认网关；自定义 DNS 时 resolv.conf 可能不是
+        return [windows_gateway(), resolv_nameserver()]
+            .into_iter()
+            .flatten()
+            .find(|ip| reachable(*ip))
+            .map(|ip| Detected {
+                host: format!("http://{}:{}", ip, PORT),
+                source: "Windows host (from WSL)",
+            });
+    }
+    if cfg!(windows) {
+        return wsl_address()
+            .filter(|ip| reachable(*ip))
+            .map(|ip| Detected {
+                host: format!("http://{}:{}", ip, PORT),
+                source: "WSL distribution",
+            });
+    }
+    None
+}
+
+fn reachable(ip: Ipv4Addr) -> bool {
+    TcpStream::connect_timeout(&SocketAddr::from((ip, PORT)), PROBE_TIMEOUT).is_ok()
+}
+
+fn is_wsl() -> bool {
+    if !cfg!(target_os = "linux") {
+        return false;
+    }
+    std::env::var_os("WSL_DISTRO_NAME").is_some()
+        || std::fs::read_to_string("/proc/sys/kernel/osrelease")
+            .is_ok_and(|release| release.to_lowercase().contains("microsoft"))
+}
+
+/// /proc/net/route 中目标为 0.0.0.0 的一行，网关按本机字节序的十六进制表示
+fn windows_gateway() -> Option<Ipv4Addr> {
+    let routes = std::fs::read_to_string("/proc/net/route").ok()?;
+    routes.lines().skip(1).find_map(|line| {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.get(1) != Some(&"00000000") {
+            return None;
+        }
+        let gateway = u32::from_str_radix(fields.get(2)?, 16).ok()?;
+        Some(Ipv4Addr::from(gateway.to_le_bytes())).filter(|ip| !ip.is_unspecified())
+    })
+}
+
+fn resolv_nameserver() -> Option<Ipv4Addr> {
+    let resolv = std::fs::read_to_string("/etc/resolv.conf").ok()?;
+    resolv.lines().find_map(|line| {
+        line.trim()
+            .strip_prefix("nameserver")?
+            .trim()
+            .parse()
+            .ok()
+            .filter(|ip: &Ipv4Addr| !ip.is_loopback())
+    })
+}
+
+/// Windows 上通过 `wsl hostname -I` 取得默认发行版的地址
+fn wsl_address() -> Option<Ipv4Addr> {
+    let output = std::process::Command::new("wsl")
+        .args(["hostname", "-I"])
+        .output()
+        .ok()
+        .filter(|o| o.status.success())?;
+    String::from_utf8_lossy(&output.stdout)
+        .split_whitespace()
+        .find_map(|ip| ip.parse().ok())
+}
@@ -0,0 +1,169 @@
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+/// 单行输入框：光标按字素（grapheme）移动，宽度按终端显示宽度计算，
+/// 保证 CJK 和 emoji 输入时光标位置正确
+#[derive(Debug, Clone, Default)]
+pub struct TextInput {
+    text: String,
+    // 光标所在的字节偏移，始终位于字素边界
+    cursor: usize,
+}
+
+impl TextInput {
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.text.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.text.clear();
+        self.cursor = 0;
+    }
+
+    /// 替换全部文本，光标移到末尾
+    pub fn set_text(&mut self, text: String) {
+        self.cursor = text.len();
+        self.text = text;
+    }
+
+    pub fn take(&mut self) -> String {
+        self.cursor = 0;
+        std::mem::take(&mut self.text)
+    }
+
+    pub fn insert_char(&mut self, c: char) {
+        self.text.insert(self.cursor, c);
+        self.cursor += c.len_utf8();
+    }
+
+    pub fn insert_str(&mut self, text: &str) {
+        self.text.insert_str(self.cursor, text);
+        self.cursor += text.len();
+    }
+
+    pub fn backspace(&mut self) {
+        if let Some(start) = self.prev_boundary() {
+            self.text.replace_range(start..self.cursor, "");
+            self.cursor = start;
+        }
+    }
+
+    pub fn delete(&mut self) {
+        if let Some(end) = self.next_boundary() {
+            self.text.replace_range(self.cursor..end, "");
+        }
+    }
+
+    pub fn move_left(&mut self) {
+        if let Some(start) = self.prev_boundary() {
+            self.cursor = start;
+        }
+    }
+
+    pub fn move_right(&mut self) {
+        if let Some(end) = self.next_boundary() {
+            self.cursor = end;
+        }
+    }
+
+    pub fn home(&mut self) {
+        self.cursor = 0;
+    }
+
+    pub fn end(&mut self) {
+        self.cursor = self.text.len();
+    }
+
+    /// 返回宽度为 `width` 的可见文本及光标列，光标超出时水平滚动
+    pub fn visible(&self, width: u16) -> (&str, u16) {
+        let width = width.max(1) as usize;
+        let before = &self.text[..self.cursor];
+        let mut start = 0;
+        let mut cursor_col = before.width();
+
+        // 光标需要占一列，从左侧丢弃字素直到光标可见
+        for (idx, g) in before.grapheme_indices(true) {
+            if cursor_col < width {
+                break;
+            }
+            cursor_col -= g.width();
+            start = idx + g.len();
+        }
+
+        let mut end = start;
+        let mut used = 0;
+        for (idx, g) in self.text[start..].grapheme_indices(true) {
+            let w = g.width();
+            if used + w > width {
+                break;
+            }
+            used += w;
+            end = start + idx + g.len();
+        }
+
+        (&self.text[start..end], cursor_col as u16)
+    }
+
+    /// 按宽度 `width` 折行后的各行及光标所在的行列，用于放大显示输入框
+    pub fn wrapped(&self, width: u16) -> (Vec<String>, u16, u16) {
+        let width = width.max(1) as usize;
+        let mut lines = vec![String::new()];
+        let mut used = 0;
+        let mut cursor = (0, 0);
+        for (idx, g) in self.text.grapheme_indices(true) {
+            // 草稿本等多行文本中的换行
+            if g == "\n" || g == "\r\n" {
+                if idx == self.cursor {
+                    cursor = (lines.len() - 1, used);
+                }
+                lines.push(String::new());
+                used = 0;
+                continue;
+            }
+            let w = g.width();
+            if used + w > width {
+                lines.push(String::new());
+                used = 0;
+            }
+            if idx == self.cursor {
+                cursor = (lines.len() - 1, used);
+            }
+            if let Some(line) = lines.last_mut() {
+                line.push_str(g);
+            }
+            used += w;
+        }
+        if self.cursor == self.text.len() {
+            // 光标在末尾且恰好占满一行时移到下一行开头
+            if used >= width {
+                lines.push(String::new());
+                used = 0;
+            }
+            cursor = (lines.len() - 1, used);
+        }
+        (lines, cursor.0 as u16, cursor.1 as u16)
+    }
+
+    fn prev_boundary(&self) -> Option<usize> {
+        self.text[..self.cursor]
+            .grapheme_indices(true)
+            .next_back()
+            .map(|(idx, _)| idx)
+    }
+
+    fn next_boundary(&self) -> Option<usize> {
+        self.text[self.cursor..]
+            .graphemes(true)
+            .next()
+            .map(|g| self.cursor + g.len())
+    }
+}
+
+/// 字符串在终端中的显示宽度
+pub fn display_width(text: &str) -> usize {
+    text.width()
+}
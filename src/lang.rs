@@ -0,0 +1,139 @@
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+use unicode_width::UnicodeWidthChar;
+use whatlang::Script;
+
+/// 只用开头这些字符检测
+const SAMPLE_CHARS: usize = 400;
+/// 字母少于这个数时不检测（代码、数字等）
+const MIN_LETTERS: usize = 12;
+
+/// 不能出现在行首的标点，断行时连同前一个字一起换到下一行
+const NO_LINE_START: &str = "，。、；：？！）」』】》〉”’…—,.;:?!)]}%";
+
+/// 检测到的消息语言，回复结束时检测一次并随消息保存
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Language {
+    /// ISO 639-3 代码，如 `eng`、`cmn`，用作消息标记
+    pub code: String,
+    /// 从右到左书写（阿拉伯文、希伯来文）
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub rtl: bool,
+    /// 中日韩文字，需要逐字断行
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub cjk: bool,
+}
+
+impl Language {
+    pub fn code(&self) -> &str {
+        &self.code
+    }
+
+    pub fn is_rtl(&self) -> bool {
+        self.rtl
+    }
+
+    pub fn is_cjk(&self) -> bool {
+        self.cjk
+    }
+}
+
+pub fn detect(text: &str) -> Option<Language> {
+    let sample: String = text.chars().take(SAMPLE_CHARS).collect();
+    if sample.chars().filter(|c| c.is_alphabetic()).count() < MIN_LETTERS {
+        return None;
+    }
+    let info = whatlang::detect(&sample)?;
+    // 拉丁和西里尔字母的语言之间容易混淆，不可靠时不显示；其他文字本身就能确定
+    if !info.is_reliable() && matches!(info.script(), Script::Latin | Script::Cyrillic) {
+        return None;
+    }
+    Some(Language {
+        code: info.lang().code().to_string(),
+        rtl: matches!(info.script(), Script::Arabic | Script::Hebrew),
+        cjk: matches!(
+            info.script(),
+            Script::Mandarin | Script::Hiragana | Script::Katakana | Script::Hangul
+        ),
+    })
+}
+
+/// 按显示宽度断开 CJK 文本。ratatui 只在空白处断行，没有空格的整段中文会被当成一个词，
+/// 和前面的英文单词混排时整段被挤到下一行。`first_width` 为第一行可用的宽度（扣除前缀），
+/// 返回每段在 `line` 中的字节偏移和内容
+pub fn wrap_cjk(line: &str, first_width: usize, width: usize) -> Vec<(usize, &str)> {
+    let width = width.max(2);
+    let mut limit = first_width.max(1);
+    let mut pieces = Vec::new();
+    let mut start = 0;
+    let mut used = 0;
+    let mut prev = 0;
+    for (i, c) in line.char_indices() {
+        let w = c.width().unwrap_or(0);
+        if used + w > limit && i > start {
+            // 闭合标点不放在行首，把前一个字一起带到下一行
+            let cut = if NO_LINE_START.contains(c) && prev > start {
+                prev
+            } else {
+                i
+            };
+            pieces.push((start, &line[start..cut]));
+            used = line[cut..i].chars().filter_map(|c| c.width()).sum();
+            start = cut;
+            limit = width;
+        }
+        used += w;
+        prev = i;
+    }
+    pieces.push((start, &line[start..]));
+    pieces
+}
+
+/// 大小写不敏感地查找 `needle` 的所有位置，返回在 `text` 中的字节范围。
+/// 逐字转为小写后比较，非 ASCII 字母转换后长度可能变化，命中位置换算回原文
+pub fn find_ignore_case(text: &str, needle: &str) -> Vec<Range<usize>> {
+    let needle = fold_case(needle);
+    if needle.is_empty() {
+        return Vec::new();
+    }
+    let mut folded = String::with_capacity(text.len());
+    // 转换后每个字节对应的原文字符范围
+    let mut origins = Vec::with_capacity(text.len());
+    for (start, c) in text.char_indices() {
+        for lower in c.to_lowercase() {
+            folded.push(lower);
+            origins.extend(std::iter::repeat_n(
+                start..start + c.len_utf8(),
+                lower.len_utf8(),
+            ));
+        }
+    }
+    folded
+        .match_indices(&needle)
+        .map(|(pos, _)| origins[pos].start..origins[pos + needle.len() - 1].end)
+        .collect()
+}
+
+/// 逐字转为小写，与 `find_ignore_case` 的比较方式一致
+pub fn fold_case(text: &str) -> String {
+    text.chars().flat_map(char::to_lowercase).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn find_ignore_case_maps_back_to_original_bytes() {
+        assert_eq!(find_ignore_case("Hello hELLO", "hello"), vec![0..5, 6..11]);
+        assert_eq!(find_ignore_case("Ünïcode ÜNÏ", "üNï"), vec![0..5, 10..15]);
+        // 'İ' 转小写后变长，后面的位置仍对应原文
+        let text = "İstanbul ISTANBUL";
+        let found = find_ignore_case(text, "stanbul");
+        assert_eq!(found.len(), 2);
+        assert!(found
+            .iter()
+            .all(|r| text[r.clone()].eq_ignore_ascii_case("stanbul")));
+        assert!(find_ignore_case("abc", "").is_empty());
+    }
+}
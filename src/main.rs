@@ -1,32 +1,197 @@
+mod channels;
+mod cli;
+mod config;
+mod connection;
+mod daemon;
+mod downloads;
+mod engine;
+mod export;
+mod fanout;
+mod gpu;
+mod headless;
+mod hostdetect;
+mod input;
+mod lang;
+mod lint;
+mod memory;
+mod model_defaults;
+mod model_settings;
+mod notifications;
 mod ollama;
+mod openwebui;
+mod present;
+mod recorder;
+mod replay;
+mod samples;
+mod scratchpad;
 mod search;
+mod server;
+mod session;
+mod substitute;
+mod tabs;
+mod timefmt;
+mod transport;
+mod variables;
+mod webhook;
 
 use anyhow::Result;
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEventKind},
+    event::{
+        self, DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste, EnableMouseCapture,
+        Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, MouseButton, MouseEvent,
+        MouseEventKind,
+    },
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 use ratatui::{
-    layout::{Constraint, Direction, Layout},
+    layout::{Constraint, Direction, Layout, Rect},
     style::{Color, Style},
-    widgets::{Block, Borders, List, ListItem, Paragraph, Scrollbar, ScrollbarOrientation, Tabs},
+    text::{Line, Span},
+    widgets::{
+        Block, Borders, Clear, List, ListItem, Paragraph, Scrollbar, ScrollbarOrientation, Tabs,
+    },
     DefaultTerminal, Frame,
 };
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
-use ollama::{ChatMessage, OllamaClient};
-use search::{ModelSearch, OnlineModel};
+use config::Config;
+use connection::{ConnectionMonitor, ConnectionStatus};
+use downloads::{DownloadManager, TransferStatus};
+use engine::{Backend, BudgetTracker, ChatEngine, ReplyBudget};
+use input::TextInput;
+use memory::Memory;
+use model_defaults::{ModelDefaults, ModelDefaultsMap};
+use model_settings::ModelSettings;
+use notifications::{Notifications, Target};
+use ollama::{ChatMessage, GenerateRequest, ModelOptions, OllamaClient};
+use search::{ModelSearch, OnlineModel, Recommended};
+use server::ServerManager;
+use session::{BranchInfo, ChatEntry, ConversationTree, GenerationMeta, Session};
+use substitute::{Confirmation, Substitution};
+use tabs::Tab;
+use variables::Variables;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
-enum Tab {
+enum ChatMode {
     #[default]
-    Chat,
-    Models,
+    Insert,
+    Normal,
     Search,
-    Prompts,
+    Command,
+}
+
+/// 发送消息使用的 API：/api/chat 带上下文，/api/generate 单次补全
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum ApiMode {
+    #[default]
+    Chat,
+    Generate,
+}
+
+/// 回复长度预设：向系统提示词追加简洁/详细要求并调整 num_predict
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum LengthPreset {
+    Short,
+    #[default]
+    Normal,
+    Detailed,
+}
+
+impl LengthPreset {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "short" => Some(LengthPreset::Short),
+            "normal" => Some(LengthPreset::Normal),
+            "detailed" => Some(LengthPreset::Detailed),
+            _ => None,
+        }
+    }
+
+    fn next(self) -> Self {
+        match self {
+            LengthPreset::Short => LengthPreset::Normal,
+            LengthPreset::Normal => LengthPreset::Detailed,
+            LengthPreset::Detailed => LengthPreset::Short,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            LengthPreset::Short => "short",
+            LengthPreset::Normal => "normal",
+            LengthPreset::Detailed => "detailed",
+        }
+    }
+
+    fn instruction(self) -> Option<&'static str> {
+        match self {
+            LengthPreset::Short => Some(
+                "Be concise. Answer in as few sentences as possible and skip preamble, caveats and summaries.",
+            ),
+            LengthPreset::Normal => None,
+            LengthPreset::Detailed => Some(
+                "Give a thorough, detailed answer. Explain your reasoning step by step and include examples where helpful.",
+            ),
+        }
+    }
+
+    fn num_predict(self) -> Option<i32> {
+        match self {
+            LengthPreset::Short => Some(256),
+            LengthPreset::Normal => None,
+            LengthPreset::Detailed => Some(4096),
+        }
+    }
+}
+
+/// Models 标签页的排序列
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum ModelSort {
+    #[default]
+    Name,
+    Size,
+    Family,
+    Modified,
+}
+
+impl ModelSort {
+    fn next(self) -> Self {
+        match self {
+            ModelSort::Name => ModelSort::Size,
+            ModelSort::Size => ModelSort::Family,
+            ModelSort::Family => ModelSort::Modified,
+            ModelSort::Modified => ModelSort::Name,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            ModelSort::Name => "name",
+            ModelSort::Size => "size",
+            ModelSort::Family => "family",
+            ModelSort::Modified => "modified",
+        }
+    }
+}
+
+/// 上一帧渲染出的区域，用于鼠标点击和滚轮的命中测试
+#[derive(Debug, Clone, Copy, Default)]
+struct HitAreas {
+    tabs: Rect,
+    messages: Rect,
+    list: Rect,
+}
+
+/// 试用模型前暂存的对话，`:back` 时恢复
+#[derive(Clone)]
+struct StashedChat {
+    messages: Vec<ChatEntry>,
+    conversation: ConversationTree,
+    current_session: Option<Session>,
+    selected_model: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -38,19 +203,138 @@ pub struct SavedPrompt {
 #[derive(Default, Clone)]
 pub struct AppState {
     current_tab: Tab,
+    tabs: Vec<Tab>,
     selected_model: Option<String>,
     models: Vec<ollama::Model>,
-    messages: Vec<ChatMessage>,
-    input_text: String,
+    messages: Vec<ChatEntry>,
+    input: TextInput,
     is_loading: bool,
     search_query: String,
     search_results: Vec<OnlineModel>,
     is_searching: bool,
+    // Active catalogue backend and the last search error, shown in the Search tab
+    search_backend: Option<&'static str>,
+    search_error: Option<String>,
+    // Whole library listed alphabetically, letters jump instead of typing
+    search_browsing: bool,
+    // Tag picker for the selected search result, marked tags are pulled together
+    tag_picker_open: bool,
+    tag_picker_model: String,
+    tag_list: Vec<String>,
+    tag_list_state: ratatui::widgets::ListState,
+    tag_marked: std::collections::HashSet<String>,
+    tags_loading: bool,
+    tags_error: Option<String>,
     model_list_state: ratatui::widgets::ListState,
+    model_sort: ModelSort,
+    model_sort_desc: bool,
+    // Models marked with Space for bulk delete
+    model_marked: std::collections::HashSet<String>,
+    model_delete_confirm: Option<Vec<String>>,
+    // Per-model options and the detail popup with library recommendations
+    model_settings: ModelSettings,
+    // Settings last used with each model, and the model whose settings are active
+    model_defaults: ModelDefaultsMap,
+    model_defaults_applied: Option<String>,
+    model_info: Option<String>,
+    // Ctrl+Z: the focused pane fills the whole screen
+    zoomed: bool,
+    recommended: Option<Recommended>,
+    recommended_loading: bool,
+    recommended_error: Option<String>,
+    // Estimated GPU offload for the model in the detail popup
+    model_offload: Option<String>,
+    // "Try it" prompts in the detail popup and the chat they replaced
+    sample_prompts: Vec<String>,
+    stashed_chat: Option<StashedChat>,
     search_list_state: ratatui::widgets::ListState,
     chat_scroll: u16,
     auto_scroll: bool,
     status_message: Option<String>,
+    // 配置错误、模型列表加载失败等：不随按键清除，问题解决前显示在按键提示的位置
+    status_error: Option<String>,
+    config: Config,
+    connection: ConnectionMonitor,
+    server: ServerManager,
+    server_log_open: bool,
+    server_autostart_tried: bool,
+    // Set by :restart, handled by the main loop
+    restart_requested: bool,
+    hit_areas: HitAreas,
+    chat_mode: ChatMode,
+    // Chat search: (message index, byte offset in content) of every hit
+    chat_search_query: String,
+    chat_search_matches: Vec<(usize, usize)>,
+    chat_search_index: usize,
+    chat_scroll_target: Option<usize>,
+    chat_command: String,
+    // Guards against repeated Enter events sending the same message twice
+    last_enter: Option<std::time::Instant>,
+    last_send: Option<(String, std::time::Instant)>,
+    // Message selected with [ / ] in normal mode, Enter shows its details
+    chat_selected: Option<usize>,
+    message_detail_open: bool,
+    // Generate mode (/api/generate)
+    api_mode: ApiMode,
+    generate_raw: bool,
+    generate_template: String,
+    generate_suffix: String,
+    length_preset: LengthPreset,
+    // Phrases that stop a streamed reply, optionally retried once
+    banned_phrases: Vec<String>,
+    retry_banned: bool,
+    // Warnings shown above the input before sending (`:lint`)
+    prompt_lint: bool,
+    lint_hints: Vec<String>,
+    // Draft the hints were shown for; Enter again sends it anyway
+    lint_warned: Option<String>,
+    // Reasoning tags shown as collapsible channels; per-message and global expansion
+    tag_rules: Vec<channels::TagRule>,
+    expanded_channels: std::collections::HashMap<usize, bool>,
+    all_channels_expanded: Option<bool>,
+    // The last reply failed because Ollama went away; offered again after reconnecting
+    failed_reply: bool,
+    retry_offer: bool,
+    // `:replay` in progress; `messages` shows the current frame
+    replay: Option<replay::Player>,
+    // `/fanout`: candidate replies to the last message, waiting for one to be kept
+    fanout: Option<fanout::Fanout>,
+    // `--oneshot`: one conversation with a fixed model, Ctrl+C to exit
+    kiosk: bool,
+    // Per-reply time/token limit, the partial answer is kept
+    reply_budget: ReplyBudget,
+    // Conversation variables ({{key}} expansion)
+    variables: Variables,
+    variables_editor_open: bool,
+    variables_list_state: ratatui::widgets::ListState,
+    variable_editing: bool,
+    variable_editing_key: Option<String>,
+    variable_input: TextInput,
+    // Conversation branches; `messages` is the current branch
+    conversation: ConversationTree,
+    branch_picker_open: bool,
+    branch_list: Vec<BranchInfo>,
+    branch_list_state: ratatui::widgets::ListState,
+    // Notification history (`:notifications`)
+    notifications: Notifications,
+    notifications_open: bool,
+    notifications_state: ratatui::widgets::ListState,
+    notified_transfers: std::collections::HashSet<u64>,
+    // Replies still held by the background daemon (`:attach`)
+    detached_jobs: Vec<daemon::JobInfo>,
+    // Job being followed after `:attach`; cleared by Esc to cancel it
+    attached_job: Option<u64>,
+    // `:%s/old/new/c` in progress on the draft or the scratchpad
+    substitute_confirm: Option<Confirmation>,
+    substitute_target: SubstituteTarget,
+    // Scratchpad (`:scratch`)
+    scratchpad: TextInput,
+    scratchpad_open: bool,
+    // Sessions
+    current_session: Option<Session>,
+    session_picker_open: bool,
+    session_list: Vec<Session>,
+    session_list_state: ratatui::widgets::ListState,
     // Prompts tab
     system_prompt: String,
     prompts: Vec<SavedPrompt>,
@@ -60,6 +344,17 @@ pub struct AppState {
     prompt_name_buffer: String,
     prompt_editing_index: Option<usize>,
     prompt_editing_name: bool, // true: editing name, false: editing content
+    // Downloads tab
+    downloads: DownloadManager,
+    downloads_list_state: ratatui::widgets::ListState,
+    // Model pulled from the chat with :model, switched to once the pull completes
+    quick_pull: Option<String>,
+    quick_pull_confirm: bool,
+    quick_pull_popup: bool,
+    // Memory tab
+    memory: Memory,
+    memory_enabled: bool,
+    memory_list_state: ratatui::widgets::ListState,
 }
 
 impl AppState {
@@ -68,7 +363,41 @@ impl AppState {
         state.model_list_state.select(Some(0));
         state.search_list_state.select(Some(0));
         state.prompts_list_state.select(Some(0));
+        state.downloads_list_state.select(Some(0));
+        state.variables_list_state.select(Some(0));
         state.prompts = load_prompts();
+        state.model_settings = model_settings::load();
+        state.model_defaults = model_defaults::load();
+        state.memory = memory::load();
+        state.scratchpad.set_text(scratchpad::load());
+        state.config = Config::load();
+        state.tabs = tabs::tab_order(state.config.tabs.as_deref());
+        state.memory_enabled = state.config.memory;
+        state.retry_banned = state.config.retry_banned;
+        state.prompt_lint = state.config.prompt_lint;
+        state.tag_rules = state
+            .config
+            .reasoning_tags
+            .clone()
+            .unwrap_or_else(channels::default_rules);
+        state.reply_budget = ReplyBudget {
+            seconds: state.config.max_reply_seconds,
+            tokens: state.config.max_reply_tokens,
+        };
+        if let Some(detected) = state.config.detected_host() {
+            state.status_message = Some(format!(
+                " Using Ollama on the {} at {} ",
+                detected.source, detected.host
+            ));
+        }
+        if let Err(e) = timefmt::configure(
+            state.config.time_format.as_deref(),
+            state.config.date_format.as_deref(),
+            state.config.timezone.as_deref(),
+        ) {
+            state.status_error = Some(format!(" Config: {} ", e));
+        }
+        state.memory_list_state.select(Some(0));
         state.prompt_editing_name = true; // 默认先编辑名称
         if let Some(first_prompt) = state.prompts.first() {
             state.system_prompt = first_prompt.content.clone();
@@ -77,12 +406,7 @@ impl AppState {
     }
 
     fn get_prompts_path() -> std::path::PathBuf {
-        let home = std::env::var("HOME").unwrap_or_else(|_| ".".to_string());
-        let config_dir = std::path::Path::new(&home)
-            .join(".config")
-            .join("ollama-cli");
-        let _ = std::fs::create_dir_all(&config_dir);
-        config_dir.join("prompts.json")
+        config::config_dir().join("prompts.json")
     }
 
     fn save_prompts(&self) {
@@ -119,76 +443,213 @@ fn load_prompts() -> Vec<SavedPrompt> {
 
 type SharedState = Arc<Mutex<AppState>>;
 
+/// 连续绘制失败这么多次后放弃 TUI，改用纯文本模式
+const MAX_DRAW_FAILURES: u32 = 5;
+
+/// TUI 退出的原因
+enum AppExit {
+    Quit,
+    /// 终端无法正常绘制，附带最后一次错误
+    RenderFailed(std::io::Error),
+}
+
 fn main() -> Result<()> {
+    // 带参数时以命令行模式运行，不启动 TUI
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let oneshot = if args.first().map(String::as_str) == Some("--oneshot") {
+        match cli::parse_oneshot(&args[1..]) {
+            Ok(oneshot) => Some(oneshot),
+            Err(message) => {
+                eprintln!("{}", message);
+                std::process::exit(2);
+            }
+        }
+    } else if !args.is_empty() {
+        transport::set_endpoint(Config::load().endpoint());
+        std::process::exit(cli::run(args));
+    } else {
+        None
+    };
+
     enable_raw_mode()?;
     let mut terminal = ratatui::init();
     let _ = execute!(
         terminal.backend_mut(),
         EnterAlternateScreen,
-        EnableMouseCapture
+        EnableMouseCapture,
+        EnableBracketedPaste
     );
 
-    let state = Arc::new(Mutex::new(AppState::new()));
+    let mut state = AppState::new();
+    if let Some(oneshot) = oneshot {
+        enter_kiosk(&mut state, oneshot);
+    }
+    transport::set_endpoint(state.config.endpoint());
+    state.connection.start();
+    let state = Arc::new(Mutex::new(state));
 
-    let result = run_app(&mut terminal, state);
+    let result = run_app(&mut terminal, state.clone());
 
-    disable_raw_mode()?;
+    let restored = disable_raw_mode();
     let _ = execute!(
         terminal.backend_mut(),
         LeaveAlternateScreen,
-        DisableMouseCapture
+        DisableMouseCapture,
+        DisableBracketedPaste
     );
     ratatui::restore();
 
+    let result = match result {
+        Ok(AppExit::Quit) => restored.map_err(Into::into),
+        Ok(AppExit::RenderFailed(e)) => {
+            eprintln!(
+                "The terminal could not draw the interface ({}); switched to plain text mode.",
+                e
+            );
+            let engine = {
+                let s = state.blocking_lock();
+                ChatEngine::new(
+                    s.selected_model.clone(),
+                    effective_system_prompt(&s),
+                    request_options(&s),
+                    session::to_messages(&s.messages),
+                )
+            };
+            cli::repl(engine)
+        }
+        Err(e) => Err(e),
+    };
+
+    {
+        let s = state.blocking_lock();
+        if !s.config.keep_server_running {
+            s.server.stop();
+        }
+    }
+
     result
 }
 
-fn run_app(terminal: &mut DefaultTerminal, state: SharedState) -> Result<()> {
+/// 重新加载配置并重建界面状态，保留当前对话、后台下载和由本程序启动的服务
+fn restart_app(state: &SharedState) {
+    let mut guard = state.blocking_lock();
+    let s = &mut *guard;
+    s.conversation.sync(&s.messages);
+
+    let mut fresh = AppState::new();
+    transport::set_endpoint(fresh.config.endpoint());
+    fresh.connection = s.connection.clone();
+    fresh.downloads = s.downloads.clone();
+    fresh.server = s.server.clone();
+    fresh.server_autostart_tried = s.server_autostart_tried;
+    fresh.selected_model = s.selected_model.clone();
+    fresh.system_prompt = s.system_prompt.clone();
+    if fresh.tabs.contains(&s.current_tab) {
+        fresh.current_tab = s.current_tab;
+    }
+
+    // 已保存的会话从磁盘重新读取标题等信息，对话内容沿用内存中的（可能有未保存的消息）
+    fresh.current_session = s
+        .current_session
+        .as_ref()
+        .and_then(|current| session::load_session(&current.id).or(Some(current.clone())));
+    fresh.conversation = std::mem::take(&mut s.conversation);
+    fresh.messages = fresh.conversation.messages();
+    fresh.variables = std::mem::take(&mut s.variables);
+    fresh.banned_phrases = std::mem::take(&mut s.banned_phrases);
+    fresh.notifications = std::mem::take(&mut s.notifications);
+    fresh.stashed_chat = s.stashed_chat.take();
+    fresh.notified_transfers = std::mem::take(&mut s.notified_transfers);
+    // 还没有选定的候选回复继续保留，仍在生成的会写入新的状态
+    fresh.fanout = s.fanout.take();
+    fresh.is_loading = fresh.fanout.as_ref().is_some_and(|f| f.running > 0);
+
+    fresh.connection.wake();
+    fresh.status_message = Some(format!(" Restarted · {} ", transport::endpoint()));
+    *s = fresh;
+}
+
+fn run_app(terminal: &mut DefaultTerminal, state: SharedState) -> Result<AppExit> {
     let runtime = tokio::runtime::Runtime::new()?;
 
     runtime.block_on(async {
         refresh_models(&state).await;
     });
+    check_detached_jobs(&mut state.blocking_lock());
 
+    let mut draw_failures = 0;
     loop {
+        if std::mem::take(&mut state.blocking_lock().restart_requested) {
+            restart_app(&state);
+            runtime.block_on(refresh_models(&state));
+            let _ = terminal.clear();
+        }
+
+        update_quick_pull(&mut state.blocking_lock());
+        update_replay(&mut state.blocking_lock());
+        notify_finished_transfers(&mut state.blocking_lock());
+
+        let (downloaded, reconnected) = {
+            let s = state.blocking_lock();
+            (
+                s.downloads.take_needs_refresh(),
+                s.connection.take_needs_refresh(),
+            )
+        };
+        if downloaded || reconnected {
+            runtime.block_on(refresh_models(&state));
+        }
+        if reconnected {
+            after_reconnect(&mut state.blocking_lock());
+        }
+
+        {
+            let mut s = state.blocking_lock();
+            if s.config.auto_start_server
+                && !s.server_autostart_tried
+                && matches!(s.connection.status(), ConnectionStatus::Unreachable(_))
+            {
+                s.server_autostart_tried = true;
+                if connection::is_local(&transport::endpoint()) {
+                    let message = start_server(&mut s);
+                    s.status_message = Some(format!(" {} ", message));
+                }
+            }
+        }
+
         let redraw_interval = {
             let s = state.blocking_lock();
-            if s.is_loading {
+            if s.is_loading
+                || s.replay
+                    .as_ref()
+                    .is_some_and(|r| !r.is_paused() && !r.is_finished())
+            {
                 50
             } else {
                 500
             }
         };
 
-        terminal.draw(|f| {
+        let drawn = terminal.draw(|f| {
             let mut s = state.blocking_lock();
+            let chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([
+                    Constraint::Length(3),
+                    Constraint::Min(0),
+                    Constraint::Length(3),
+                ])
+                .split(f.area());
+            let msg_area = chunks[1];
+            let viewport_width = msg_area.width.saturating_sub(4).max(1);
+            if let Some(target) = s.chat_scroll_target.take() {
+                let target = target.min(s.messages.len());
+                s.auto_scroll = false;
+                s.chat_scroll = wrapped_line_count(&chat_transcript(&s, target), viewport_width);
+            }
             if s.auto_scroll && s.current_tab == Tab::Chat && !s.messages.is_empty() {
-                let area = f.area();
-                let chunks = Layout::default()
-                    .direction(Direction::Vertical)
-                    .constraints([
-                        Constraint::Length(3),
-                        Constraint::Min(0),
-                        Constraint::Length(3),
-                    ])
-                    .split(area);
-                let msg_area = chunks[1];
-                let viewport_width = msg_area.width.saturating_sub(4).max(1);
-                let mut total_wrapped_lines = 0u16;
-                let mut content = String::new();
-                for msg in &s.messages {
-                    let role = match msg.role.as_str() {
-                        "user" => "You",
-                        "assistant" => "AI",
-                        _ => &msg.role,
-                    };
-                    content.push_str(&format!("{}: {}\n\n", role, msg.content));
-                }
-                for line in content.lines() {
-                    let line_len = line.len() as u16;
-                    let wrapped = (line_len.saturating_sub(1) / viewport_width).saturating_add(1);
-                    total_wrapped_lines = total_wrapped_lines.saturating_add(wrapped.max(1));
-                }
+                let mut total_wrapped_lines =
+                    wrapped_line_count(&chat_transcript(&s, s.messages.len()), viewport_width);
                 total_wrapped_lines =
                     total_wrapped_lines.saturating_add((s.messages.len().saturating_sub(1)) as u16);
                 let viewport_height = msg_area.height.saturating_sub(2);
@@ -196,23 +657,111 @@ fn run_app(terminal: &mut DefaultTerminal, state: SharedState) -> Result<()> {
                 s.chat_scroll = max_scroll;
             }
             ui(f, &mut s);
-        })?;
+        });
+        // 偶发的绘制错误重试即可，连续失败说明终端不受支持
+        match drawn {
+            Ok(_) => draw_failures = 0,
+            Err(e) => {
+                draw_failures += 1;
+                if draw_failures >= MAX_DRAW_FAILURES {
+                    return Ok(AppExit::RenderFailed(e));
+                }
+                let _ = terminal.clear();
+            }
+        }
 
         if event::poll(std::time::Duration::from_millis(redraw_interval))? {
-            if let Event::Key(key) = event::read()? {
-                if key.kind == KeyEventKind::Press {
+            match event::read()? {
+                Event::Key(key) if key.kind == KeyEventKind::Press => {
                     let state = state.clone();
                     let mut s = state.blocking_lock();
+                    s.status_message = None;
+
+                    if s.kiosk {
+                        if key.code == KeyCode::Char('c')
+                            && key.modifiers.contains(KeyModifiers::CONTROL)
+                        {
+                            return Ok(AppExit::Quit);
+                        }
+                        if kiosk_blocks(&s, key.code) {
+                            continue;
+                        }
+                    }
+
+                    // Esc only leaves the app from chat normal mode, elsewhere it backs out
+                    let consumes_esc = (s.current_tab == Tab::Chat
+                        && (s.chat_mode != ChatMode::Normal
+                            || s.session_picker_open
+                            || s.variables_editor_open
+                            || s.message_detail_open
+                            || s.branch_picker_open
+                            || s.substitute_confirm.is_some()
+                            || s.scratchpad_open
+                            || s.notifications_open
+                            || s.replay.is_some()
+                            || s.attached_job.is_some()
+                            || s.retry_offer
+                            || s.quick_pull_popup))
+                        || (s.current_tab == Tab::Models
+                            && (s.model_delete_confirm.is_some() || s.model_info.is_some()))
+                        || (s.current_tab == Tab::Search
+                            && (s.search_browsing || s.tag_picker_open));
 
-                    match s.current_tab {
-                        Tab::Chat => handle_chat_key(&mut s, key.code, &state),
-                        Tab::Models => handle_models_input(&mut s, key.code, &state),
-                        Tab::Search => handle_search_input(&mut s, key.code, &state),
-                        Tab::Prompts => handle_prompts_input(&mut s, key.code),
+                    // 无法连接本机 Ollama 时，S 在后台启动 ollama serve
+                    if key.code == KeyCode::Char('S')
+                        && !is_typing(&s)
+                        && matches!(
+                            s.connection.status(),
+                            ConnectionStatus::Unreachable(_) | ConnectionStatus::Reconnecting(_)
+                        )
+                    {
+                        let message = start_server(&mut s);
+                        s.status_message = Some(format!(" {} ", message));
+                        continue;
                     }
 
-                    if key.code == KeyCode::Esc && !s.prompt_input_mode {
-                        return Ok(());
+                    // 数字键 1-9 直接跳转到对应的标签页
+                    if let KeyCode::Char(c @ '1'..='9') = key.code {
+                        if !is_typing(&s)
+                            && !s.session_picker_open
+                            && !s.variables_editor_open
+                            && !s.branch_picker_open
+                            && s.substitute_confirm.is_none()
+                            && !s.notifications_open
+                            && s.model_delete_confirm.is_none()
+                            && s.model_info.is_none()
+                            && !s.retry_offer
+                            && s.replay.is_none()
+                            && !(s.current_tab == Tab::Chat && pending_fanout(&s).is_some())
+                        {
+                            let index = c as usize - '1' as usize;
+                            if let Some(tab) = s.tabs.get(index).copied() {
+                                s.current_tab = tab;
+                            }
+                            continue;
+                        }
+                    }
+
+                    if key.code == KeyCode::Char('z')
+                        && key.modifiers.contains(KeyModifiers::CONTROL)
+                    {
+                        s.zoomed = !s.zoomed;
+                        continue;
+                    }
+
+                    if key.code == KeyCode::Char('L') && !is_typing(&s) {
+                        s.server_log_open = !s.server_log_open;
+                        continue;
+                    }
+
+                    if let Some(info) = s.current_tab.info() {
+                        (info.handle_key)(&mut s, key, &state);
+                    }
+
+                    if key.code == KeyCode::Esc && consumes_esc {
+                        // handled by the current tab
+                    } else if key.code == KeyCode::Esc && !s.prompt_input_mode && !s.kiosk {
+                        return Ok(AppExit::Quit);
                     } else if key.code == KeyCode::Esc && s.prompt_input_mode {
                         s.prompt_input_mode = false;
                         s.prompt_edit_buffer.clear();
@@ -222,20 +771,66 @@ fn run_app(terminal: &mut DefaultTerminal, state: SharedState) -> Result<()> {
                     }
 
                     if key.code == KeyCode::Tab && !s.prompt_input_mode {
-                        s.current_tab = match s.current_tab {
-                            Tab::Chat => Tab::Models,
-                            Tab::Models => Tab::Search,
-                            Tab::Search => Tab::Prompts,
-                            Tab::Prompts => Tab::Chat,
-                        };
+                        let next = s
+                            .tabs
+                            .iter()
+                            .position(|tab| *tab == s.current_tab)
+                            .map_or(0, |i| (i + 1) % s.tabs.len());
+                        s.current_tab = s.tabs[next];
                     }
                 }
+                Event::Mouse(mouse) => {
+                    let mut s = state.blocking_lock();
+                    handle_mouse(&mut s, mouse);
+                }
+                // 输入法提交的文本和粘贴内容通过 bracketed paste 一次性到达
+                Event::Paste(text) => {
+                    let mut s = state.blocking_lock();
+                    handle_paste(&mut s, &text);
+                }
+                _ => {}
             }
         }
     }
 }
 
+/// 展示模式：只保留聊天标签页，使用固定的模型和新的对话
+fn enter_kiosk(state: &mut AppState, oneshot: cli::Oneshot) {
+    state.kiosk = true;
+    state.tabs = vec![Tab::Chat];
+    state.current_tab = Tab::Chat;
+    state.selected_model = Some(oneshot.model);
+    if let Some(system) = oneshot.system {
+        state.system_prompt = system;
+    }
+    state.chat_mode = ChatMode::Insert;
+}
+
+/// 展示模式下忽略的按键：切换对话、模型和标签页，命令行，导出文件等
+fn kiosk_blocks(state: &AppState, key: KeyCode) -> bool {
+    match key {
+        KeyCode::Tab | KeyCode::BackTab => true,
+        KeyCode::Char('S' | 'L') if !is_typing(state) => true,
+        KeyCode::Char('o' | 'v' | 'b' | 'B' | ':' | 'x' | 'D')
+            if state.chat_mode == ChatMode::Normal =>
+        {
+            !state.message_detail_open
+        }
+        KeyCode::Enter if state.chat_mode == ChatMode::Insert => {
+            state.input.as_str().starts_with("/model ")
+        }
+        _ => false,
+    }
+}
+
 fn ui(frame: &mut Frame, state: &mut AppState) {
+    // 放大时只显示当前标签页，不显示标签栏和状态栏
+    if state.zoomed {
+        state.hit_areas = HitAreas::default();
+        render_tab(frame, state, frame.area());
+        return;
+    }
+
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .constraints([
@@ -245,13 +840,14 @@ fn ui(frame: &mut Frame, state: &mut AppState) {
         ])
         .split(frame.area());
 
-    let tabs = Tabs::new(vec![" Chat ", " Models ", " Search ", " Prompts "])
-        .select(match state.current_tab {
-            Tab::Chat => 0,
-            Tab::Models => 1,
-            Tab::Search => 2,
-            Tab::Prompts => 3,
-        })
+    let tabs = Tabs::new(tabs::tab_labels(&state.tabs))
+        .select(
+            state
+                .tabs
+                .iter()
+                .position(|tab| *tab == state.current_tab)
+                .unwrap_or(0),
+        )
         .style(Style::default().fg(Color::White))
         .highlight_style(
             Style::default()
@@ -262,37 +858,106 @@ fn ui(frame: &mut Frame, state: &mut AppState) {
 
     frame.render_widget(tabs, chunks[0]);
 
-    match state.current_tab {
-        Tab::Chat => render_chat(frame, state, chunks[1]),
-        Tab::Models => render_models(frame, state, chunks[1]),
-        Tab::Search => render_search(frame, state, chunks[1]),
-        Tab::Prompts => render_prompts(frame, state, chunks[1]),
-    }
+    let badges = Paragraph::new(Line::from(header_badges(state)))
+        .alignment(ratatui::layout::Alignment::Right);
+    frame.render_widget(badges, chunks[0]);
+
+    state.hit_areas = HitAreas {
+        tabs: chunks[0],
+        ..HitAreas::default()
+    };
+
+    let body = if state.server_log_open {
+        let split = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(0), Constraint::Length(10)])
+            .split(chunks[1]);
+        render_server_log(frame, state, split[1]);
+        split[0]
+    } else {
+        chunks[1]
+    };
+
+    render_tab(frame, state, body);
 
     let status = state.status_message.clone().unwrap_or_else(|| {
-        if state.is_loading {
+        if state.attached_job.is_some() {
+            " Generating (reattached) · Esc: cancel ".to_string()
+        } else if state.is_loading {
             " Generating... ".to_string()
+        } else if let ConnectionStatus::Unreachable(reason) = state.connection.status() {
+            format!(" {} ", reason)
+        } else if let Some(error) = &state.status_error {
+            error.clone()
         } else if state.prompt_input_mode {
             " Enter: save | Esc: cancel | Tab: switch fields ".to_string()
         } else {
-            let model_info = state
+            let mut model_info = state
                 .selected_model
                 .as_ref()
                 .map(|m| format!("[{}] ", m))
                 .unwrap_or_default();
+            if state.api_mode == ApiMode::Generate {
+                model_info.push_str(if state.generate_raw {
+                    "[generate raw] "
+                } else {
+                    "[generate] "
+                });
+            }
             match state.current_tab {
-                Tab::Chat => format!(
-                    "{}Enter: send | j/k: scroll | g: top | G: bottom | Tab: switch | Esc: quit ",
-                    model_info
-                ),
-                Tab::Models => " j/k: select | Enter: use | Tab: switch | Esc: quit ".to_string(),
+                Tab::Chat => match state.chat_mode {
+                    ChatMode::Insert => format!(
+                        "{}Enter: send | Esc: normal mode | Tab: switch ",
+                        model_info
+                    ),
+                    ChatMode::Normal => {
+                        let hits = if state.chat_search_matches.is_empty() {
+                            String::new()
+                        } else {
+                            format!(
+                                "[{}/{}] ",
+                                state.chat_search_index + 1,
+                                state.chat_search_matches.len()
+                            )
+                        };
+                        format!(
+                            "{}{}i: insert | j/k: scroll | g/G: top/bottom | /: search | n/N: next/prev | x: export code | [/]: select message | Enter: details | b/B: branch/branches | o: sessions | v: variables | l: length | D: default settings | :: command | Esc: quit ",
+                            model_info, hits
+                        )
+                    }
+                    ChatMode::Search => " Enter: find | Esc: cancel ".to_string(),
+                    ChatMode::Command => {
+                        " :save | :new | :sessions | :title [text] | :set k=v | :unset k | :record | :memory | :model <name> | :server start|stop|log | :mode chat|generate | :raw | :suffix | :template "
+                            .to_string()
+                    }
+                },
+                Tab::Models if state.model_delete_confirm.is_some() => {
+                    " y: delete | n / Esc: cancel ".to_string()
+                }
+                Tab::Models if state.model_info.is_some() => {
+                    " 1-5: try sample | a: apply recommended | c: clear saved | Esc: close "
+                        .to_string()
+                }
+                Tab::Models => {
+                    " j/k: select | Space: mark | u: unmark all | d: delete | s: sort | r: reverse | i: info | Enter: use | Tab: switch | Esc: quit "
+                        .to_string()
+                }
                 Tab::Search => {
-                    " j/k: select | Enter: search | Tab: switch | Esc: quit ".to_string()
+                    " j/k: select | Enter: search | Ctrl+P: pull | Tab: switch | Esc: quit "
+                        .to_string()
                 }
                 Tab::Prompts => {
                     " j/k: select | Enter: use | e: edit | n: new | d: delete | Esc: quit "
                         .to_string()
                 }
+                Tab::Downloads => {
+                    " j/k: select | c: cancel | r: retry | x: clear finished | Tab: switch | Esc: quit "
+                        .to_string()
+                }
+                Tab::Memory => {
+                    " j/k: select | d: delete | m: toggle memory | Tab: switch | Esc: quit "
+                        .to_string()
+                }
             }
         }
     });
@@ -304,12 +969,120 @@ fn ui(frame: &mut Frame, state: &mut AppState) {
     frame.render_widget(footer, chunks[2]);
 }
 
-fn render_chat(frame: &mut Frame, state: &AppState, area: ratatui::layout::Rect) {
-    // Split into messages area (flexible) and input area (3 lines)
-    let msg_area = Layout::default()
+fn render_tab(frame: &mut Frame, state: &mut AppState, area: ratatui::layout::Rect) {
+    if let Some(info) = state.current_tab.info() {
+        (info.render)(frame, state, area);
+    }
+}
+
+/// `ollama serve` 的输出，始终显示最新的几行
+fn render_server_log(frame: &mut Frame, state: &AppState, area: ratatui::layout::Rect) {
+    let lines = state.server.log_lines();
+    let height = area.height.saturating_sub(2) as usize;
+    let visible: Vec<Line> = lines
+        .iter()
+        .skip(lines.len().saturating_sub(height))
+        .map(|line| Line::from(line.clone()))
+        .collect();
+    let title = if state.server.is_running() {
+        " ollama serve (running) · L: hide "
+    } else {
+        " ollama serve (stopped) · L: hide "
+    };
+    let log = Paragraph::new(visible)
+        .style(Style::default().fg(Color::DarkGray))
+        .block(Block::default().borders(Borders::ALL).title(title));
+    frame.render_widget(log, area);
+}
+
+/// 标签栏右侧显示的状态标记
+fn header_badges(state: &AppState) -> Vec<Span<'static>> {
+    let mut badges = Vec::new();
+    let (connection, color) = match state.connection.status() {
+        ConnectionStatus::Checking => ("○ connecting".to_string(), Color::DarkGray),
+        ConnectionStatus::Connected(version) => (format!("● ollama {}", version), Color::Green),
+        ConnectionStatus::Unreachable(_) => ("✗ unreachable".to_string(), Color::Red),
+        ConnectionStatus::Reconnecting(attempt) => {
+            (format!("⟳ reconnecting ({})", attempt), Color::Yellow)
+        }
+    };
+    badges.push(Span::styled(
+        format!("{} ", connection),
+        Style::default().fg(color),
+    ));
+    if recorder::is_recording() {
+        badges.push(Span::styled("● REC ", Style::default().fg(Color::Red)));
+    }
+    if state.notifications.unread_errors() > 0 {
+        badges.push(Span::styled(
+            format!("✉ {} ", state.notifications.unread_errors()),
+            Style::default().fg(Color::Red),
+        ));
+    }
+    if let Some(model) = &state.model_defaults_applied {
+        badges.push(Span::styled(
+            format!("◆ {} settings ", model),
+            Style::default().fg(Color::Cyan),
+        ));
+    }
+    if !state.reply_budget.is_unlimited() {
+        badges.push(Span::styled(
+            format!("⏱ {} ", state.reply_budget.label()),
+            Style::default().fg(Color::Yellow),
+        ));
+    }
+    if let Some(player) = &state.replay {
+        badges.push(Span::styled(
+            format!(
+                "{} replay {}x {:.0}/{:.0}s ",
+                if player.is_paused() { "⏸" } else { "▶" },
+                player.speed,
+                player.position(),
+                player.timeline.duration()
+            ),
+            Style::default().fg(Color::Magenta),
+        ));
+    }
+    if state.length_preset != LengthPreset::Normal {
+        badges.push(Span::styled(
+            format!("[{}] ", state.length_preset.label()),
+            Style::default().fg(Color::Cyan),
+        ));
+    }
+    badges
+}
+
+fn render_chat(frame: &mut Frame, state: &mut AppState, area: ratatui::layout::Rect) {
+    // 放大时焦点在输入框（insert 模式）则只显示输入框，否则只显示消息
+    if state.zoomed && state.chat_mode == ChatMode::Insert {
+        render_zoomed_input(frame, state, area);
+        return;
+    }
+    let input_height = if state.zoomed && state.chat_mode == ChatMode::Normal {
+        0
+    } else {
+        3
+    };
+    let hints_height = if input_height > 0 && !state.lint_hints.is_empty() {
+        state.lint_hints.len() as u16 + 1
+    } else {
+        0
+    };
+
+    // Split into messages area (flexible), lint hints and input area (3 lines)
+    let mut msg_area = Layout::default()
         .direction(Direction::Vertical)
-        .constraints([Constraint::Min(0), Constraint::Length(3)])
-        .split(area);
+        .constraints([
+            Constraint::Min(0),
+            Constraint::Length(hints_height),
+            Constraint::Length(input_height),
+        ])
+        .split(area)
+        .to_vec();
+    if hints_height > 0 {
+        render_lint_hints(frame, &state.lint_hints, msg_area[1]);
+    }
+    msg_area.remove(1);
 
     if state.messages.is_empty() {
         let welcome = Paragraph::new("")
@@ -318,25 +1091,18 @@ fn render_chat(frame: &mut Frame, state: &AppState, area: ratatui::layout::Rect)
             .alignment(ratatui::layout::Alignment::Center);
         frame.render_widget(welcome, msg_area[0]);
     } else {
-        let mut content = String::new();
-        for msg in &state.messages {
-            let role = match msg.role.as_str() {
-                "user" => "You",
-                "assistant" => "AI",
-                _ => &msg.role,
-            };
-            content.push_str(&format!("{}: {}\n\n", role, msg.content));
+        let mut content = chat_transcript(state, state.messages.len());
+        for line in fanout_lines(state) {
+            content.push('\n');
+            for span in &line.spans {
+                content.push_str(&span.content);
+            }
         }
 
         let viewport_width = msg_area[0].width.saturating_sub(4).max(1);
         let viewport_height = msg_area[0].height.saturating_sub(2);
 
-        let mut total_wrapped_lines = 0u16;
-        for line in content.lines() {
-            let line_len = line.len() as u16;
-            let wrapped = (line_len.saturating_sub(1) / viewport_width).saturating_add(1);
-            total_wrapped_lines = total_wrapped_lines.saturating_add(wrapped.max(1));
-        }
+        let mut total_wrapped_lines = wrapped_line_count(&content, viewport_width);
         total_wrapped_lines =
             total_wrapped_lines.saturating_add((state.messages.len().saturating_sub(1)) as u16);
 
@@ -348,12 +1114,21 @@ fn render_chat(frame: &mut Frame, state: &AppState, area: ratatui::layout::Rect)
             state.chat_scroll.min(max_scroll)
         };
 
-        let paragraph = Paragraph::new(content)
-            .block(Block::default().borders(Borders::ALL).title(" Messages "))
+        let title = match (&state.current_session, &state.stashed_chat) {
+            (Some(session), _) => format!(" Messages · {} ", session.display_title()),
+            (None, Some(_)) => format!(
+                " Try it · {} (throwaway, :back to return) ",
+                state.selected_model.as_deref().unwrap_or_default()
+            ),
+            (None, None) => " Messages ".to_string(),
+        };
+        let paragraph = Paragraph::new(chat_lines(state, viewport_width))
+            .block(Block::default().borders(Borders::ALL).title(title))
             .wrap(ratatui::widgets::Wrap { trim: false })
             .scroll((scroll, 0));
 
         frame.render_widget(paragraph, msg_area[0]);
+        state.hit_areas.messages = msg_area[0];
 
         if total_wrapped_lines > viewport_height && total_wrapped_lines > 0 {
             let mut sb_state = ratatui::widgets::ScrollbarState::new(total_wrapped_lines as usize)
@@ -368,335 +1143,4441 @@ fn render_chat(frame: &mut Frame, state: &AppState, area: ratatui::layout::Rect)
         }
     }
 
-    let input = Paragraph::new(state.input_text.as_str())
-        .style(Style::default().fg(Color::White))
-        .block(Block::default().borders(Borders::ALL).title(" Input "));
+    let input_width = msg_area[1].width.saturating_sub(2);
+    let (visible_input, cursor_col) = state.input.visible(input_width);
+    let input = match state.chat_mode {
+        ChatMode::Search => Paragraph::new(format!("/{}", state.chat_search_query))
+            .style(Style::default().fg(Color::Yellow))
+            .block(Block::default().borders(Borders::ALL).title(" Search ")),
+        ChatMode::Command => Paragraph::new(format!(":{}", state.chat_command))
+            .style(Style::default().fg(Color::Yellow))
+            .block(Block::default().borders(Borders::ALL).title(" Command ")),
+        ChatMode::Insert => Paragraph::new(visible_input)
+            .style(Style::default().fg(Color::White))
+            .block(Block::default().borders(Borders::ALL).title(" Input ")),
+        ChatMode::Normal => Paragraph::new(visible_input)
+            .style(Style::default().fg(Color::DarkGray))
+            .block(Block::default().borders(Borders::ALL).title(" Input ")),
+    };
 
-    frame.render_widget(input, msg_area[1]);
+    if input_height > 0 {
+        frame.render_widget(input, msg_area[1]);
+    }
+
+    match state.chat_mode {
+        ChatMode::Insert => {
+            frame.set_cursor_position((msg_area[1].x + 1 + cursor_col, msg_area[1].y + 1))
+        }
+        ChatMode::Search | ChatMode::Command => {
+            let query = if state.chat_mode == ChatMode::Search {
+                &state.chat_search_query
+            } else {
+                &state.chat_command
+            };
+            let query_width = input::display_width(query) as u16 + 1;
+            frame.set_cursor_position((
+                msg_area[1].x + 1 + query_width.min(input_width),
+                msg_area[1].y + 1,
+            ))
+        }
+        ChatMode::Normal => {}
+    }
+
+    if state.session_picker_open {
+        render_session_picker(frame, state, area);
+    } else if state.variables_editor_open {
+        render_variables_editor(frame, state, area);
+    } else if state.message_detail_open {
+        render_message_detail(frame, state, area);
+    } else if state.branch_picker_open {
+        render_branch_picker(frame, state, area);
+    } else if let Some(confirm) = &state.substitute_confirm {
+        render_substitute_confirm(frame, confirm, area);
+    } else if state.scratchpad_open {
+        render_scratchpad(frame, state, area);
+    } else if state.notifications_open {
+        render_notifications(frame, state, area);
+    }
+
+    if state.quick_pull_popup {
+        render_quick_pull(frame, state, area);
+    }
+    if state.retry_offer {
+        render_retry_offer(frame, area);
+    }
 }
 
-fn render_models(frame: &mut Frame, state: &AppState, area: ratatui::layout::Rect) {
-    let model_items: Vec<ListItem> = state
-        .models
+fn render_retry_offer(frame: &mut Frame, area: ratatui::layout::Rect) {
+    let popup = centered_rect(50, 25, area);
+    frame.render_widget(Clear, popup);
+    let text = vec![
+        Line::from("Ollama is reachable again."),
+        Line::default(),
+        Line::from("The last reply failed while it was down. Send it again?"),
+        Line::default(),
+        Line::from(Span::styled(
+            "y / Enter: resend | n / Esc: dismiss",
+            Style::default().fg(Color::DarkGray),
+        )),
+    ];
+    frame.render_widget(
+        Paragraph::new(text)
+            .wrap(ratatui::widgets::Wrap { trim: false })
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(" Reconnected "),
+            ),
+        popup,
+    );
+}
+
+fn render_lint_hints(frame: &mut Frame, hints: &[String], area: ratatui::layout::Rect) {
+    let mut lines: Vec<Line> = hints
         .iter()
-        .map(|m| {
-            let size_gb = m.size as f64 / 1_073_741_824.0;
-            let content = format!("{} ({:.1} GB)", m.name, size_gb);
-            ListItem::new(content)
+        .map(|hint| {
+            Line::from(Span::styled(
+                format!(" ⚠ {}", hint),
+                Style::default().fg(Color::Yellow),
+            ))
         })
         .collect();
+    lines.push(Line::from(Span::styled(
+        "   Enter: send anyway | Esc: dismiss | :lint off to disable",
+        Style::default().fg(Color::DarkGray),
+    )));
+    frame.render_widget(Paragraph::new(lines), area);
+}
 
-    if model_items.is_empty() {
-        let empty =
-            Paragraph::new("No models installed. Go to Search tab to find and install models.")
-                .style(Style::default().fg(Color::DarkGray))
-                .block(
-                    Block::default()
-                        .borders(Borders::ALL)
-                        .title(" Installed Models "),
-                );
-        frame.render_widget(empty, area);
-    } else {
-        let list = List::new(model_items)
-            .block(
-                Block::default()
-                    .borders(Borders::ALL)
-                    .title(" Installed Models "),
-            )
-            .highlight_style(
-                Style::default()
-                    .fg(Color::Yellow)
-                    .add_modifier(ratatui::style::Modifier::BOLD),
-            )
-            .highlight_symbol("> ");
+fn render_zoomed_input(frame: &mut Frame, state: &AppState, area: ratatui::layout::Rect) {
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(" Input (Ctrl+Z: restore) ");
+    let inner = block.inner(area);
+    let (lines, row, col) = state.input.wrapped(inner.width);
+    // 光标所在行超出高度时向下滚动
+    let scroll = row.saturating_sub(inner.height.saturating_sub(1));
+    let paragraph = Paragraph::new(lines.into_iter().map(Line::from).collect::<Vec<_>>())
+        .block(block)
+        .scroll((scroll, 0));
+    frame.render_widget(paragraph, area);
+    frame.set_cursor_position((inner.x + col, inner.y + row - scroll));
+}
 
-        let mut list_state = state.model_list_state.clone();
-        frame.render_stateful_widget(list, area, &mut list_state);
+fn render_quick_pull(frame: &mut Frame, state: &AppState, area: ratatui::layout::Rect) {
+    let Some(model) = &state.quick_pull else {
+        return;
+    };
+    let popup = centered_rect(60, 30, area);
+    frame.render_widget(Clear, popup);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(format!(" Pull {} ", model));
+
+    if state.quick_pull_confirm {
+        let text = vec![
+            Line::from(format!("{} is not installed.", model)),
+            Line::default(),
+            Line::from("Pull it now and switch to it when done?"),
+            Line::default(),
+            Line::from(Span::styled(
+                "y: pull | n / Esc: cancel",
+                Style::default().fg(Color::DarkGray),
+            )),
+        ];
+        frame.render_widget(Paragraph::new(text).block(block), popup);
+        return;
     }
-}
 
-fn render_search(frame: &mut Frame, state: &AppState, area: ratatui::layout::Rect) {
+    let inner = block.inner(popup);
+    frame.render_widget(block, popup);
     let chunks = Layout::default()
         .direction(Direction::Vertical)
-        .constraints([Constraint::Length(3), Constraint::Min(0)])
-        .split(area);
+        .constraints([
+            Constraint::Length(1),
+            Constraint::Length(1),
+            Constraint::Min(0),
+        ])
+        .split(inner);
 
-    let status = if state.is_searching {
-        " Searching..."
-    } else {
-        ""
+    let transfer = latest_transfer(state, model);
+    let (status, color) = match transfer.as_ref().map(|t| &t.status) {
+        Some(TransferStatus::Failed(e)) => (format!("failed: {}", e), Color::Red),
+        Some(TransferStatus::Retrying(n)) => (format!("retrying ({})", n), Color::Yellow),
+        Some(TransferStatus::Queued) => ("waiting in the download queue".to_string(), Color::White),
+        Some(_) => (
+            transfer
+                .as_ref()
+                .map(|t| t.detail.clone())
+                .unwrap_or_default(),
+            Color::White,
+        ),
+        None => (String::new(), Color::White),
     };
-    let search_input = Paragraph::new(format!("Search: {}{}", state.search_query, status))
-        .style(Style::default().fg(Color::White))
-        .block(
-            Block::default()
-                .borders(Borders::ALL)
-                .title(" Search Online Models "),
-        );
+    frame.render_widget(
+        Paragraph::new(status).style(Style::default().fg(color)),
+        chunks[0],
+    );
 
-    frame.render_widget(search_input, chunks[0]);
+    let percent = transfer.as_ref().map(|t| t.percent()).unwrap_or(0);
+    let gauge = ratatui::widgets::Gauge::default()
+        .gauge_style(Style::default().fg(Color::Green))
+        .percent(percent);
+    frame.render_widget(gauge, chunks[1]);
 
-    let search_items: Vec<ListItem> = state
-        .search_results
+    frame.render_widget(
+        Paragraph::new("Esc: hide (the pull continues in the Downloads tab)")
+            .style(Style::default().fg(Color::DarkGray)),
+        chunks[2],
+    );
+}
+
+/// 选中消息的详细信息：时间、模型、参数和 token 统计
+fn render_message_detail(frame: &mut Frame, state: &AppState, area: ratatui::layout::Rect) {
+    let Some(entry) = state.chat_selected.and_then(|i| state.messages.get(i)) else {
+        return;
+    };
+    let popup = centered_rect(60, 50, area);
+    frame.render_widget(Clear, popup);
+
+    let field = |name: &str, value: String| {
+        Line::from(vec![
+            Span::styled(format!("{:<14}", name), Style::default().fg(Color::Cyan)),
+            Span::raw(value),
+        ])
+    };
+    let seconds = |ns: u64| format!("{:.2}s", ns as f64 / 1e9);
+
+    let time = Some(timefmt::datetime(entry.timestamp))
+        .filter(|t| entry.timestamp > 0 && !t.is_empty())
+        .unwrap_or_else(|| "-".to_string());
+
+    let mut lines = vec![
+        field("Role", role_label(entry.role()).to_string()),
+        field("Time", time),
+        field(
+            "Length",
+            format!("{} chars", entry.content().chars().count()),
+        ),
+    ];
+    if let Some(phrase) = &entry.banned_phrase {
+        lines.push(field("Stopped", format!("contained \"{}\"", phrase)));
+    }
+    if let Some(reason) = entry.meta.as_ref().and_then(|m| m.stopped.as_ref()) {
+        lines.push(field("Stopped", format!("at the {}", reason)));
+    }
+    if let Some(meta) = &entry.meta {
+        let stats = &meta.stats;
+        lines.push(field("Model", meta.model.clone()));
+        lines.push(field("Mode", meta.mode.clone()));
+        let options = meta
+            .options
+            .as_ref()
+            .map(format_options)
+            .filter(|o| !o.is_empty())
+            .unwrap_or_else(|| "default".to_string());
+        lines.push(field("Options", options));
+        let count = |n: Option<u64>| n.map(|n| n.to_string()).unwrap_or_else(|| "-".to_string());
+        lines.push(field("Prompt tokens", count(stats.prompt_eval_count)));
+        lines.push(field("Reply tokens", count(stats.eval_count)));
+        if let Some(speed) = stats.tokens_per_second() {
+            lines.push(field("Speed", format!("{:.1} tokens/s", speed)));
+        }
+        if let Some(total) = stats.total_duration {
+            lines.push(field("Total time", seconds(total)));
+        }
+        if let Some(load) = stats.load_duration {
+            lines.push(field("Load time", seconds(load)));
+        }
+    }
+
+    let detail = Paragraph::new(lines).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(" Message details (Esc: close) "),
+    );
+    frame.render_widget(detail, popup);
+}
+
+fn render_variables_editor(frame: &mut Frame, state: &mut AppState, area: ratatui::layout::Rect) {
+    let popup = centered_rect(60, 50, area);
+    frame.render_widget(Clear, popup);
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(0), Constraint::Length(3)])
+        .split(popup);
+
+    let items: Vec<ListItem> = state
+        .variables
         .iter()
-        .map(|m| ListItem::new(m.name.clone()))
+        .map(|(key, value)| {
+            ListItem::new(Line::from(vec![
+                Span::styled(key.clone(), Style::default().fg(Color::Cyan)),
+                Span::raw(" = "),
+                Span::raw(value.clone()),
+            ]))
+        })
         .collect();
 
-    if search_items.is_empty() {
-        let empty = Paragraph::new(
-            "Press Enter to load popular models, or type and press Enter to search.",
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(" Variables (a: add | e: edit | d: delete | Esc: close) "),
         )
-        .style(Style::default().fg(Color::DarkGray))
-        .block(Block::default().borders(Borders::ALL));
-        frame.render_widget(empty, chunks[1]);
+        .highlight_style(Style::default().fg(Color::Yellow))
+        .highlight_symbol("> ");
+    frame.render_stateful_widget(list, chunks[0], &mut state.variables_list_state);
+    state.hit_areas.list = chunks[0];
+
+    let input_width = chunks[1].width.saturating_sub(2);
+    let (visible_input, cursor_col) = state.variable_input.visible(input_width);
+    let (text, style) = if state.variable_editing {
+        (visible_input, Style::default().fg(Color::White))
     } else {
-        let list = List::new(search_items)
-            .block(
-                Block::default()
-                    .borders(Borders::ALL)
-                    .title(" Search Results "),
-            )
-            .highlight_style(
-                Style::default()
-                    .fg(Color::Yellow)
-                    .add_modifier(ratatui::style::Modifier::BOLD),
-            )
-            .highlight_symbol("> ");
+        (
+            "Use {{key}} in messages and prompts",
+            Style::default().fg(Color::DarkGray),
+        )
+    };
+    let input = Paragraph::new(text)
+        .style(style)
+        .block(Block::default().borders(Borders::ALL).title(" key=value "));
+    frame.render_widget(input, chunks[1]);
 
-        let mut list_state = state.search_list_state.clone();
-        frame.render_stateful_widget(list, chunks[1], &mut list_state);
+    if state.variable_editing {
+        frame.set_cursor_position((chunks[1].x + 1 + cursor_col, chunks[1].y + 1));
     }
 }
 
-fn render_prompts(frame: &mut Frame, state: &AppState, area: ratatui::layout::Rect) {
-    if state.prompt_input_mode {
-        // 编辑/新建模式
-        let chunks = Layout::default()
-            .direction(Direction::Vertical)
-            .constraints([Constraint::Length(3), Constraint::Min(0)])
-            .split(area);
+fn render_session_picker(frame: &mut Frame, state: &mut AppState, area: ratatui::layout::Rect) {
+    let popup = centered_rect(70, 70, area);
+    frame.render_widget(Clear, popup);
 
-        let name_title = if state.prompt_editing_index.is_some() {
-            " Edit Name "
-        } else {
-            " New Name "
-        };
-        let name_style = if state.prompt_editing_name {
-            Style::default().fg(Color::Yellow)
-        } else {
-            Style::default().fg(Color::DarkGray)
-        };
-        let name_input = Paragraph::new(state.prompt_name_buffer.as_str())
-            .style(name_style)
-            .block(Block::default().borders(Borders::ALL).title(name_title));
-        frame.render_widget(name_input, chunks[0]);
+    if state.session_list.is_empty() {
+        let empty = Paragraph::new("No saved sessions. Use :save in the chat to save one.")
+            .style(Style::default().fg(Color::DarkGray))
+            .block(Block::default().borders(Borders::ALL).title(" Sessions "));
+        frame.render_widget(empty, popup);
+        return;
+    }
 
-        let content_title = if state.prompt_editing_index.is_some() {
-            " Edit Content "
-        } else {
-            " New Content "
-        };
-        let content_style = if state.prompt_editing_name {
-            Style::default().fg(Color::DarkGray)
+    let items: Vec<ListItem> = state
+        .session_list
+        .iter()
+        .map(|session| {
+            let updated = timefmt::datetime(session.updated_at);
+            let meta = format!(
+                "  {} · {} · {} messages",
+                updated,
+                session.model.as_deref().unwrap_or("-"),
+                session.messages.len()
+            );
+            let mut lines = vec![Line::from(Span::styled(
+                session.display_title(),
+                Style::default().add_modifier(ratatui::style::Modifier::BOLD),
+            ))];
+            if let Some(summary) = &session.summary {
+                lines.push(Line::from(format!("  {}", summary)));
+            }
+            lines.push(Line::from(Span::styled(
+                meta,
+                Style::default().fg(Color::DarkGray),
+            )));
+            ListItem::new(lines)
+        })
+        .collect();
+
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(" Sessions (Enter: open | d: delete | Esc: close) "),
+        )
+        .highlight_style(Style::default().fg(Color::Yellow))
+        .highlight_symbol("> ");
+
+    frame.render_stateful_widget(list, popup, &mut state.session_list_state);
+    state.hit_areas.list = popup;
+}
+
+fn render_branch_picker(frame: &mut Frame, state: &mut AppState, area: ratatui::layout::Rect) {
+    let popup = centered_rect(70, 60, area);
+    frame.render_widget(Clear, popup);
+
+    let now = chrono::Local::now().timestamp();
+    let items: Vec<ListItem> = state
+        .branch_list
+        .iter()
+        .map(|branch| {
+            let marker = if branch.current { "● " } else { "  " };
+            let preview = branch.preview.lines().next().unwrap_or("").to_string();
+            let meta = format!(
+                "  {} messages · forks after message {} · {}",
+                branch.len,
+                branch.shared,
+                relative_time(branch.updated_at, now)
+            );
+            ListItem::new(vec![
+                Line::from(vec![
+                    Span::styled(marker, Style::default().fg(Color::Green)),
+                    Span::raw(preview),
+                ]),
+                Line::from(Span::styled(meta, Style::default().fg(Color::DarkGray))),
+            ])
+        })
+        .collect();
+
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(" Branches (Enter: switch | Esc: close) "),
+        )
+        .highlight_style(Style::default().fg(Color::Yellow))
+        .highlight_symbol("> ");
+
+    frame.render_stateful_widget(list, popup, &mut state.branch_list_state);
+}
+
+fn render_notifications(frame: &mut Frame, state: &mut AppState, area: ratatui::layout::Rect) {
+    let popup = centered_rect(80, 70, area);
+    frame.render_widget(Clear, popup);
+
+    let items: Vec<ListItem> = state
+        .notifications
+        .entries()
+        .iter()
+        .map(|n| {
+            let color = match n.kind {
+                notifications::Kind::Error => Color::Red,
+                notifications::Kind::Download => Color::Cyan,
+                notifications::Kind::Generation => Color::Green,
+            };
+            let mut spans = vec![
+                Span::styled(
+                    timefmt::custom(n.time.timestamp(), "%H:%M:%S "),
+                    Style::default().fg(Color::DarkGray),
+                ),
+                Span::styled(format!("{:<6}", n.kind.label()), Style::default().fg(color)),
+                Span::raw(n.message.clone()),
+            ];
+            if n.target.is_some() {
+                spans.push(Span::styled(" ↵", Style::default().fg(Color::DarkGray)));
+            }
+            ListItem::new(Line::from(spans))
+        })
+        .collect();
+
+    let title = format!(
+        " Notifications ({}) (Enter: jump | c: clear | Esc: close) ",
+        items.len()
+    );
+    if items.is_empty() {
+        let empty = Paragraph::new("No notifications yet.")
+            .style(Style::default().fg(Color::DarkGray))
+            .block(Block::default().borders(Borders::ALL).title(title));
+        frame.render_widget(empty, popup);
+        return;
+    }
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .highlight_style(Style::default().fg(Color::Yellow))
+        .highlight_symbol("> ");
+    frame.render_stateful_widget(list, popup, &mut state.notifications_state);
+}
+
+fn render_scratchpad(frame: &mut Frame, state: &AppState, area: ratatui::layout::Rect) {
+    let popup = centered_rect(70, 60, area);
+    frame.render_widget(Clear, popup);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(" Scratchpad (Esc: save & close | :scratch %s/old/new/ replaces) ");
+    let inner = block.inner(popup);
+    let (lines, row, col) = state.scratchpad.wrapped(inner.width);
+    let scroll = row.saturating_sub(inner.height.saturating_sub(1));
+    let paragraph = Paragraph::new(lines.into_iter().map(Line::from).collect::<Vec<_>>())
+        .block(block)
+        .scroll((scroll, 0));
+    frame.render_widget(paragraph, popup);
+    frame.set_cursor_position((inner.x + col, inner.y + row - scroll));
+}
+
+fn render_substitute_confirm(
+    frame: &mut Frame,
+    confirm: &Confirmation,
+    area: ratatui::layout::Rect,
+) {
+    let popup = centered_rect(70, 50, area);
+    frame.render_widget(Clear, popup);
+
+    // 已确认的显示为替换后的文本，当前匹配高亮，其余匹配加下划线
+    let mut segments: Vec<(String, Style)> = Vec::new();
+    let mut last = 0;
+    for (idx, range) in confirm.matches.iter().enumerate() {
+        segments.push((
+            confirm.text[last..range.start].to_string(),
+            Style::default(),
+        ));
+        let original = confirm.text[range.clone()].to_string();
+        if confirm.accepted.contains(range) {
+            segments.push((
+                confirm.substitution.replacement.clone(),
+                Style::default().fg(Color::Green),
+            ));
+        } else if idx == confirm.current {
+            segments.push((
+                original,
+                Style::default().fg(Color::Black).bg(Color::Yellow),
+            ));
+            segments.push((
+                format!("→{}", confirm.substitution.replacement),
+                Style::default().fg(Color::Green),
+            ));
         } else {
-            Style::default().fg(Color::Yellow)
-        };
-        let content_input = Paragraph::new(state.prompt_edit_buffer.as_str())
-            .style(content_style)
-            .block(Block::default().borders(Borders::ALL).title(content_title));
-        frame.render_widget(content_input, chunks[1]);
+            segments.push((
+                original,
+                Style::default().add_modifier(ratatui::style::Modifier::UNDERLINED),
+            ));
+        }
+        last = range.end;
+    }
+    segments.push((confirm.text[last..].to_string(), Style::default()));
+
+    let mut lines = vec![Line::default()];
+    for (text, style) in segments {
+        for (i, part) in text.split('\n').enumerate() {
+            if i > 0 {
+                lines.push(Line::default());
+            }
+            if !part.is_empty() {
+                if let Some(line) = lines.last_mut() {
+                    line.spans.push(Span::styled(part.to_string(), style));
+                }
+            }
+        }
+    }
+
+    let title = format!(
+        " Replace {}/{}? (y: yes | n: no | a: all | l: last | q: stop) ",
+        (confirm.current + 1).min(confirm.matches.len()),
+        confirm.matches.len()
+    );
+    let paragraph = Paragraph::new(lines)
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .wrap(ratatui::widgets::Wrap { trim: false });
+    frame.render_widget(paragraph, popup);
+}
+
+fn session_item_height(session: &Session) -> usize {
+    if session.summary.is_some() {
+        3
     } else {
-        // 列表模式
-        let chunks = Layout::default()
-            .direction(Direction::Horizontal)
-            .constraints([Constraint::Percentage(30), Constraint::Percentage(70)])
-            .split(area);
+        2
+    }
+}
 
-        // 左侧提示词列表
-        let prompt_items: Vec<ListItem> = state
-            .prompts
+fn centered_rect(
+    percent_x: u16,
+    percent_y: u16,
+    area: ratatui::layout::Rect,
+) -> ratatui::layout::Rect {
+    let vertical = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Percentage((100 - percent_y) / 2),
+            Constraint::Percentage(percent_y),
+            Constraint::Percentage((100 - percent_y) / 2),
+        ])
+        .split(area);
+    Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([
+            Constraint::Percentage((100 - percent_x) / 2),
+            Constraint::Percentage(percent_x),
+            Constraint::Percentage((100 - percent_x) / 2),
+        ])
+        .split(vertical[1])[1]
+}
+
+fn render_downloads(frame: &mut Frame, state: &mut AppState, area: ratatui::layout::Rect) {
+    let transfers = state.downloads.snapshot();
+
+    if transfers.is_empty() {
+        let empty = Paragraph::new("No downloads. Press Ctrl+P on a search result to pull it.")
+            .style(Style::default().fg(Color::DarkGray))
+            .block(Block::default().borders(Borders::ALL).title(" Transfers "));
+        frame.render_widget(empty, area);
+        return;
+    }
+
+    let items: Vec<ListItem> = transfers
+        .iter()
+        .map(|t| {
+            let filled = (t.percent() / 5) as usize;
+            let bar = format!("{}{}", "#".repeat(filled), "-".repeat(20 - filled));
+            let total = t.expected_size.unwrap_or(t.total);
+            let status = match &t.status {
+                TransferStatus::Queued => "queued".to_string(),
+                TransferStatus::Checking => "checking".to_string(),
+                TransferStatus::Downloading => t.detail.clone(),
+                TransferStatus::Retrying(n) => format!("retry {} ({})", n, t.detail),
+                TransferStatus::Completed => "done".to_string(),
+                TransferStatus::Failed(e) => format!("failed: {}", e),
+                TransferStatus::Cancelled => "cancelled".to_string(),
+            };
+            let color = match t.status {
+                TransferStatus::Completed => Color::Green,
+                TransferStatus::Failed(_) => Color::Red,
+                TransferStatus::Cancelled => Color::DarkGray,
+                _ => Color::White,
+            };
+            ListItem::new(format!(
+                "{} [{}] {:>3}% {:.1}/{:.1} GB  {}",
+                t.model,
+                bar,
+                t.percent(),
+                t.completed as f64 / 1_073_741_824.0,
+                total as f64 / 1_073_741_824.0,
+                status
+            ))
+            .style(Style::default().fg(color))
+        })
+        .collect();
+
+    // 多标签分组的合并进度，共享的层只计一次
+    let mut title = String::from(" Transfers ");
+    let mut groups: Vec<u64> = Vec::new();
+    for t in &transfers {
+        if let Some(group) = t.group.filter(|g| !groups.contains(g)) {
+            groups.push(group);
+        }
+    }
+    for group in groups {
+        let members: Vec<_> = transfers
             .iter()
-            .map(|p| ListItem::new(p.name.as_str()))
+            .filter(|t| t.group == Some(group))
             .collect();
+        if members.iter().all(|t| t.status.is_finished()) {
+            continue;
+        }
+        let Some((completed, total)) = state.downloads.group_progress(group) else {
+            continue;
+        };
+        let name = members[0].model.split(':').next().unwrap_or_default();
+        let percent = completed
+            .checked_mul(100)
+            .and_then(|v| v.checked_div(total))
+            .unwrap_or(0);
+        title.push_str(&format!(
+            "· {} ×{} {}% of {:.1} GB ",
+            name,
+            members.len(),
+            percent,
+            total as f64 / 1_073_741_824.0
+        ));
+    }
 
-        let list = List::new(prompt_items)
-            .block(
-                Block::default()
-                    .borders(Borders::ALL)
-                    .title(" Saved Prompts "),
-            )
-            .highlight_style(
-                Style::default()
-                    .fg(Color::Yellow)
-                    .add_modifier(ratatui::style::Modifier::BOLD),
-            )
-            .highlight_symbol("> ");
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .highlight_style(
+            Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(ratatui::style::Modifier::BOLD),
+        )
+        .highlight_symbol("> ");
 
-        let mut list_state = state.prompts_list_state.clone();
-        frame.render_stateful_widget(list, chunks[0], &mut list_state);
+    frame.render_stateful_widget(list, area, &mut state.downloads_list_state);
+    state.hit_areas.list = area;
+}
 
-        // 右侧当前选中提示词内容
-        let current_content = if let Some(selected) = state.prompts_list_state.selected() {
-            state
-                .prompts
-                .get(selected)
-                .map(|p| p.content.as_str())
-                .unwrap_or("No prompt selected")
-        } else {
-            "No prompt selected"
-        };
+fn render_memory(frame: &mut Frame, state: &mut AppState, area: ratatui::layout::Rect) {
+    let entries = state.memory.entries();
+    let title = format!(
+        " Memory ({}) · {} facts ",
+        if state.memory_enabled { "on" } else { "off" },
+        entries.len()
+    );
 
-        let active_indicator = if let Some(selected) = state.prompts_list_state.selected() {
-            if let Some(prompt) = state.prompts.get(selected) {
-                if prompt.content == state.system_prompt {
-                    " [ACTIVE]"
-                } else {
-                    ""
-                }
+    if entries.is_empty() {
+        let empty = Paragraph::new(
+            "Nothing remembered yet. With memory on, facts from your conversations are saved here.",
+        )
+        .style(Style::default().fg(Color::DarkGray))
+        .block(Block::default().borders(Borders::ALL).title(title));
+        frame.render_widget(empty, area);
+        return;
+    }
+
+    let items: Vec<ListItem> = entries
+        .iter()
+        .map(|entry| {
+            let created = timefmt::date(entry.created_at);
+            ListItem::new(Line::from(vec![
+                Span::styled(
+                    format!("{}  ", created),
+                    Style::default().fg(Color::DarkGray),
+                ),
+                Span::raw(entry.fact.clone()),
+            ]))
+        })
+        .collect();
+
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .highlight_style(
+            Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(ratatui::style::Modifier::BOLD),
+        )
+        .highlight_symbol("> ");
+
+    frame.render_stateful_widget(list, area, &mut state.memory_list_state);
+    state.hit_areas.list = area;
+}
+
+fn role_label(role: &str) -> &str {
+    match role {
+        "user" => "You",
+        "assistant" => "AI",
+        _ => role,
+    }
+}
+
+/// 消息前显示的相对时间（如 `5m`），没有记录时间的旧消息返回空字符串
+fn relative_time(timestamp: i64, now: i64) -> String {
+    if timestamp <= 0 {
+        return String::new();
+    }
+    let elapsed = (now - timestamp).max(0);
+    match elapsed {
+        0..=59 => "now".to_string(),
+        60..=3599 => format!("{}m", elapsed / 60),
+        3600..=86_399 => format!("{}h", elapsed / 3600),
+        86_400..=604_799 => format!("{}d", elapsed / 86_400),
+        _ => timefmt::custom(timestamp, "%m-%d"),
+    }
+}
+
+/// 消息首行的前缀：相对时间和角色
+fn message_prefix(entry: &ChatEntry, now: i64) -> (String, String) {
+    let time = relative_time(entry.timestamp, now);
+    let time = if time.is_empty() {
+        time
+    } else {
+        format!("{} ", time)
+    };
+    (time, format!("{}: ", role_label(entry.role())))
+}
+
+/// 前 `count` 条消息按显示时的样子拼成的纯文本，用于计算滚动位置
+fn chat_transcript(state: &AppState, count: usize) -> String {
+    let now = chrono::Local::now().timestamp();
+    let mut content = String::new();
+    for (i, entry) in state.messages.iter().take(count).enumerate() {
+        let (time, role) = message_prefix(entry, now);
+        let badge = language_badge(message_language(entry));
+        content.push_str(&format!("{}{}{}", time, role, badge));
+        for (n, line) in message_lines(state, i).iter().enumerate() {
+            if n > 0 {
+                content.push('\n');
+            }
+            if line.kind == channels::LineKind::Body {
+                content.push_str("│ ");
+            }
+            content.push_str(&line.text);
+        }
+        content.push_str("\n\n");
+    }
+    content
+}
+
+fn wrapped_line_count(content: &str, viewport_width: u16) -> u16 {
+    let mut total_wrapped_lines = 0u16;
+    for line in content.lines() {
+        let line_len = input::display_width(line) as u16;
+        let wrapped = (line_len.saturating_sub(1) / viewport_width).saturating_add(1);
+        total_wrapped_lines = total_wrapped_lines.saturating_add(wrapped.max(1));
+    }
+    total_wrapped_lines
+}
+
+/// 回复的语言，用于显示标记和调整排版
+fn message_language(entry: &ChatEntry) -> Option<&lang::Language> {
+    entry.language.as_ref()
+}
+
+fn language_badge(language: Option<&lang::Language>) -> String {
+    language
+        .map(|l| format!("[{}] ", l.code()))
+        .unwrap_or_default()
+}
+
+/// 渲染消息列表，高亮搜索命中，当前命中使用醒目颜色。
+/// 从右到左的语言整条右对齐，CJK 文本按 `width` 逐字断行
+fn chat_lines(state: &AppState, width: u16) -> Vec<Line<'static>> {
+    let needle = state.chat_search_query.as_str();
+    let current = state
+        .chat_search_matches
+        .get(state.chat_search_index)
+        .copied();
+    let now = chrono::Local::now().timestamp();
+    let mut lines = Vec::new();
+    for (i, entry) in state.messages.iter().enumerate() {
+        let current_offset = current.filter(|(m, _)| *m == i).map(|(_, o)| o);
+        let language = message_language(entry);
+        for (n, display) in message_lines(state, i).into_iter().enumerate() {
+            let mut spans = Vec::new();
+            if n == 0 {
+                let (time, role) = message_prefix(entry, now);
+                spans.push(Span::styled(time, Style::default().fg(Color::DarkGray)));
+                let role_style = if state.chat_selected == Some(i) {
+                    Style::default().add_modifier(ratatui::style::Modifier::REVERSED)
+                } else {
+                    Style::default()
+                };
+                spans.push(Span::styled(role, role_style));
+                if entry.banned_phrase.is_some() {
+                    spans.push(Span::styled("⚑ ", Style::default().fg(Color::Red)));
+                }
+                if entry.meta.as_ref().is_some_and(|m| m.stopped.is_some()) {
+                    spans.push(Span::styled("⏱ ", Style::default().fg(Color::Yellow)));
+                }
+                spans.push(Span::styled(
+                    language_badge(language),
+                    Style::default().fg(Color::DarkGray),
+                ));
+            }
+            let tag_style = display
+                .channel
+                .map(|rule| channel_style(&state.tag_rules[rule]))
+                .unwrap_or_default();
+            let text = display.text.as_str();
+            if display.kind == channels::LineKind::Header {
+                spans.push(Span::styled(
+                    display.text.clone(),
+                    tag_style.add_modifier(ratatui::style::Modifier::ITALIC),
+                ));
+                lines.push(Line::from(spans));
+                continue;
+            }
+            if display.kind == channels::LineKind::Body {
+                spans.push(Span::styled("│ ", tag_style));
+            }
+            let offset = display.offset.unwrap_or(0);
+            let pieces = match language {
+                Some(language) if language.is_cjk() && display.channel.is_none() => {
+                    let prefix: usize = spans.iter().map(|s| s.width()).sum();
+                    lang::wrap_cjk(
+                        text,
+                        (width as usize).saturating_sub(prefix),
+                        width as usize,
+                    )
+                }
+                _ => vec![(0, text)],
+            };
+            for (piece_offset, piece) in pieces {
+                let mut piece_spans = std::mem::take(&mut spans);
+                piece_spans.extend(
+                    highlight_spans(piece, needle, offset + piece_offset, current_offset)
+                        .into_iter()
+                        .map(|span| {
+                            // 搜索高亮优先，其余部分用通道的颜色
+                            if span.style == Style::default() {
+                                span.style(tag_style)
+                            } else {
+                                span
+                            }
+                        }),
+                );
+                let line = Line::from(piece_spans);
+                lines.push(if language.is_some_and(|l| l.is_rtl()) {
+                    line.alignment(ratatui::layout::Alignment::Right)
+                } else {
+                    line
+                });
+            }
+        }
+        lines.push(Line::default());
+    }
+    lines.extend(fanout_lines(state));
+    lines
+}
+
+/// 一条消息按推理等通道拆分后的显示行，只处理 AI 回复
+fn message_lines(state: &AppState, index: usize) -> Vec<channels::DisplayLine> {
+    let entry = &state.messages[index];
+    let rules: &[channels::TagRule] = if entry.role() == "assistant" {
+        &state.tag_rules
+    } else {
+        &[]
+    };
+    let expanded = state
+        .expanded_channels
+        .get(&index)
+        .copied()
+        .or(state.all_channels_expanded);
+    channels::display_lines(entry.content(), rules, expanded)
+}
+
+fn channel_style(rule: &channels::TagRule) -> Style {
+    let color = rule
+        .color
+        .as_deref()
+        .and_then(|c| c.parse::<Color>().ok())
+        .unwrap_or(Color::DarkGray);
+    Style::default().fg(color)
+}
+
+/// `t`：展开或折叠选中（或最后一条）回复中的推理等通道内容
+fn toggle_channels(state: &mut AppState) {
+    let Some(index) = state
+        .chat_selected
+        .or_else(|| state.messages.iter().rposition(|m| m.role() == "assistant"))
+    else {
+        return;
+    };
+    let expanded = message_lines(state, index)
+        .iter()
+        .any(|line| line.kind == channels::LineKind::Body);
+    state.expanded_channels.insert(index, !expanded);
+}
+
+fn highlight_spans(
+    text: &str,
+    needle: &str,
+    offset: usize,
+    current: Option<usize>,
+) -> Vec<Span<'static>> {
+    let mut spans = Vec::new();
+    let mut last = 0;
+    for std::ops::Range { start, end } in lang::find_ignore_case(text, needle) {
+        if start > last {
+            spans.push(Span::raw(text[last..start].to_string()));
+        }
+        let style = if current == Some(offset + start) {
+            Style::default().fg(Color::Black).bg(Color::Yellow)
+        } else {
+            Style::default().bg(Color::DarkGray)
+        };
+        spans.push(Span::styled(text[start..end].to_string(), style));
+        last = end;
+    }
+    if last < text.len() {
+        spans.push(Span::raw(text[last..].to_string()));
+    }
+    spans
+}
+
+/// 大小写不敏感地查找所有消息中的命中位置
+fn find_chat_matches(messages: &[ChatEntry], query: &str) -> Vec<(usize, usize)> {
+    messages
+        .iter()
+        .enumerate()
+        .flat_map(|(i, entry)| {
+            lang::find_ignore_case(entry.content(), query)
+                .into_iter()
+                .map(move |range| (i, range.start))
+        })
+        .collect()
+}
+
+/// 滚动到当前命中；命中在折叠的推理等通道里时展开该条回复
+fn jump_to_search_match(state: &mut AppState) {
+    let Some(&(message, offset)) = state.chat_search_matches.get(state.chat_search_index) else {
+        return;
+    };
+    state.chat_scroll_target = Some(message);
+    let shown = |state: &AppState| {
+        message_lines(state, message).iter().any(|line| {
+            line.offset
+                .is_some_and(|start| (start..start + line.text.len()).contains(&offset))
+        })
+    };
+    if shown(state) {
+        return;
+    }
+    let previous = state.expanded_channels.insert(message, true);
+    // 命中在标记本身等展开后也不显示的地方时，保持原来的折叠状态
+    if !shown(state) {
+        match previous {
+            Some(expanded) => state.expanded_channels.insert(message, expanded),
+            None => state.expanded_channels.remove(&message),
+        };
+    }
+}
+
+fn render_models(frame: &mut Frame, state: &mut AppState, area: ratatui::layout::Rect) {
+    let title = format!(
+        " Installed Models ({}) · sort: {} {} ",
+        transport::endpoint(),
+        state.model_sort.label(),
+        if state.model_sort_desc { "↓" } else { "↑" }
+    );
+
+    if state.models.is_empty() {
+        let empty =
+            Paragraph::new("No models installed. Go to Search tab to find and install models.")
+                .style(Style::default().fg(Color::DarkGray))
+                .block(Block::default().borders(Borders::ALL).title(title));
+        frame.render_widget(empty, area);
+        return;
+    }
+
+    let total: i64 = state.models.iter().map(|m| m.size).sum();
+    let marked: i64 = state
+        .models
+        .iter()
+        .filter(|m| state.model_marked.contains(&m.name))
+        .map(|m| m.size)
+        .sum();
+    let mut footer = format!(
+        " {} models · {} on disk ",
+        state.models.len(),
+        format_gb(total)
+    );
+    if !state.model_marked.is_empty() {
+        footer.push_str(&format!(
+            "· {} marked ({}) ",
+            state.model_marked.len(),
+            format_gb(marked)
+        ));
+    }
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(title)
+        .title_bottom(footer);
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+
+    let name_width = state
+        .models
+        .iter()
+        .map(|m| input::display_width(&m.name))
+        .max()
+        .unwrap_or(0)
+        .clamp(20, 40);
+    let row = |mark: &str,
+               name: &str,
+               size: &str,
+               family: &str,
+               params: &str,
+               quant: &str,
+               modified: &str| {
+        format!(
+            "{} {:<name_width$} {:>9}  {:<10} {:<7} {:<8} {}",
+            mark, name, size, family, params, quant, modified
+        )
+    };
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(1), Constraint::Min(0)])
+        .split(inner);
+    let header = Paragraph::new(format!(
+        "  {}",
+        row("   ", "NAME", "SIZE", "FAMILY", "PARAMS", "QUANT", "MODIFIED")
+    ))
+    .style(Style::default().fg(Color::DarkGray));
+    frame.render_widget(header, chunks[0]);
+
+    let model_items: Vec<ListItem> = state
+        .models
+        .iter()
+        .map(|m| {
+            let details = m.details.clone().unwrap_or_default();
+            let marked = state.model_marked.contains(&m.name);
+            let content = row(
+                if marked { "[x]" } else { "[ ]" },
+                &m.name,
+                &format_gb(m.size),
+                &details.family,
+                &details.parameter_size,
+                &details.quantization_level,
+                &model_modified(m)
+                    .map(|t| timefmt::date(t.timestamp()))
+                    .unwrap_or_default(),
+            );
+            let style = if marked {
+                Style::default().fg(Color::Red)
+            } else {
+                Style::default()
+            };
+            ListItem::new(content).style(style)
+        })
+        .collect();
+
+    let list = List::new(model_items)
+        .highlight_style(
+            Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(ratatui::style::Modifier::BOLD),
+        )
+        .highlight_symbol("> ");
+
+    frame.render_stateful_widget(list, chunks[1], &mut state.model_list_state);
+    // 鼠标命中按带边框的列表计算：表头占上边框的位置
+    state.hit_areas.list = Rect {
+        y: chunks[1].y - 1,
+        height: chunks[1].height + 2,
+        ..chunks[1]
+    };
+
+    if let Some(names) = &state.model_delete_confirm {
+        render_delete_confirm(frame, state, names, area);
+    } else if let Some(name) = &state.model_info {
+        render_model_info(frame, state, name, area);
+    }
+}
+
+fn render_model_info(frame: &mut Frame, state: &AppState, name: &str, area: ratatui::layout::Rect) {
+    let popup = if state.zoomed {
+        area
+    } else {
+        centered_rect(70, 70, area)
+    };
+    frame.render_widget(Clear, popup);
+
+    let label = |text: &str| {
+        Span::styled(
+            format!("{:<16}", text),
+            Style::default().fg(Color::DarkGray),
+        )
+    };
+    let heading = |text: &str| {
+        Line::from(Span::styled(
+            text.to_string(),
+            Style::default().add_modifier(ratatui::style::Modifier::BOLD),
+        ))
+    };
+    let mut lines = Vec::new();
+    if let Some(model) = state.models.iter().find(|m| m.name == name) {
+        let details = model.details.clone().unwrap_or_default();
+        lines.push(Line::from(vec![
+            label("Size"),
+            Span::raw(format_gb(model.size)),
+        ]));
+        lines.push(Line::from(vec![label("Family"), Span::raw(details.family)]));
+        lines.push(Line::from(vec![
+            label("Parameters"),
+            Span::raw(details.parameter_size),
+        ]));
+        lines.push(Line::from(vec![
+            label("Quantization"),
+            Span::raw(details.quantization_level),
+        ]));
+        lines.push(Line::from(vec![
+            label("GPU offload"),
+            Span::raw(
+                state
+                    .model_offload
+                    .clone()
+                    .unwrap_or_else(|| "estimating...".to_string()),
+            ),
+        ]));
+    }
+
+    lines.push(Line::default());
+    lines.push(heading("Saved settings"));
+    match state.model_settings.get(name).filter(|o| !o.is_empty()) {
+        Some(options) => {
+            for (key, value) in options.entries() {
+                lines.push(Line::from(vec![label(key), Span::raw(value)]));
+            }
+        }
+        None => lines.push(Line::from(Span::styled(
+            "none (server defaults)",
+            Style::default().fg(Color::DarkGray),
+        ))),
+    }
+
+    if !state.sample_prompts.is_empty() {
+        lines.push(Line::default());
+        lines.push(heading(
+            "Try it (press a number to send in a throwaway chat)",
+        ));
+        for (i, prompt) in state.sample_prompts.iter().enumerate() {
+            lines.push(Line::from(vec![
+                Span::styled(format!("{}. ", i + 1), Style::default().fg(Color::Yellow)),
+                Span::raw(prompt.clone()),
+            ]));
+        }
+    }
+
+    lines.push(Line::default());
+    lines.push(heading("Recommended (ollama.com)"));
+    if state.recommended_loading {
+        lines.push(Line::from(Span::styled(
+            "Loading...",
+            Style::default().fg(Color::DarkGray),
+        )));
+    } else if let Some(error) = &state.recommended_error {
+        lines.push(Line::from(Span::styled(
+            error.clone(),
+            Style::default().fg(Color::Red),
+        )));
+    } else if let Some(recommended) = &state.recommended {
+        if recommended.options.is_empty() {
+            lines.push(Line::from(Span::styled(
+                "no recommended parameters",
+                Style::default().fg(Color::DarkGray),
+            )));
+        }
+        for (key, value) in recommended.options.entries() {
+            lines.push(Line::from(vec![label(key), Span::raw(value)]));
+        }
+        if let Some(system) = &recommended.system {
+            lines.push(Line::from(vec![
+                label("system"),
+                Span::raw(system.trim().to_string()),
+            ]));
+        }
+        if let Some(template) = &recommended.template {
+            lines.push(Line::from(label("template")));
+            for line in template.lines().take(8) {
+                lines.push(Line::from(Span::styled(
+                    format!("  {}", line),
+                    Style::default().fg(Color::DarkGray),
+                )));
+            }
+            if template.lines().count() > 8 {
+                lines.push(Line::from(Span::styled(
+                    "  ...",
+                    Style::default().fg(Color::DarkGray),
+                )));
+            }
+        }
+    }
+
+    let paragraph = Paragraph::new(lines)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(format!(" {} ", name)),
+        )
+        .wrap(ratatui::widgets::Wrap { trim: false });
+    frame.render_widget(paragraph, popup);
+}
+
+fn render_delete_confirm(
+    frame: &mut Frame,
+    state: &AppState,
+    names: &[String],
+    area: ratatui::layout::Rect,
+) {
+    let size: i64 = state
+        .models
+        .iter()
+        .filter(|m| names.contains(&m.name))
+        .map(|m| m.size)
+        .sum();
+    let mut lines = vec![
+        Line::from(format!(
+            "Delete {} model{} ({})?",
+            names.len(),
+            if names.len() == 1 { "" } else { "s" },
+            format_gb(size)
+        )),
+        Line::default(),
+    ];
+    lines.extend(
+        names
+            .iter()
+            .take(8)
+            .map(|name| Line::from(format!("  {}", name))),
+    );
+    if names.len() > 8 {
+        lines.push(Line::from(format!("  ... and {} more", names.len() - 8)));
+    }
+    lines.push(Line::default());
+    lines.push(Line::from(Span::styled(
+        "y: delete | n / Esc: cancel",
+        Style::default().fg(Color::DarkGray),
+    )));
+
+    let popup = centered_rect(50, 50, area);
+    frame.render_widget(Clear, popup);
+    let dialog = Paragraph::new(lines)
+        .style(Style::default().fg(Color::Red))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(" Confirm delete "),
+        );
+    frame.render_widget(dialog, popup);
+}
+
+fn format_gb(bytes: i64) -> String {
+    format!("{:.1} GB", bytes as f64 / 1_073_741_824.0)
+}
+
+fn model_modified(model: &ollama::Model) -> Option<chrono::DateTime<chrono::FixedOffset>> {
+    chrono::DateTime::parse_from_rfc3339(model.modified_at.as_deref()?).ok()
+}
+
+/// 按当前排序列重新排序，保持选中的模型不变
+fn sort_models(state: &mut AppState) {
+    let selected = state
+        .model_list_state
+        .selected()
+        .and_then(|i| state.models.get(i))
+        .map(|m| m.name.clone());
+
+    let family = |m: &ollama::Model| {
+        m.details
+            .as_ref()
+            .map(|d| d.family.clone())
+            .unwrap_or_default()
+    };
+    match state.model_sort {
+        ModelSort::Name => state.models.sort_by(|a, b| a.name.cmp(&b.name)),
+        ModelSort::Size => state.models.sort_by_key(|m| m.size),
+        ModelSort::Family => state
+            .models
+            .sort_by(|a, b| family(a).cmp(&family(b)).then(a.name.cmp(&b.name))),
+        ModelSort::Modified => state.models.sort_by_key(model_modified),
+    }
+    if state.model_sort_desc {
+        state.models.reverse();
+    }
+
+    let index = selected
+        .and_then(|name| state.models.iter().position(|m| m.name == name))
+        .unwrap_or(0);
+    state.model_list_state.select(Some(index));
+}
+
+fn render_search(frame: &mut Frame, state: &mut AppState, area: ratatui::layout::Rect) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(3), Constraint::Min(0)])
+        .split(area);
+
+    let status = if state.is_searching {
+        " Searching..."
+    } else {
+        ""
+    };
+    let title = match state.search_backend {
+        Some(backend) => format!(" Search Online Models · via {} ", backend),
+        None => " Search Online Models ".to_string(),
+    };
+    let search_input = Paragraph::new(format!("Search: {}{}", state.search_query, status))
+        .style(Style::default().fg(Color::White))
+        .block(Block::default().borders(Borders::ALL).title(title));
+
+    frame.render_widget(search_input, chunks[0]);
+    // 终端在光标处绘制输入法的预编辑文本
+    let query_width = input::display_width(&format!("Search: {}", state.search_query)) as u16;
+    frame.set_cursor_position((
+        chunks[0].x + 1 + query_width.min(chunks[0].width.saturating_sub(3)),
+        chunks[0].y + 1,
+    ));
+
+    let search_items: Vec<ListItem> = state
+        .search_results
+        .iter()
+        .map(|m| {
+            let mut spans = vec![Span::raw(m.name.clone())];
+            if let Some(description) = &m.description {
+                spans.push(Span::styled(
+                    format!("  {}", description),
+                    Style::default().fg(Color::DarkGray),
+                ));
+            }
+            ListItem::new(Line::from(spans))
+        })
+        .collect();
+
+    if let Some(error) = state
+        .search_error
+        .as_ref()
+        .filter(|_| search_items.is_empty())
+    {
+        let message = Paragraph::new(format!("Search failed: {}", error))
+            .style(Style::default().fg(Color::Red))
+            .wrap(ratatui::widgets::Wrap { trim: true })
+            .block(Block::default().borders(Borders::ALL));
+        frame.render_widget(message, chunks[1]);
+    } else if search_items.is_empty() {
+        let empty = Paragraph::new(
+            "Press Enter to load popular models, or type and press Enter to search.",
+        )
+        .style(Style::default().fg(Color::DarkGray))
+        .block(Block::default().borders(Borders::ALL));
+        frame.render_widget(empty, chunks[1]);
+    } else {
+        let list = List::new(search_items)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(if state.search_browsing {
+                        format!(
+                            " Library A-Z · {} models (type a letter to jump, Esc: back) ",
+                            state.search_results.len()
+                        )
+                    } else {
+                        " Search Results ".to_string()
+                    }),
+            )
+            .highlight_style(
+                Style::default()
+                    .fg(Color::Yellow)
+                    .add_modifier(ratatui::style::Modifier::BOLD),
+            )
+            .highlight_symbol("> ");
+
+        frame.render_stateful_widget(list, chunks[1], &mut state.search_list_state);
+        state.hit_areas.list = chunks[1];
+    }
+
+    if state.tag_picker_open {
+        render_tag_picker(frame, state, area);
+    }
+}
+
+fn render_prompts(frame: &mut Frame, state: &mut AppState, area: ratatui::layout::Rect) {
+    if state.prompt_input_mode {
+        // 编辑/新建模式
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(3), Constraint::Min(0)])
+            .split(area);
+
+        let name_title = if state.prompt_editing_index.is_some() {
+            " Edit Name "
+        } else {
+            " New Name "
+        };
+        let name_style = if state.prompt_editing_name {
+            Style::default().fg(Color::Yellow)
+        } else {
+            Style::default().fg(Color::DarkGray)
+        };
+        let name_input = Paragraph::new(state.prompt_name_buffer.as_str())
+            .style(name_style)
+            .block(Block::default().borders(Borders::ALL).title(name_title));
+        frame.render_widget(name_input, chunks[0]);
+
+        let content_title = if state.prompt_editing_index.is_some() {
+            " Edit Content "
+        } else {
+            " New Content "
+        };
+        let content_style = if state.prompt_editing_name {
+            Style::default().fg(Color::DarkGray)
+        } else {
+            Style::default().fg(Color::Yellow)
+        };
+        let content_input = Paragraph::new(state.prompt_edit_buffer.as_str())
+            .style(content_style)
+            .block(Block::default().borders(Borders::ALL).title(content_title));
+        frame.render_widget(content_input, chunks[1]);
+
+        // 光标放在正在编辑的字段末尾，输入法预编辑文本会显示在此处
+        if state.prompt_editing_name {
+            let width = input::display_width(&state.prompt_name_buffer) as u16;
+            frame.set_cursor_position((
+                chunks[0].x + 1 + width.min(chunks[0].width.saturating_sub(3)),
+                chunks[0].y + 1,
+            ));
+        } else {
+            let inner_width = chunks[1].width.saturating_sub(2).max(1);
+            let last_line = state.prompt_edit_buffer.rsplit('\n').next().unwrap_or("");
+            let lines = wrapped_line_count(&state.prompt_edit_buffer, inner_width).max(1);
+            let col = input::display_width(last_line) as u16 % inner_width;
+            let row = (lines - 1).min(chunks[1].height.saturating_sub(3));
+            frame.set_cursor_position((chunks[1].x + 1 + col, chunks[1].y + 1 + row));
+        }
+    } else {
+        // 列表模式
+        let chunks = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(30), Constraint::Percentage(70)])
+            .split(area);
+
+        // 左侧提示词列表
+        let prompt_items: Vec<ListItem> = state
+            .prompts
+            .iter()
+            .map(|p| ListItem::new(p.name.as_str()))
+            .collect();
+
+        let list = List::new(prompt_items)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(" Saved Prompts "),
+            )
+            .highlight_style(
+                Style::default()
+                    .fg(Color::Yellow)
+                    .add_modifier(ratatui::style::Modifier::BOLD),
+            )
+            .highlight_symbol("> ");
+
+        frame.render_stateful_widget(list, chunks[0], &mut state.prompts_list_state);
+        state.hit_areas.list = chunks[0];
+
+        // 右侧当前选中提示词内容
+        let current_content = if let Some(selected) = state.prompts_list_state.selected() {
+            state
+                .prompts
+                .get(selected)
+                .map(|p| p.content.as_str())
+                .unwrap_or("No prompt selected")
+        } else {
+            "No prompt selected"
+        };
+
+        let active_indicator = if let Some(selected) = state.prompts_list_state.selected() {
+            if let Some(prompt) = state.prompts.get(selected) {
+                if prompt.content == state.system_prompt {
+                    " [ACTIVE]"
+                } else {
+                    ""
+                }
+            } else {
+                ""
+            }
+        } else {
+            ""
+        };
+
+        let preview = Paragraph::new(current_content)
+            .style(Style::default().fg(Color::White))
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(format!(" Preview{} ", active_indicator)),
+            )
+            .wrap(ratatui::widgets::Wrap { trim: false });
+        frame.render_widget(preview, chunks[1]);
+    }
+}
+
+fn handle_chat_key(state: &mut AppState, key: KeyCode, shared_state: &SharedState) {
+    // 生成中只处理 Esc：关闭弹窗或回到普通模式，普通模式下再按 Esc 照常退出
+    if state.is_loading && key != KeyCode::Esc {
+        return;
+    }
+
+    if state.quick_pull_popup {
+        handle_quick_pull_key(state, key);
+        return;
+    }
+
+    if state.retry_offer {
+        match key {
+            KeyCode::Char('y') | KeyCode::Char('Y') | KeyCode::Enter => {
+                let message = resend_failed_reply(state, shared_state);
+                state.status_message = Some(format!(" {} ", message));
+            }
+            KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => {
+                state.retry_offer = false;
+            }
+            _ => {}
+        }
+        return;
+    }
+
+    if state.session_picker_open {
+        handle_session_picker_key(state, key);
+        return;
+    }
+
+    if state.variables_editor_open {
+        handle_variables_editor_key(state, key);
+        return;
+    }
+
+    if state.message_detail_open {
+        if matches!(key, KeyCode::Esc | KeyCode::Enter | KeyCode::Char('q')) {
+            state.message_detail_open = false;
+        }
+        return;
+    }
+
+    if state.branch_picker_open {
+        handle_branch_picker_key(state, key);
+        return;
+    }
+
+    if state.substitute_confirm.is_some() {
+        handle_substitute_confirm_key(state, key);
+        return;
+    }
+
+    if state.scratchpad_open {
+        handle_scratchpad_key(state, key);
+        return;
+    }
+
+    if state.notifications_open {
+        handle_notifications_key(state, key);
+        return;
+    }
+
+    if state.replay.is_some() {
+        handle_replay_key(state, key);
+        return;
+    }
+
+    // 取回的回复生成中按 Esc 取消守护进程中的任务，下一段输出到达时生效
+    if key == KeyCode::Esc
+        && state.chat_mode == ChatMode::Normal
+        && state.attached_job.take().is_some()
+    {
+        state.status_message = Some(" Cancelling the reattached reply... ".to_string());
+        return;
+    }
+
+    if state.chat_mode == ChatMode::Normal && pending_fanout(state).is_some() {
+        let keep = match key {
+            KeyCode::Char(c @ '1'..='9') => Some(Some(c as usize - '1' as usize)),
+            KeyCode::Char('a') => Some(None),
+            _ => None,
+        };
+        if let Some(keep) = keep {
+            state.status_message = Some(format!(" {} ", keep_fanout(state, keep)));
+            return;
+        }
+    }
+
+    match state.chat_mode {
+        ChatMode::Normal => handle_chat_normal_key(state, key),
+        ChatMode::Search => handle_chat_search_key(state, key),
+        ChatMode::Command => handle_chat_command_key(state, key, shared_state),
+        ChatMode::Insert => handle_chat_insert_key(state, key, shared_state),
+    }
+}
+
+fn handle_chat_normal_key(state: &mut AppState, key: KeyCode) {
+    match key {
+        KeyCode::Char('i') => {
+            state.chat_mode = ChatMode::Insert;
+        }
+        KeyCode::Char('j') => {
+            state.auto_scroll = false;
+            let viewport = 5u16;
+            state.chat_scroll = state.chat_scroll.saturating_add(viewport);
+        }
+        KeyCode::Char('k') => {
+            state.auto_scroll = false;
+            let viewport = 5u16;
+            state.chat_scroll = state.chat_scroll.saturating_sub(viewport);
+        }
+        KeyCode::Char('G') => {
+            state.auto_scroll = false;
+            state.chat_scroll = u16::MAX;
+        }
+        KeyCode::Char('g') => {
+            state.auto_scroll = false;
+            state.chat_scroll = 0;
+        }
+        KeyCode::Char('o') => {
+            open_session_picker(state);
+        }
+        KeyCode::Char('v') => {
+            open_variables_editor(state);
+        }
+        KeyCode::Char('b') => {
+            let message = fork_conversation(state);
+            state.status_message = Some(format!(" {} ", message));
+        }
+        KeyCode::Char('B') => {
+            open_branch_picker(state);
+        }
+        KeyCode::Char('t') => toggle_channels(state),
+        KeyCode::Char('T') => {
+            let expanded = !state.all_channels_expanded.unwrap_or(false);
+            state.all_channels_expanded = Some(expanded);
+            state.expanded_channels.clear();
+            state.status_message = Some(format!(
+                " Reasoning {} ",
+                if expanded { "expanded" } else { "collapsed" }
+            ));
+        }
+        KeyCode::Char('[') | KeyCode::Char(']') => {
+            if state.messages.is_empty() {
+                return;
+            }
+            let last = state.messages.len() - 1;
+            let selected = match (state.chat_selected, key) {
+                (None, _) => last,
+                (Some(i), KeyCode::Char('[')) => i.saturating_sub(1),
+                (Some(i), _) => (i + 1).min(last),
+            };
+            state.chat_selected = Some(selected);
+            state.chat_scroll_target = Some(selected);
+        }
+        KeyCode::Enter => {
+            if state.chat_selected.is_none() {
+                state.chat_selected = state.messages.iter().rposition(|m| m.role() == "assistant");
+            }
+            if state.chat_selected.is_some() {
+                state.message_detail_open = true;
+            }
+        }
+        KeyCode::Char('D') => {
+            state.status_message = Some(format!(" {} ", reset_model_defaults(state)));
+        }
+        KeyCode::Char('l') => {
+            state.length_preset = state.length_preset.next();
+            state.status_message = Some(format!(
+                " Response length: {} ",
+                state.length_preset.label()
+            ));
+        }
+        KeyCode::Char(':') => {
+            state.chat_mode = ChatMode::Command;
+            state.chat_command.clear();
+        }
+        KeyCode::Char('/') => {
+            state.chat_mode = ChatMode::Search;
+            state.chat_search_query.clear();
+            state.chat_search_matches.clear();
+            state.chat_search_index = 0;
+        }
+        KeyCode::Char('n') if !state.chat_search_matches.is_empty() => {
+            state.chat_search_index =
+                (state.chat_search_index + 1) % state.chat_search_matches.len();
+            jump_to_search_match(state);
+        }
+        KeyCode::Char('N') if !state.chat_search_matches.is_empty() => {
+            let len = state.chat_search_matches.len();
+            state.chat_search_index = (state.chat_search_index + len - 1) % len;
+            jump_to_search_match(state);
+        }
+        KeyCode::Char('x') => {
+            // 导出对话中的代码块为独立文件
+            state.status_message = Some(format!(" {} ", export_conversation(state, "scripts")));
+        }
+        _ => {}
+    }
+}
+
+fn handle_chat_search_key(state: &mut AppState, key: KeyCode) {
+    match key {
+        KeyCode::Char(c) => {
+            state.chat_search_query.push(c);
+        }
+        KeyCode::Backspace => {
+            state.chat_search_query.pop();
+        }
+        KeyCode::Enter => {
+            state.chat_search_matches =
+                find_chat_matches(&state.messages, &state.chat_search_query);
+            state.chat_search_index = 0;
+            state.chat_mode = ChatMode::Normal;
+            jump_to_search_match(state);
+        }
+        KeyCode::Esc => {
+            state.chat_search_query.clear();
+            state.chat_search_matches.clear();
+            state.chat_mode = ChatMode::Normal;
+        }
+        _ => {}
+    }
+}
+
+fn handle_chat_insert_key(state: &mut AppState, key: KeyCode, shared_state: &SharedState) {
+    match key {
+        // 有提示时 Esc 先关闭提示
+        KeyCode::Esc if !state.lint_hints.is_empty() => {
+            state.lint_hints.clear();
+        }
+        KeyCode::Esc => {
+            state.chat_mode = ChatMode::Normal;
+        }
+        // 提示针对的是修改前的内容
+        KeyCode::Char(c) => {
+            state.input.insert_char(c);
+            state.lint_hints.clear();
+        }
+        KeyCode::Backspace => {
+            state.input.backspace();
+            state.lint_hints.clear();
+        }
+        KeyCode::Delete => {
+            state.input.delete();
+            state.lint_hints.clear();
+        }
+        KeyCode::Left => {
+            state.input.move_left();
+        }
+        KeyCode::Right => {
+            state.input.move_right();
+        }
+        KeyCode::Home => {
+            state.input.home();
+        }
+        KeyCode::End => {
+            state.input.end();
+        }
+        KeyCode::Enter => {
+            // 部分终端（如一些 Windows 终端）会重复上报 Enter，间隔过短的按键直接忽略
+            let now = std::time::Instant::now();
+            let repeated = state
+                .last_enter
+                .is_some_and(|last| now.duration_since(last) < state.config.send_debounce());
+            state.last_enter = Some(now);
+            if repeated {
+                return;
+            }
+
+            // 输入框中也可以用 /model 切换或拉取模型，/fanout 发送多份
+            if state.input.as_str().starts_with("/model ")
+                || state.input.as_str().starts_with("/fanout ")
+                || state.input.as_str() == "/fanout"
+            {
+                let command = state.input.take();
+                run_chat_command(state, &command[1..], shared_state);
+                return;
+            }
+            if !state.input.is_empty() && state.selected_model.is_some() {
+                if let Some(fanout) = pending_fanout(state) {
+                    state.status_message = Some(format!(
+                        " Keep one of the {} replies first (1-{}, a: all) ",
+                        fanout.replies.len(),
+                        fanout.replies.len()
+                    ));
+                    return;
+                }
+                if state.prompt_lint && state.lint_warned.as_deref() != Some(state.input.as_str()) {
+                    let hints = lint::check(state.input.as_str(), &state.system_prompt);
+                    if !hints.is_empty() {
+                        state.lint_hints = hints;
+                        state.lint_warned = Some(state.input.as_str().to_string());
+                        return;
+                    }
+                }
+                state.lint_hints.clear();
+                state.lint_warned = None;
+                // 重复发送时保留输入框中的内容
+                let duplicate = state.last_send.as_ref().is_some_and(|(last, at)| {
+                    last == state.input.as_str()
+                        && now.duration_since(*at) < state.config.duplicate_send_window()
+                });
+                if duplicate {
+                    state.status_message = Some(" Ignored duplicate message ".to_string());
+                    return;
+                }
+                let text = state.input.take();
+                state.last_send = Some((text.clone(), now));
+                let user_input = variables::expand(&text, &state.variables);
+
+                state.messages.push(ChatEntry::new("user", user_input));
+                remember_model_defaults(state);
+                start_generation(state, shared_state, None);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// 为最后一条用户消息生成回复。`retry_instruction` 用于回复因禁用短语被中止后的重试
+fn start_generation(
+    state: &mut AppState,
+    shared_state: &SharedState,
+    retry_instruction: Option<String>,
+) {
+    let Some(model) = state.selected_model.clone() else {
+        return;
+    };
+    let user_input = state
+        .messages
+        .iter()
+        .rev()
+        .find(|m| m.role() == "user")
+        .map(|m| m.content().to_string())
+        .unwrap_or_default();
+
+    let mut reply = ChatEntry::new("assistant", String::new());
+    reply.meta = Some(GenerationMeta {
+        model: model.clone(),
+        mode: match state.api_mode {
+            ApiMode::Chat => "chat",
+            ApiMode::Generate if state.generate_raw => "generate raw",
+            ApiMode::Generate => "generate",
+        }
+        .to_string(),
+        options: request_options(state),
+        ..GenerationMeta::default()
+    });
+    state.messages.push(reply);
+
+    let s_for_callback = shared_state.clone();
+    let s_for_join = shared_state.clone();
+    let reply_model = model.clone();
+
+    state.is_loading = true;
+    state.auto_scroll = true;
+
+    let tracker = Arc::new(BudgetTracker::new(state.reply_budget));
+    let chunk_tracker = tracker.clone();
+    let on_chunk = move |chunk: String| {
+        // 已超出时间上限，等连接在截止时刻断开
+        if chunk_tracker.stopped().is_some() {
+            return false;
+        }
+        let s = s_for_callback.clone();
+        let mut s = s.blocking_lock();
+        let banned = find_banned_phrase(&chunk, &s.banned_phrases);
+        let over_budget = chunk_tracker.tick();
+        if let Some(last) = s.messages.last_mut() {
+            if last.role() == "assistant" {
+                last.message.content = chunk;
+                last.banned_phrase = banned.clone();
+                if let Some(meta) = last.meta.as_mut() {
+                    meta.stopped = over_budget.clone();
+                }
+            }
+        }
+        // 出现禁用短语或超出预算时停止生成
+        banned.is_none() && over_budget.is_none()
+    };
+
+    let with_retry = |prompt: String| match &retry_instruction {
+        Some(instruction) if prompt.is_empty() => instruction.clone(),
+        Some(instruction) => format!("{}\n\n{}", prompt, instruction),
+        None => prompt,
+    };
+    let handle = match state.api_mode {
+        ApiMode::Chat => {
+            let messages = chat_history(state, with_retry(effective_system_prompt(state)));
+            tracker.watch(engine::stream_chat(
+                &chat_backend(state),
+                model,
+                messages,
+                tracker.options(request_options(state)),
+                tracker.deadline(),
+                on_chunk,
+            ))
+        }
+        ApiMode::Generate => {
+            let mut request = generate_request(state, model, user_input);
+            if !request.raw && retry_instruction.is_some() {
+                request.system = Some(with_retry(request.system.unwrap_or_default()));
+            }
+            request.options = tracker.options(request.options.take());
+            tracker.watch(OllamaClient::generate_streaming(
+                request,
+                tracker.deadline(),
+                on_chunk,
+            ))
+        }
+    };
+
+    let is_retry = retry_instruction.is_some();
+    state.failed_reply = false;
+    std::thread::spawn(move || {
+        let result = handle.join();
+        let shared_state = s_for_join.clone();
+        let mut s = s_for_join.blocking_lock();
+        s.is_loading = false;
+        if let Some(last) = s.messages.last_mut().filter(|m| m.role() == "assistant") {
+            last.detect_language();
+        }
+        match result {
+            Ok(Ok(completion)) => {
+                if let Some(meta) = s
+                    .messages
+                    .last_mut()
+                    .filter(|m| m.role() == "assistant")
+                    .and_then(|m| m.meta.as_mut())
+                {
+                    meta.stats = completion.stats;
+                    meta.stopped = tracker.stopped();
+                }
+                let banned = s
+                    .messages
+                    .last()
+                    .filter(|_| completion.cancelled)
+                    .and_then(|m| m.banned_phrase.clone());
+                if let Some(phrase) = banned {
+                    if s.retry_banned && !is_retry {
+                        // 被中止的回复保留为另一个分支，重试的回复作为它的兄弟节点
+                        let state = &mut *s;
+                        state.conversation.sync(&state.messages);
+                        state.messages = state.conversation.fork(state.messages.len() - 2);
+                        s.status_message = Some(format!(
+                            " Reply contained \"{}\", retrying — the stopped reply is kept (B: branches) ",
+                            phrase
+                        ));
+                        let instruction = banned_retry_instruction(&s.banned_phrases, &phrase);
+                        start_generation(&mut s, &shared_state, Some(instruction));
+                    } else {
+                        let target = s.messages.last().map(|m| Target::Message(m.timestamp));
+                        let message = format!("Stopped: reply contained \"{}\"", phrase);
+                        reply_webhook(&s, &reply_model, "stopped", message.clone());
+                        notify(&mut s, notifications::Kind::Generation, message, target);
+                    }
+                } else {
+                    let stopped = s
+                        .messages
+                        .last()
+                        .and_then(|m| m.meta.as_ref())
+                        .and_then(|meta| meta.stopped.clone());
+                    let target = s.messages.last().map(|m| Target::Message(m.timestamp));
+                    match stopped {
+                        Some(reason) => {
+                            let message = format!("Stopped at the {}, partial answer kept", reason);
+                            reply_webhook(&s, &reply_model, "stopped", message.clone());
+                            notify(&mut s, notifications::Kind::Generation, message, target);
+                        }
+                        None => {
+                            let message = format!("Reply from {} finished", reply_model);
+                            reply_webhook(&s, &reply_model, "finished", message.clone());
+                            s.notifications
+                                .push(notifications::Kind::Generation, message, target);
+                        }
+                    }
+                    if s.memory_enabled && s.api_mode == ApiMode::Chat {
+                        extract_memories(&s);
+                    }
+                }
+            }
+            Ok(Err(e)) => {
+                // 去掉没有内容的回复占位
+                if s.messages
+                    .last()
+                    .is_some_and(|m| m.role() == "assistant" && m.content().is_empty())
+                {
+                    s.messages.pop();
+                }
+                s.connection.report_failure(&e);
+                s.failed_reply = connection::is_connection_error(&e);
+                let message = connection::describe_error(&e);
+                reply_webhook(&s, &reply_model, "failed", message.clone());
+                notify(&mut s, notifications::Kind::Error, message, None);
+            }
+            Err(_) => {}
+        }
+    });
+}
+
+/// 发送给 `/api/chat` 的消息：系统提示词加上当前对话
+fn chat_history(state: &AppState, system_prompt: String) -> Vec<ChatMessage> {
+    // 推理过程等通道内容不发回给模型
+    engine::request_messages(
+        &system_prompt,
+        session::to_messages(&state.messages)
+            .into_iter()
+            .map(|mut m| {
+                if m.role == "assistant" {
+                    m.content = channels::strip(&m.content, &state.tag_rules);
+                }
+                m
+            }),
+    )
+}
+
+/// 配置了 `daemon` 时回复交给守护进程生成，按当前会话登记以便之后重新接上
+fn chat_backend(state: &AppState) -> Backend {
+    if state.config.daemon {
+        Backend::Daemon {
+            session: state.current_session.as_ref().map(|s| s.id.clone()),
+        }
+    } else {
+        Backend::Direct
+    }
+}
+
+/// 还没有选定的 `/fanout` 候选回复
+fn pending_fanout(state: &AppState) -> Option<&fanout::Fanout> {
+    state
+        .fanout
+        .as_ref()
+        .filter(|f| f.belongs_to(&state.messages))
+}
+
+/// `/fanout [N] [prompt]`：用不同的 seed 把同一个问题发送 N 次，回复并排显示，选定一条后加入对话。
+/// 省略问题时发送输入框中的草稿
+fn start_fanout(state: &mut AppState, arg: &str, shared_state: &SharedState) -> String {
+    let (count, prompt) = match fanout::parse_args(arg) {
+        Ok(parsed) => parsed,
+        Err(message) => return message,
+    };
+    if state.is_loading {
+        return "Wait for the reply to finish".to_string();
+    }
+    if pending_fanout(state).is_some() {
+        return "Keep one of the replies first".to_string();
+    }
+    let Some(model) = state.selected_model.clone() else {
+        return "Select a model first".to_string();
+    };
+    if state.api_mode != ApiMode::Chat {
+        return "Fan-out works in chat mode (:mode chat)".to_string();
+    }
+    let prompt = if prompt.is_empty() {
+        state.input.take()
+    } else {
+        prompt.to_string()
+    };
+    if prompt.trim().is_empty() {
+        return "Usage: /fanout [N] <prompt> (or draft the prompt first)".to_string();
+    }
+
+    let user_input = variables::expand(&prompt, &state.variables);
+    state.messages.push(ChatEntry::new("user", user_input));
+    remember_model_defaults(state);
+    let messages = chat_history(state, effective_system_prompt(state));
+    let mut fanout = fanout::Fanout::new(state.messages.last().unwrap(), count);
+    let id = fanout.id;
+    let mut handles = Vec::new();
+    for (i, &seed) in fanout.seeds.iter().enumerate() {
+        let mut options = request_options(state).unwrap_or_default();
+        options.seed = Some(seed);
+        let mut reply = ChatEntry::new("assistant", String::new());
+        reply.meta = Some(GenerationMeta {
+            model: model.clone(),
+            mode: "chat".to_string(),
+            options: Some(options.clone()),
+            ..GenerationMeta::default()
+        });
+        fanout.replies.push(reply);
+
+        let s_for_callback = shared_state.clone();
+        let tracker = Arc::new(BudgetTracker::new(state.reply_budget));
+        let chunk_tracker = tracker.clone();
+        let on_chunk = move |chunk: String| {
+            if chunk_tracker.stopped().is_some() {
+                return false;
+            }
+            let mut s = s_for_callback.blocking_lock();
+            let over_budget = chunk_tracker.tick();
+            // 已经放弃的候选回复停止生成
+            let Some(reply) = s
+                .fanout
+                .as_mut()
+                .filter(|f| f.id == id)
+                .and_then(|f| f.replies.get_mut(i))
+            else {
+                return false;
+            };
+            reply.message.content = chunk;
+            if let Some(meta) = reply.meta.as_mut() {
+                meta.stopped = over_budget.clone();
+            }
+            over_budget.is_none()
+        };
+        let handle = engine::stream_chat(
+            &chat_backend(state),
+            model.clone(),
+            messages.clone(),
+            tracker.options(Some(options)),
+            tracker.deadline(),
+            on_chunk,
+        );
+        handles.push((tracker.watch(handle), tracker));
+    }
+    state.fanout = Some(fanout);
+    state.is_loading = true;
+    state.auto_scroll = true;
+    state.chat_mode = ChatMode::Normal;
+    state.failed_reply = false;
+
+    for (i, (handle, tracker)) in handles.into_iter().enumerate() {
+        let s_for_join = shared_state.clone();
+        let reply_model = model.clone();
+        std::thread::spawn(move || {
+            let result = handle.join();
+            let mut s = s_for_join.blocking_lock();
+            let Some(fanout) = s.fanout.as_mut().filter(|f| f.id == id) else {
+                return;
+            };
+            fanout.running = fanout.running.saturating_sub(1);
+            let finished = fanout.running == 0;
+            let count = fanout.replies.len();
+            let error = match result {
+                Ok(Ok(completion)) => {
+                    fanout.replies[i].detect_language();
+                    if let Some(meta) = fanout.replies[i].meta.as_mut() {
+                        meta.stats = completion.stats;
+                        meta.stopped = tracker.stopped();
+                    }
+                    None
+                }
+                Ok(Err(e)) => {
+                    let reply = &mut fanout.replies[i];
+                    if reply.content().is_empty() {
+                        reply.message.content = format!("⚠ {}", connection::describe_error(&e));
+                    }
+                    Some(e)
+                }
+                Err(_) => None,
+            };
+            if let Some(e) = error {
+                s.connection.report_failure(&e);
+            }
+            if finished {
+                s.is_loading = false;
+                let message = format!("{} replies from {} finished", count, reply_model);
+                reply_webhook(&s, &reply_model, "finished", message.clone());
+                s.notifications
+                    .push(notifications::Kind::Generation, message, None);
+                s.status_message = Some(format!(
+                    " 1-{}: keep one and drop the rest | a: keep all as branches ",
+                    count
+                ));
+            }
+        });
+    }
+    format!("Sampling {} replies from {}...", count, model)
+}
+
+/// 选定一条候选回复加入对话，其余丢弃；`keep` 为 None 时全部保留为同一问题下的分支
+fn keep_fanout(state: &mut AppState, keep: Option<usize>) -> String {
+    if state.is_loading {
+        return "Wait for all replies to finish".to_string();
+    }
+    let Some(fanout) = pending_fanout(state).cloned() else {
+        state.fanout = None;
+        return "No replies to choose from".to_string();
+    };
+    let count = fanout.replies.len();
+    if keep.is_some_and(|k| k >= count) {
+        return format!("Choose a reply between 1 and {}", count);
+    }
+    state.fanout = None;
+    state.chat_selected = None;
+    state.auto_scroll = true;
+    match keep {
+        Some(k) => {
+            state.messages.push(fanout.replies[k].clone());
+            format!("Kept reply #{}, dropped {} others", k + 1, count - 1)
+        }
+        None => {
+            // 每条回复都作为问题的子节点；倒序添加，最后停在第一条回复所在的分支
+            for reply in fanout.replies.iter().rev() {
+                let mut branch = state.messages.clone();
+                branch.push(reply.clone());
+                state.conversation.sync(&branch);
+            }
+            state.messages = state.conversation.messages();
+            format!("Kept all {} replies as branches (B: branches)", count)
+        }
+    }
+}
+
+/// 候选回复的显示行，接在对话后面
+fn fanout_lines(state: &AppState) -> Vec<Line<'static>> {
+    let Some(fanout) = pending_fanout(state) else {
+        return Vec::new();
+    };
+    let mut lines = Vec::new();
+    for (i, reply) in fanout.replies.iter().enumerate() {
+        let model = reply
+            .meta
+            .as_ref()
+            .map(|m| m.model.as_str())
+            .unwrap_or_default();
+        let mut header = vec![
+            Span::styled(
+                format!("#{} ", i + 1),
+                Style::default()
+                    .fg(Color::Cyan)
+                    .add_modifier(ratatui::style::Modifier::BOLD),
+            ),
+            Span::styled(
+                format!("seed {} · {} ", fanout.seeds[i], model),
+                Style::default().fg(Color::DarkGray),
+            ),
+        ];
+        if reply.meta.as_ref().is_some_and(|m| m.stopped.is_some()) {
+            header.push(Span::styled("⏱ ", Style::default().fg(Color::Yellow)));
+        }
+        if fanout.running > 0 && reply.content().is_empty() {
+            header.push(Span::styled("…", Style::default().fg(Color::DarkGray)));
+        }
+        lines.push(Line::from(header));
+        for display in channels::display_lines(reply.content(), &state.tag_rules, None) {
+            let style = display
+                .channel
+                .map(|rule| channel_style(&state.tag_rules[rule]))
+                .unwrap_or_default();
+            lines.push(Line::from(vec![
+                Span::styled("│ ", Style::default().fg(Color::Cyan)),
+                Span::styled(display.text, style),
+            ]));
+        }
+        lines.push(Line::default());
+    }
+    if fanout.running == 0 {
+        lines.push(Line::from(Span::styled(
+            format!(
+                "1-{}: keep one and drop the rest · a: keep all as branches",
+                fanout.replies.len()
+            ),
+            Style::default().fg(Color::DarkGray),
+        )));
+    }
+    lines
+}
+
+/// 回复中出现的第一个禁用短语（不区分大小写）
+fn find_banned_phrase(content: &str, phrases: &[String]) -> Option<String> {
+    let content = content.to_lowercase();
+    phrases
+        .iter()
+        .find(|p| !p.is_empty() && content.contains(&p.to_lowercase()))
+        .cloned()
+}
+
+/// 重试时追加到系统提示词的要求
+fn banned_retry_instruction(phrases: &[String], hit: &str) -> String {
+    let list = phrases
+        .iter()
+        .map(|p| format!("\"{}\"", p))
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!(
+        "IMPORTANT: your previous reply was rejected because it contained \"{}\". \
+         Do not use any of these phrases anywhere in your reply: {}.",
+        hit, list
+    )
+}
+
+/// 系统提示词（展开变量）加上对话变量说明和长度预设对应的要求
+fn effective_system_prompt(state: &AppState) -> String {
+    let mut prompt = variables::expand(&state.system_prompt, &state.variables);
+    let extras = [
+        variables::context_block(&state.variables),
+        relevant_memories(state),
+        state.length_preset.instruction().map(str::to_string),
+    ];
+    for extra in extras.into_iter().flatten() {
+        if !prompt.is_empty() {
+            prompt.push_str("\n\n");
+        }
+        prompt.push_str(&extra);
+    }
+    prompt
+}
+
+/// 与最近一条用户消息相关的记忆
+fn relevant_memories(state: &AppState) -> Option<String> {
+    if !state.memory_enabled {
+        return None;
+    }
+    let query = state
+        .messages
+        .iter()
+        .rev()
+        .find(|m| m.role() == "user")
+        .map(|m| m.content())?;
+    memory::context_block(&state.memory.relevant(query, 5))
+}
+
+/// 在后台从最近一轮对话中提取事实写入长期记忆
+fn extract_memories(state: &AppState) {
+    let Some(model) = state
+        .config
+        .memory_model
+        .clone()
+        .or_else(|| state.selected_model.clone())
+    else {
+        return;
+    };
+    let messages = memory::extraction_prompt(&session::to_messages(&state.messages));
+    let source = state.current_session.as_ref().map(|s| s.id.clone());
+    let store = state.memory.clone();
+
+    std::thread::spawn(move || {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let client = OllamaClient::new(None);
+        let Ok(reply) = rt.block_on(client.chat_with_format(
+            &model,
+            messages,
+            Some(memory::extraction_schema()),
+        )) else {
+            return;
+        };
+        for fact in memory::parse_facts(&reply) {
+            let _ = store.add(&fact, source.as_deref());
+        }
+    });
+}
+
+/// 当前模型保存的参数，回复长度预设覆盖其中的 num_predict
+fn request_options(state: &AppState) -> Option<ModelOptions> {
+    let mut options = state
+        .selected_model
+        .as_ref()
+        .and_then(|model| state.model_settings.get(model))
+        .cloned()
+        .unwrap_or_default();
+    if let Some(num_predict) = state.length_preset.num_predict() {
+        options.num_predict = Some(num_predict);
+    }
+    (!options.is_empty()).then_some(options)
+}
+
+/// 当前对话的系统提示词、回复长度和预算
+fn current_defaults(state: &AppState) -> ModelDefaults {
+    ModelDefaults {
+        system_prompt: state.system_prompt.clone(),
+        length: state.length_preset.label().to_string(),
+        max_reply_seconds: state.reply_budget.seconds,
+        max_reply_tokens: state.reply_budget.tokens,
+    }
+}
+
+/// 没有按模型记住的设置时使用：第一个系统提示词、正常长度和配置中的预算
+fn global_defaults(state: &AppState) -> ModelDefaults {
+    ModelDefaults {
+        system_prompt: state
+            .prompts
+            .first()
+            .map(|p| p.content.clone())
+            .unwrap_or_default(),
+        length: LengthPreset::Normal.label().to_string(),
+        max_reply_seconds: state.config.max_reply_seconds,
+        max_reply_tokens: state.config.max_reply_tokens,
+    }
+}
+
+fn use_defaults(state: &mut AppState, defaults: ModelDefaults) {
+    state.system_prompt = defaults.system_prompt;
+    state.length_preset = LengthPreset::parse(&defaults.length).unwrap_or_default();
+    state.reply_budget = ReplyBudget {
+        seconds: defaults.max_reply_seconds,
+        tokens: defaults.max_reply_tokens,
+    };
+}
+
+/// 发送消息时记住当前模型使用的设置；与全局默认相同时不保存
+fn remember_model_defaults(state: &mut AppState) {
+    let Some(model) = state.selected_model.clone() else {
+        return;
+    };
+    if state.kiosk {
+        return;
+    }
+    let current = current_defaults(state);
+    let changed = if current == global_defaults(state) {
+        state.model_defaults.remove(&model).is_some()
+    } else {
+        state.model_defaults.insert(model, current.clone()) != Some(current)
+    };
+    if changed {
+        if let Err(e) = model_defaults::save(&state.model_defaults) {
+            state.status_message = Some(format!(" Could not save model settings: {} ", e));
+        }
+    }
+}
+
+/// 在还没有消息的对话中选择模型后调用，应用该模型上次使用的设置。返回是否应用
+fn apply_model_defaults(state: &mut AppState) -> bool {
+    state.model_defaults_applied = None;
+    if state.kiosk || !state.messages.is_empty() {
+        return false;
+    }
+    let Some(model) = state.selected_model.clone() else {
+        return false;
+    };
+    let Some(defaults) = state.model_defaults.get(&model).cloned() else {
+        return false;
+    };
+    use_defaults(state, defaults);
+    state.model_defaults_applied = Some(model);
+    true
+}
+
+/// `D`：恢复全局默认设置，并忘记当前模型记住的设置
+fn reset_model_defaults(state: &mut AppState) -> String {
+    let defaults = global_defaults(state);
+    use_defaults(state, defaults);
+    state.model_defaults_applied = None;
+    let Some(model) = state.selected_model.clone() else {
+        return "Restored the global defaults".to_string();
+    };
+    if state.model_defaults.remove(&model).is_some() {
+        if let Err(e) = model_defaults::save(&state.model_defaults) {
+            return format!("Could not save model settings: {}", e);
+        }
+    }
+    format!("Restored the global defaults for {}", model)
+}
+
+fn format_options(options: &ModelOptions) -> String {
+    options
+        .entries()
+        .iter()
+        .map(|(name, value)| format!("{}={}", name, value))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// 单次补全请求：raw 模式下不套用模板也不附带系统提示词
+fn generate_request(state: &AppState, model: String, prompt: String) -> GenerateRequest {
+    let non_empty = |text: &str| (!text.is_empty()).then(|| text.to_string());
+    GenerateRequest {
+        model,
+        prompt,
+        stream: true,
+        raw: state.generate_raw,
+        system: if state.generate_raw {
+            None
+        } else {
+            non_empty(&effective_system_prompt(state))
+        },
+        template: non_empty(&state.generate_template),
+        suffix: non_empty(&state.generate_suffix),
+        options: request_options(state),
+    }
+}
+
+fn handle_chat_command_key(state: &mut AppState, key: KeyCode, shared_state: &SharedState) {
+    match key {
+        KeyCode::Char(c) => {
+            state.chat_command.push(c);
+        }
+        KeyCode::Backspace if state.chat_command.is_empty() => {
+            state.chat_mode = ChatMode::Normal;
+        }
+        KeyCode::Backspace => {
+            state.chat_command.pop();
+        }
+        KeyCode::Enter => {
+            let command = std::mem::take(&mut state.chat_command);
+            state.chat_mode = ChatMode::Normal;
+            run_chat_command(state, command.trim(), shared_state);
+        }
+        KeyCode::Esc => {
+            state.chat_command.clear();
+            state.chat_mode = ChatMode::Normal;
+        }
+        _ => {}
+    }
+}
+
+/// `:%s` 替换的对象
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum SubstituteTarget {
+    #[default]
+    Draft,
+    Scratchpad,
+}
+
+/// 替换 `target` 的全部文本，草稿本随即保存
+fn set_target_text(state: &mut AppState, target: SubstituteTarget, text: String) {
+    match target {
+        SubstituteTarget::Draft => {
+            state.input.set_text(text);
+            state.lint_hints.clear();
+        }
+        SubstituteTarget::Scratchpad => {
+            state.scratchpad.set_text(text);
+            save_scratchpad(state);
+        }
+    }
+}
+
+/// `:%s/old/new/[gc]` 在输入框的草稿中替换，`:scratch %s/old/new/[gc]` 在草稿本中替换；带 `c` 时逐个确认
+fn substitute_text(
+    state: &mut AppState,
+    target: SubstituteTarget,
+    substitution: Substitution,
+) -> String {
+    let text = match target {
+        SubstituteTarget::Draft => state.input.as_str().to_string(),
+        SubstituteTarget::Scratchpad => state.scratchpad.as_str().to_string(),
+    };
+    let matches = substitution.matches(&text);
+    if matches.is_empty() {
+        return format!("Pattern not found: {}", substitution.pattern);
+    }
+    if substitution.confirm {
+        state.substitute_confirm = Some(Confirmation::new(substitution, text));
+        state.substitute_target = target;
+        return String::new();
+    }
+    set_target_text(state, target, substitution.apply(&text, &matches));
+    format!("Replaced {} occurrence(s)", matches.len())
+}
+
+fn save_scratchpad(state: &mut AppState) {
+    if let Err(e) = scratchpad::save(state.scratchpad.as_str()) {
+        state.status_message = Some(format!(" Failed to save the scratchpad: {} ", e));
+    }
+}
+
+/// 草稿本弹窗中的编辑，Enter 换行，Esc 保存并关闭
+fn handle_scratchpad_key(state: &mut AppState, key: KeyCode) {
+    match key {
+        KeyCode::Esc => {
+            state.scratchpad_open = false;
+            save_scratchpad(state);
+        }
+        KeyCode::Enter => state.scratchpad.insert_char('\n'),
+        KeyCode::Char(c) => state.scratchpad.insert_char(c),
+        KeyCode::Backspace => state.scratchpad.backspace(),
+        KeyCode::Delete => state.scratchpad.delete(),
+        KeyCode::Left => state.scratchpad.move_left(),
+        KeyCode::Right => state.scratchpad.move_right(),
+        KeyCode::Home => state.scratchpad.home(),
+        KeyCode::End => state.scratchpad.end(),
+        _ => {}
+    }
+}
+
+fn handle_substitute_confirm_key(state: &mut AppState, key: KeyCode) {
+    let Some(confirm) = state.substitute_confirm.as_mut() else {
+        return;
+    };
+    let stop = match key {
+        KeyCode::Char('y') => {
+            confirm.accept();
+            false
+        }
+        KeyCode::Char('n') => {
+            confirm.skip();
+            false
+        }
+        KeyCode::Char('a') => {
+            confirm.accept_all();
+            true
+        }
+        KeyCode::Char('l') => {
+            confirm.accept();
+            true
+        }
+        KeyCode::Char('q') | KeyCode::Esc => true,
+        _ => return,
+    };
+    if stop || confirm.is_done() {
+        let replaced = confirm.accepted.len();
+        let total = confirm.matches.len();
+        let text = confirm.finish();
+        state.substitute_confirm = None;
+        let target = state.substitute_target;
+        set_target_text(state, target, text);
+        state.status_message = Some(format!(" Replaced {} of {} ", replaced, total));
+    }
+}
+
+/// 执行 `:` 命令
+fn run_chat_command(state: &mut AppState, command: &str, shared_state: &SharedState) {
+    let (target, substitution) = match command.strip_prefix("scratch ") {
+        Some(rest) => (SubstituteTarget::Scratchpad, rest.trim()),
+        None => (SubstituteTarget::Draft, command),
+    };
+    if let Some(parsed) = Substitution::parse(substitution) {
+        let message = match parsed {
+            Ok(substitution) => substitute_text(state, target, substitution),
+            Err(e) => e,
+        };
+        if !message.is_empty() {
+            state.status_message = Some(format!(" {} ", message));
+        }
+        return;
+    }
+
+    let (name, arg) = command
+        .split_once(' ')
+        .map(|(n, a)| (n, a.trim()))
+        .unwrap_or((command, ""));
+
+    let message = match name {
+        "mode" => match arg {
+            "chat" => {
+                state.api_mode = ApiMode::Chat;
+                "Switched to chat mode (/api/chat)".to_string()
+            }
+            "generate" | "gen" => {
+                state.api_mode = ApiMode::Generate;
+                "Switched to generate mode (/api/generate)".to_string()
+            }
+            _ => "Usage: :mode chat|generate".to_string(),
+        },
+        "raw" => {
+            state.generate_raw = match arg {
+                "on" => true,
+                "off" => false,
+                _ => !state.generate_raw,
+            };
+            format!(
+                "Raw prompt {}",
+                if state.generate_raw { "on" } else { "off" }
+            )
+        }
+        "suffix" => {
+            state.generate_suffix = unescape(arg);
+            if arg.is_empty() {
+                "Suffix cleared".to_string()
+            } else {
+                "Suffix set".to_string()
+            }
+        }
+        "template" => {
+            state.generate_template = unescape(arg);
+            if arg.is_empty() {
+                "Template cleared".to_string()
+            } else {
+                "Template set".to_string()
+            }
+        }
+        "save" | "w" => save_session(state, shared_state),
+        "length" | "len" => {
+            state.length_preset =
+                LengthPreset::parse(arg).unwrap_or_else(|| state.length_preset.next());
+            format!("Response length: {}", state.length_preset.label())
+        }
+        "title" => {
+            if arg.is_empty() {
+                match state.current_session.clone() {
+                    Some(session) => {
+                        generate_session_title(state, session.id, shared_state);
+                        "Generating title...".to_string()
+                    }
+                    None => "Save the session first (:save)".to_string(),
+                }
+            } else {
+                set_session_title(state, arg)
+            }
+        }
+        "branch" | "fork" => fork_conversation(state),
+        "zoom" => {
+            state.zoomed = !state.zoomed;
+            return;
+        }
+        "notifications" | "notif" => {
+            state.notifications_open = true;
+            state.notifications_state.select(Some(0));
+            state.notifications.mark_read();
+            return;
+        }
+        "branches" => {
+            open_branch_picker(state);
+            return;
+        }
+        "new" => {
+            state.messages.clear();
+            forget_failed_reply(state);
+            state.conversation = ConversationTree::default();
+            state.banned_phrases.clear();
+            state.chat_selected = None;
+            state.variables.clear();
+            state.current_session = None;
+            state.chat_search_matches.clear();
+            state.chat_scroll = 0;
+            if apply_model_defaults(state) {
+                "Started a new conversation with the model's last settings (D: reset)".to_string()
+            } else {
+                "Started a new conversation".to_string()
+            }
+        }
+        "back" => restore_stashed_chat(state),
+        "fanout" => start_fanout(state, arg, shared_state),
+        "retry" => resend_failed_reply(state, shared_state),
+        "replay" => match arg.split_once(' ').unwrap_or((arg, "")) {
+            ("cast", path) => export_replay_cast(state, path.trim()),
+            ("", _) => start_replay(state, 1.0),
+            (speed, _) => match speed.trim_end_matches('x').parse::<f64>() {
+                Ok(speed) if speed > 0.0 => start_replay(state, speed),
+                _ => "Usage: :replay [speed] | :replay cast [file]".to_string(),
+            },
+        },
+        "lint" => {
+            state.prompt_lint = match arg {
+                "on" => true,
+                "off" => false,
+                _ => !state.prompt_lint,
+            };
+            if !state.prompt_lint {
+                state.lint_hints.clear();
+            }
+            format!(
+                "Prompt warnings {}",
+                if state.prompt_lint { "on" } else { "off" }
+            )
+        }
+        "attach" => attach_job(state, arg, shared_state),
+        "daemon" => match arg {
+            "" | "status" => match daemon::list() {
+                Ok(jobs) => {
+                    state.detached_jobs = jobs;
+                    format!(
+                        "Daemon running, {} job(s) · replies {}",
+                        state.detached_jobs.len(),
+                        if state.config.daemon {
+                            "go through the daemon"
+                        } else {
+                            "are generated here (set \"daemon\": true to detach)"
+                        }
+                    )
+                }
+                Err(e) => e.to_string(),
+            },
+            "start" => match daemon::spawn() {
+                Ok(()) => format!("Daemon listening on {}", daemon::socket_path().display()),
+                Err(e) => format!("Could not start the daemon: {}", e),
+            },
+            "stop" => match daemon::shutdown() {
+                Ok(()) => {
+                    state.detached_jobs.clear();
+                    "Daemon stopped".to_string()
+                }
+                Err(e) => e.to_string(),
+            },
+            _ => "Usage: :daemon [status|start|stop]".to_string(),
+        },
+        "restart" => {
+            if state.is_loading {
+                "Wait for the reply to finish before restarting".to_string()
+            } else {
+                state.restart_requested = true;
+                return;
+            }
+        }
+        "ban" => {
+            if arg.is_empty() {
+                if state.banned_phrases.is_empty() {
+                    "No banned phrases (:ban <phrase> to add)".to_string()
+                } else {
+                    format!(
+                        "Banned: {} · retry {}",
+                        state.banned_phrases.join(" | "),
+                        if state.retry_banned { "on" } else { "off" }
+                    )
+                }
+            } else if state.banned_phrases.iter().any(|p| p == arg) {
+                format!("\"{}\" is already banned", arg)
+            } else {
+                state.banned_phrases.push(arg.to_string());
+                format!("Replies containing \"{}\" will be stopped", arg)
+            }
+        }
+        "unban" => {
+            if arg.is_empty() {
+                state.banned_phrases.clear();
+                "Cleared banned phrases".to_string()
+            } else {
+                state.banned_phrases.retain(|p| p != arg);
+                format!("Removed \"{}\"", arg)
+            }
+        }
+        "budget" => match ReplyBudget::parse(arg).filter(|_| !arg.is_empty()) {
+            Some(budget) => {
+                state.reply_budget = budget;
+                format!("Reply budget: {}", budget.label())
+            }
+            None if arg.is_empty() => format!("Reply budget: {}", state.reply_budget.label()),
+            None => "Usage: :budget [30s] [500t] | off".to_string(),
+        },
+        "banretry" => {
+            state.retry_banned = match arg {
+                "on" => true,
+                "off" => false,
+                _ => !state.retry_banned,
+            };
+            format!(
+                "Retry after a banned phrase: {}",
+                if state.retry_banned { "on" } else { "off" }
+            )
+        }
+        "export" => export_conversation(state, arg),
+        "import" => import_webui(state, arg),
+        "sessions" | "open" => {
+            open_session_picker(state);
+            return;
+        }
+        "set" => match variables::parse_assignment(arg) {
+            Some((key, value)) => {
+                let message = format!("Set {{{{{}}}}} = {}", key, value);
+                state.variables.insert(key, value);
+                sync_session_variables(state);
+                message
+            }
+            None => "Usage: :set key=value".to_string(),
+        },
+        "unset" => {
+            if state.variables.remove(arg).is_some() {
+                sync_session_variables(state);
+                format!("Removed {{{{{}}}}}", arg)
+            } else {
+                format!("No variable named {}", arg)
+            }
+        }
+        "vars" => {
+            open_variables_editor(state);
+            return;
+        }
+        "scratch" if arg.is_empty() => {
+            state.scratchpad_open = true;
+            return;
+        }
+        "scratch" => "Usage: :scratch or :scratch %s/old/new/[gc]".to_string(),
+        "model" => {
+            if arg.is_empty() {
+                return;
+            }
+            let wanted = full_model_name(arg);
+            match state
+                .models
+                .iter()
+                .find(|m| full_model_name(&m.name) == wanted)
+            {
+                Some(model) => {
+                    let name = model.name.clone();
+                    state.selected_model = Some(name.clone());
+                    if apply_model_defaults(state) {
+                        format!("Using {} with its last settings (D: reset)", name)
+                    } else {
+                        format!("Using {}", name)
+                    }
+                }
+                None => {
+                    state.quick_pull = Some(arg.to_string());
+                    state.quick_pull_confirm = true;
+                    state.quick_pull_popup = true;
+                    return;
+                }
+            }
+        }
+        "server" => match arg {
+            "start" | "" => start_server(state),
+            "stop" => {
+                if state.server.stop() {
+                    "Stopped `ollama serve`".to_string()
+                } else {
+                    "No server started from ollama-cli".to_string()
+                }
+            }
+            "log" => {
+                state.server_log_open = !state.server_log_open;
+                return;
+            }
+            _ => "Usage: :server start|stop|log".to_string(),
+        },
+        "memory" => {
+            state.memory_enabled = match arg {
+                "on" => true,
+                "off" => false,
+                _ => !state.memory_enabled,
+            };
+            format!("Memory {}", if state.memory_enabled { "on" } else { "off" })
+        }
+        "record" | "rec" => {
+            let enable = match arg {
+                "on" => true,
+                "off" => false,
+                _ => !recorder::is_recording(),
+            };
+            if enable {
+                match recorder::start() {
+                    Ok(path) => format!("Recording API traffic to {}", path.display()),
+                    Err(e) => format!("Failed to start recording: {}", e),
+                }
+            } else {
+                match recorder::stop() {
+                    Some(path) => format!("Recording saved to {}", path.display()),
+                    None => "Not recording".to_string(),
+                }
+            }
+        }
+        "" => return,
+        _ => format!("Unknown command: {}", name),
+    };
+    state.status_message = Some(format!(" {} ", message));
+}
+
+/// 保存当前对话；首次保存时在后台生成标题和摘要
+fn save_session(state: &mut AppState, shared_state: &SharedState) -> String {
+    if state.messages.is_empty() {
+        return "Nothing to save".to_string();
+    }
+    let mut session = state
+        .current_session
+        .take()
+        .unwrap_or_else(|| Session::new(state.selected_model.clone(), Vec::new()));
+    session.model = state.selected_model.clone().or(session.model);
+    state.conversation.sync(&state.messages);
+    session.messages = state.messages.clone();
+    session.tree = state.conversation.clone();
+    session.variables = state.variables.clone();
+    session.banned_phrases = state.banned_phrases.clone();
+
+    let result = session.save();
+    let needs_title = session.title.is_none();
+    let id = session.id.clone();
+    state.current_session = Some(session);
+
+    match result {
+        Ok(()) => {
+            if needs_title {
+                generate_session_title(state, id.clone(), shared_state);
+            }
+            format!("Saved session {}", id)
+        }
+        Err(e) => format!("Failed to save session: {}", e),
+    }
+}
+
+fn set_session_title(state: &mut AppState, title: &str) -> String {
+    let Some(session) = state.current_session.as_mut() else {
+        return "Save the session first (:save)".to_string();
+    };
+    session.title = Some(title.to_string());
+    match session.save() {
+        Ok(()) => "Title updated".to_string(),
+        Err(e) => format!("Failed to save session: {}", e),
+    }
+}
+
+/// 在后台请模型生成标题和一句话摘要，完成后写回会话文件
+fn generate_session_title(state: &AppState, id: String, shared_state: &SharedState) {
+    let Some(model) = state
+        .config
+        .title_model
+        .clone()
+        .or_else(|| state.selected_model.clone())
+    else {
+        return;
+    };
+    let messages = session::title_prompt(&session::to_messages(&state.messages));
+    let s = shared_state.clone();
+
+    std::thread::spawn(move || {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let client = OllamaClient::new(None);
+        let Ok(reply) = rt.block_on(client.chat(&model, messages)) else {
+            return;
+        };
+        let (title, summary) = session::parse_title_reply(&reply);
+        if title.is_empty() {
+            return;
+        }
+
+        // 从磁盘重新读取，避免覆盖生成期间保存的新消息
+        if let Some(mut session) = session::load_session(&id) {
+            session.title = Some(title.clone());
+            session.summary = summary.clone();
+            let _ = session.save();
+        }
+
+        rt.block_on(async {
+            let mut s = s.lock().await;
+            if let Some(current) = s.current_session.as_mut() {
+                if current.id == id {
+                    current.title = Some(title);
+                    current.summary = summary;
+                }
+            }
+        });
+    });
+}
+
+/// 从选中的消息（默认最后一条）分叉出新分支，原来的后续消息保留为另一个分支
+fn fork_conversation(state: &mut AppState) -> String {
+    if state.is_loading {
+        return "Wait for the reply to finish before branching".to_string();
+    }
+    if state.messages.is_empty() {
+        return "Nothing to branch from".to_string();
+    }
+    let index = state
+        .chat_selected
+        .unwrap_or(state.messages.len() - 1)
+        .min(state.messages.len() - 1);
+    state.conversation.sync(&state.messages);
+    state.messages = state.conversation.fork(index);
+    forget_failed_reply(state);
+    state.chat_selected = None;
+    state.chat_search_matches.clear();
+    state.auto_scroll = true;
+    format!(
+        "Branched after message {} — the original thread is kept (B: branches)",
+        index + 1
+    )
+}
+
+fn open_branch_picker(state: &mut AppState) {
+    if !state.is_loading {
+        state.conversation.sync(&state.messages);
+    }
+    state.branch_list = state.conversation.branches();
+    let current = state.branch_list.iter().position(|b| b.current);
+    state.branch_list_state.select(current.or(Some(0)));
+    state.branch_picker_open = true;
+}
+
+fn handle_branch_picker_key(state: &mut AppState, key: KeyCode) {
+    let len = state.branch_list.len();
+    let selected = state.branch_list_state.selected().unwrap_or(0);
+    match key {
+        KeyCode::Char('j') | KeyCode::Down if len > 0 => {
+            state
+                .branch_list_state
+                .select(Some((selected + 1).min(len - 1)));
+        }
+        KeyCode::Char('k') | KeyCode::Up => {
+            state
+                .branch_list_state
+                .select(Some(selected.saturating_sub(1)));
+        }
+        KeyCode::Char('g') => state.branch_list_state.select(Some(0)),
+        KeyCode::Char('G') | KeyCode::End if len > 0 => {
+            state.branch_list_state.select(Some(len - 1));
+        }
+        KeyCode::Enter => {
+            if state.is_loading {
+                state.status_message =
+                    Some(" Wait for the reply to finish before switching ".to_string());
+                return;
+            }
+            if let Some(branch) = state.branch_list.get(selected) {
+                // 列表可能是在生成中打开的，先记下之后的消息，否则切换时会丢失
+                state.conversation.sync(&state.messages);
+                state.messages = state.conversation.switch(branch.leaf);
+                forget_failed_reply(state);
+                state.chat_selected = None;
+                state.chat_search_matches.clear();
+                state.auto_scroll = true;
+                state.branch_picker_open = false;
+            }
+        }
+        KeyCode::Esc | KeyCode::Char('q') => {
+            state.branch_picker_open = false;
+        }
+        _ => {}
+    }
+}
+
+fn open_session(state: &mut AppState, session: Session) {
+    state.expanded_channels.clear();
+    state.conversation = session.conversation();
+    state.conversation.detect_languages();
+    state.messages = state.conversation.messages();
+    forget_failed_reply(state);
+    state.chat_selected = None;
+    state.variables = session.variables.clone();
+    state.banned_phrases = session.banned_phrases.clone();
+    if session.model.is_some() {
+        state.selected_model = session.model.clone();
+    }
+    state.current_session = Some(session);
+    state.chat_search_matches.clear();
+    state.auto_scroll = true;
+}
+
+/// `:export [format] [file]`：按格式导出当前对话；省略格式时按扩展名推断，默认为 Open WebUI 的 JSON
+fn export_conversation(state: &mut AppState, arg: &str) -> String {
+    if state.messages.is_empty() {
+        return "Nothing to export".to_string();
+    }
+    let first = arg.split_whitespace().next().unwrap_or_default();
+    let (exporter, path) = match export::find(first) {
+        Some(exporter) => (exporter, arg[first.len()..].trim()),
+        None => match export::find_by_path(std::path::Path::new(arg)) {
+            Some(exporter) => (exporter, arg),
+            None if arg.is_empty() || arg.contains(['.', '/', '\\']) => (
+                export::find("json").expect("json exporter is registered"),
+                arg,
+            ),
+            None => {
+                return format!(
+                    "Unknown format \"{}\" (formats: {})",
+                    first,
+                    export::format_names()
+                )
+            }
+        },
+    };
+
+    state.conversation.sync(&state.messages);
+    let mut session = state
+        .current_session
+        .clone()
+        .unwrap_or_else(|| Session::new(state.selected_model.clone(), Vec::new()));
+    session.model = state.selected_model.clone().or(session.model);
+    session.messages = state.messages.clone();
+    session.tree = state.conversation.clone();
+
+    let path = if path.is_empty() {
+        exporter.default_path()
+    } else {
+        std::path::PathBuf::from(path)
+    };
+    match exporter.export(&session, &path) {
+        Ok(what) => format!(
+            "Exported {} to {} ({})",
+            what,
+            path.display(),
+            exporter.description()
+        ),
+        Err(e) => format!("Export failed: {}", e),
+    }
+}
+
+/// `:replay [speed]`：按原来的节奏逐条重新显示当前对话，回复逐字出现
+fn start_replay(state: &mut AppState, speed: f64) -> String {
+    if state.is_loading {
+        return "Wait for the reply to finish".to_string();
+    }
+    if state.messages.is_empty() {
+        return "Nothing to replay".to_string();
+    }
+    let mut player = replay::Player::new(std::mem::take(&mut state.messages), 1.0);
+    player.set_speed(speed);
+    state.messages = player.timeline.frame(0.0);
+    state.replay = Some(player);
+    state.chat_mode = ChatMode::Normal;
+    state.chat_selected = None;
+    state.auto_scroll = true;
+    "Replaying · Space: pause | +/-: speed | ←/→: seek | Esc: stop".to_string()
+}
+
+fn update_replay(state: &mut AppState) {
+    let Some(player) = &state.replay else {
+        return;
+    };
+    let frame = player.timeline.frame(player.position());
+    if frame.len() != state.messages.len()
+        || frame.last().map(|m| m.content().len())
+            != state.messages.last().map(|m| m.content().len())
+    {
+        state.messages = frame;
+        state.auto_scroll = true;
+    }
+}
+
+fn stop_replay(state: &mut AppState) {
+    if let Some(player) = state.replay.take() {
+        state.messages = player.original;
+        state.auto_scroll = true;
+    }
+}
+
+fn handle_replay_key(state: &mut AppState, key: KeyCode) {
+    let Some(player) = state.replay.as_mut() else {
+        return;
+    };
+    match key {
+        KeyCode::Char(' ') => player.toggle_pause(),
+        KeyCode::Char('+') | KeyCode::Char('=') => player.set_speed(player.speed * 2.0),
+        KeyCode::Char('-') => player.set_speed(player.speed / 2.0),
+        KeyCode::Right | KeyCode::Char('l') => player.seek(5.0),
+        KeyCode::Left | KeyCode::Char('h') => player.seek(-5.0),
+        KeyCode::Char('r') => {
+            player.seek(-player.position());
+            if player.is_paused() {
+                player.toggle_pause();
+            }
+        }
+        KeyCode::Esc | KeyCode::Char('q') => stop_replay(state),
+        _ => {}
+    }
+}
+
+/// `:replay cast [file]`：把当前对话导出为 asciicast，可用 asciinema 播放或转成 GIF
+fn export_replay_cast(state: &AppState, path: &str) -> String {
+    let messages = &state.messages;
+    if messages.is_empty() {
+        return "Nothing to replay".to_string();
+    }
+    let path = if path.is_empty() {
+        std::path::PathBuf::from(format!(
+            "{}.cast",
+            export::default_export_dir("ollama-cli-replay").display()
+        ))
+    } else {
+        std::path::PathBuf::from(path)
+    };
+    let title = state
+        .current_session
+        .as_ref()
+        .map(|s| s.display_title())
+        .unwrap_or_else(|| "ollama-cli".to_string());
+    let (width, height) = crossterm::terminal::size().unwrap_or((100, 30));
+    match replay::Timeline::new(messages.clone()).write_cast(&path, width, height, &title) {
+        Ok(()) => format!(
+            "Replay saved to {} (asciinema play to watch)",
+            path.display()
+        ),
+        Err(e) => format!("Export failed: {}", e),
+    }
+}
+
+/// 导入 Open WebUI 导出的对话，保存为会话并打开第一条
+fn import_webui(state: &mut AppState, path: &str) -> String {
+    if path.is_empty() {
+        return "Usage: :import <file.json>".to_string();
+    }
+    let mut sessions = match openwebui::import_file(std::path::Path::new(path)) {
+        Ok(sessions) => sessions,
+        Err(e) => return format!("Import failed: {}", e),
+    };
+    let failed = sessions
+        .iter_mut()
+        .filter_map(|session| session.save().err())
+        .count();
+    let count = sessions.len();
+    if let Some(first) = sessions.into_iter().next() {
+        open_session(state, first);
+    }
+    if failed > 0 {
+        format!(
+            "Imported {} conversations, {} could not be saved",
+            count, failed
+        )
+    } else {
+        format!("Imported {} conversations from {}", count, path)
+    }
+}
+
+fn open_session_picker(state: &mut AppState) {
+    state.session_list = session::list_sessions();
+    state.session_list_state.select(Some(0));
+    state.session_picker_open = true;
+}
+
+fn handle_session_picker_key(state: &mut AppState, key: KeyCode) {
+    match key {
+        KeyCode::Char('j') | KeyCode::Down => {
+            if let Some(selected) = state.session_list_state.selected() {
+                if state.session_list.is_empty() {
+                    return;
+                }
+                let new_selected = (selected + 1).min(state.session_list.len() - 1);
+                state.session_list_state.select(Some(new_selected));
+            }
+        }
+        KeyCode::Char('k') | KeyCode::Up => {
+            if let Some(selected) = state.session_list_state.selected() {
+                state
+                    .session_list_state
+                    .select(Some(selected.saturating_sub(1)));
+            }
+        }
+        KeyCode::Char('g') => {
+            state.session_list_state.select(Some(0));
+        }
+        KeyCode::Char('G') | KeyCode::End if !state.session_list.is_empty() => {
+            state
+                .session_list_state
+                .select(Some(state.session_list.len() - 1));
+        }
+        KeyCode::Enter => {
+            if let Some(selected) = state.session_list_state.selected() {
+                if let Some(session) = state.session_list.get(selected).cloned() {
+                    state.status_message = Some(format!(" Opened {} ", session.display_title()));
+                    open_session(state, session);
+                    state.session_picker_open = false;
+                }
+            }
+        }
+        KeyCode::Char('d') => {
+            if let Some(selected) = state.session_list_state.selected() {
+                if let Some(session) = state.session_list.get(selected) {
+                    if session::delete_session(&session.id).is_ok() {
+                        state.session_list.remove(selected);
+                        if selected >= state.session_list.len() {
+                            state
+                                .session_list_state
+                                .select(Some(state.session_list.len().saturating_sub(1)));
+                        }
+                    }
+                }
+            }
+        }
+        KeyCode::Esc => {
+            state.session_picker_open = false;
+        }
+        _ => {}
+    }
+}
+
+/// 未指定标签时补上 `:latest`，便于与已安装模型的名称比较
+fn full_model_name(name: &str) -> String {
+    if name.contains(':') {
+        name.to_string()
+    } else {
+        format!("{}:latest", name)
+    }
+}
+
+fn latest_transfer(state: &AppState, model: &str) -> Option<downloads::Transfer> {
+    state
+        .downloads
+        .snapshot()
+        .into_iter()
+        .rev()
+        .find(|t| t.model == model)
+}
+
+fn handle_quick_pull_key(state: &mut AppState, key: KeyCode) {
+    if state.quick_pull_confirm {
+        match key {
+            KeyCode::Char('y') | KeyCode::Char('Y') | KeyCode::Enter => {
+                if let Some(model) = &state.quick_pull {
+                    state.downloads.enqueue(model);
+                }
+                state.quick_pull_confirm = false;
+            }
+            KeyCode::Char('n') | KeyCode::Esc => {
+                state.quick_pull = None;
+                state.quick_pull_confirm = false;
+                state.quick_pull_popup = false;
+            }
+            _ => {}
+        }
+        return;
+    }
+
+    if key == KeyCode::Esc {
+        state.quick_pull_popup = false;
+        // 失败后关闭弹窗即放弃自动切换
+        let failed = state
+            .quick_pull
+            .as_deref()
+            .and_then(|model| latest_transfer(state, model))
+            .is_some_and(|t| t.status.is_finished());
+        if failed {
+            state.quick_pull = None;
+        }
+    }
+}
+
+/// 设置状态栏消息并记入通知历史
+fn notify(
+    state: &mut AppState,
+    kind: notifications::Kind,
+    message: String,
+    target: Option<Target>,
+) {
+    state.status_message = Some(format!(" {} ", message));
+    state.notifications.push(kind, message, target);
+}
+
+/// 下载完成或失败时各记一条通知
+fn notify_finished_transfers(state: &mut AppState) {
+    for transfer in state.downloads.snapshot() {
+        let message = match &transfer.status {
+            TransferStatus::Completed => format!("Pulled {}", transfer.model),
+            TransferStatus::Failed(e) => format!("Pull of {} failed: {}", transfer.model, e),
+            _ => continue,
+        };
+        if !state.notified_transfers.insert(transfer.id) {
+            continue;
+        }
+        let (kind, status) = if matches!(transfer.status, TransferStatus::Failed(_)) {
+            (notifications::Kind::Error, "failed")
+        } else {
+            (notifications::Kind::Download, "finished")
+        };
+        let payload = webhook::Payload::new("pull", status, &transfer.model, message.clone());
+        fire_webhook(state, payload);
+        state
+            .notifications
+            .push(kind, message, Some(Target::Transfer(transfer.id)));
+    }
+}
+
+/// 配置了 `webhook_url` 时在后台发送
+fn fire_webhook(state: &AppState, payload: webhook::Payload) {
+    if let Some(url) = state
+        .config
+        .webhook_url
+        .as_deref()
+        .filter(|u| !u.is_empty())
+    {
+        webhook::send(url, payload);
+    }
+}
+
+/// 回复结束的 webhook，附带最后一条回复的内容和所属会话
+fn reply_webhook(state: &AppState, model: &str, status: &'static str, message: String) {
+    let mut payload = webhook::Payload::new("generation", status, model, message);
+    payload.session = state.current_session.as_ref().map(|s| s.id.clone());
+    if let Some(reply) = state.messages.last().filter(|m| m.role() == "assistant") {
+        payload.content = Some(reply.content().to_string());
+        payload.eval_count = reply.meta.as_ref().and_then(|m| m.stats.eval_count);
+    }
+    fire_webhook(state, payload);
+}
+
+fn handle_notifications_key(state: &mut AppState, key: KeyCode) {
+    let len = state.notifications.entries().len();
+    let selected = state.notifications_state.selected().unwrap_or(0);
+    match key {
+        KeyCode::Char('j') | KeyCode::Down if len > 0 => {
+            state
+                .notifications_state
+                .select(Some((selected + 1).min(len - 1)));
+        }
+        KeyCode::Char('k') | KeyCode::Up => {
+            state
+                .notifications_state
+                .select(Some(selected.saturating_sub(1)));
+        }
+        KeyCode::Char('c') => {
+            state.notifications.clear();
+        }
+        KeyCode::Enter => {
+            let target = state
+                .notifications
+                .entries()
+                .get(selected)
+                .and_then(|n| n.target.clone());
+            match target {
+                Some(Target::Message(timestamp)) => {
+                    // 回复所在的对话可能已经切换；同一秒内的用户消息和回复以回复为准
+                    let index = state
+                        .messages
+                        .iter()
+                        .rposition(|m| m.timestamp == timestamp && m.role() == "assistant")
+                        .or_else(|| state.messages.iter().position(|m| m.timestamp == timestamp));
+                    match index {
+                        Some(index) => {
+                            state.notifications_open = false;
+                            state.chat_mode = ChatMode::Normal;
+                            state.chat_selected = Some(index);
+                            state.chat_scroll_target = Some(index);
+                        }
+                        None => {
+                            state.status_message = Some(
+                                " That message is not in the current conversation ".to_string(),
+                            );
+                        }
+                    }
+                }
+                Some(Target::Transfer(id)) => {
+                    state.notifications_open = false;
+                    if let Some(index) = state.downloads.snapshot().iter().position(|t| t.id == id)
+                    {
+                        state.downloads_list_state.select(Some(index));
+                    }
+                    if state.tabs.contains(&Tab::Downloads) {
+                        state.current_tab = Tab::Downloads;
+                    }
+                }
+                _ => {}
+            }
+        }
+        KeyCode::Esc | KeyCode::Char('q') => {
+            state.notifications_open = false;
+        }
+        _ => {}
+    }
+}
+
+/// 从聊天发起的拉取完成后自动切换到该模型
+fn update_quick_pull(state: &mut AppState) {
+    if state.quick_pull_confirm {
+        return;
+    }
+    let Some(model) = state.quick_pull.clone() else {
+        return;
+    };
+    let Some(transfer) = latest_transfer(state, &model) else {
+        return;
+    };
+    match transfer.status {
+        TransferStatus::Completed => {
+            state.selected_model = Some(full_model_name(&model));
+            apply_model_defaults(state);
+            state.quick_pull = None;
+            state.quick_pull_popup = false;
+            state.status_message = Some(format!(" Pulled {} — now using it ", model));
+        }
+        TransferStatus::Cancelled => {
+            state.quick_pull = None;
+            state.quick_pull_popup = false;
+        }
+        _ => {}
+    }
+}
+
+fn open_variables_editor(state: &mut AppState) {
+    state.variables_editor_open = true;
+    state.variable_editing = false;
+    state.variables_list_state.select(Some(0));
+}
+
+/// 变量变化后同步到当前会话，已保存的会话立即写回磁盘
+fn sync_session_variables(state: &mut AppState) {
+    if let Some(session) = state.current_session.as_mut() {
+        session.variables = state.variables.clone();
+        let _ = session.save();
+    }
+}
+
+fn handle_variables_editor_key(state: &mut AppState, key: KeyCode) {
+    if state.variable_editing {
+        match key {
+            KeyCode::Char(c) => state.variable_input.insert_char(c),
+            KeyCode::Backspace => state.variable_input.backspace(),
+            KeyCode::Delete => state.variable_input.delete(),
+            KeyCode::Left => state.variable_input.move_left(),
+            KeyCode::Right => state.variable_input.move_right(),
+            KeyCode::Home => state.variable_input.home(),
+            KeyCode::End => state.variable_input.end(),
+            KeyCode::Enter => match variables::parse_assignment(state.variable_input.as_str()) {
+                Some((key, value)) => {
+                    if let Some(old_key) = state.variable_editing_key.take() {
+                        state.variables.remove(&old_key);
+                    }
+                    let index = state.variables.range(..key.clone()).count();
+                    state.variables.insert(key, value);
+                    state.variables_list_state.select(Some(index));
+                    state.variable_input.clear();
+                    state.variable_editing = false;
+                    sync_session_variables(state);
+                }
+                None => {
+                    state.status_message = Some(" Expected key=value ".to_string());
+                }
+            },
+            KeyCode::Esc => {
+                state.variable_input.clear();
+                state.variable_editing_key = None;
+                state.variable_editing = false;
+            }
+            _ => {}
+        }
+        return;
+    }
+
+    let selected_key = state
+        .variables_list_state
+        .selected()
+        .and_then(|i| state.variables.keys().nth(i).cloned());
+
+    match key {
+        KeyCode::Char('j') | KeyCode::Down => {
+            if let Some(selected) = state.variables_list_state.selected() {
+                if state.variables.is_empty() {
+                    return;
+                }
+                let new_selected = (selected + 1).min(state.variables.len() - 1);
+                state.variables_list_state.select(Some(new_selected));
+            }
+        }
+        KeyCode::Char('k') | KeyCode::Up => {
+            if let Some(selected) = state.variables_list_state.selected() {
+                state
+                    .variables_list_state
+                    .select(Some(selected.saturating_sub(1)));
+            }
+        }
+        KeyCode::Char('a') | KeyCode::Char('n') => {
+            state.variable_input.clear();
+            state.variable_editing_key = None;
+            state.variable_editing = true;
+        }
+        KeyCode::Char('e') | KeyCode::Enter => {
+            if let Some(key) = selected_key {
+                state.variable_input.clear();
+                state
+                    .variable_input
+                    .insert_str(&format!("{}={}", key, state.variables[&key]));
+                state.variable_editing_key = Some(key);
+                state.variable_editing = true;
+            }
+        }
+        KeyCode::Char('d') => {
+            if let Some(key) = selected_key {
+                state.variables.remove(&key);
+                let len = state.variables.len();
+                if let Some(selected) = state.variables_list_state.selected() {
+                    if selected >= len {
+                        state
+                            .variables_list_state
+                            .select(Some(len.saturating_sub(1)));
+                    }
+                }
+                sync_session_variables(state);
+            }
+        }
+        KeyCode::Esc => {
+            state.variables_editor_open = false;
+        }
+        _ => {}
+    }
+}
+
+/// 命令行参数中支持 \n 和 \t 转义，便于输入多行的 suffix/template
+fn unescape(text: &str) -> String {
+    text.replace("\\n", "\n").replace("\\t", "\t")
+}
+
+fn contains(area: Rect, column: u16, row: u16) -> bool {
+    column >= area.x && column < area.x + area.width && row >= area.y && row < area.y + area.height
+}
+
+/// 根据 Tabs 组件的布局（左右各一格 padding，标签之间一格分隔符）找到被点击的标签
+fn tab_at(tabs: &[Tab], area: Rect, column: u16) -> Option<Tab> {
+    let mut x = area.x;
+    for (tab, title) in tabs.iter().zip(tabs::tab_labels(tabs)) {
+        let width = 1 + input::display_width(&title) as u16 + 1;
+        if column >= x && column < x + width {
+            return Some(*tab);
+        }
+        x += width + 1;
+    }
+    None
+}
+
+/// 当前可见的列表及其长度
+fn active_list(state: &mut AppState) -> Option<(&mut ratatui::widgets::ListState, usize)> {
+    if state.current_tab == Tab::Chat && state.session_picker_open {
+        return Some((&mut state.session_list_state, state.session_list.len()));
+    }
+    if state.current_tab == Tab::Chat && state.variables_editor_open {
+        return Some((&mut state.variables_list_state, state.variables.len()));
+    }
+    match state.current_tab {
+        Tab::Models => Some((&mut state.model_list_state, state.models.len())),
+        Tab::Search => Some((&mut state.search_list_state, state.search_results.len())),
+        Tab::Prompts if !state.prompt_input_mode => {
+            Some((&mut state.prompts_list_state, state.prompts.len()))
+        }
+        Tab::Downloads => {
+            let len = state.downloads.snapshot().len();
+            Some((&mut state.downloads_list_state, len))
+        }
+        Tab::Memory => {
+            let len = state.memory.entries().len();
+            Some((&mut state.memory_list_state, len))
+        }
+        _ => None,
+    }
+}
+
+fn handle_mouse(state: &mut AppState, mouse: MouseEvent) {
+    let areas = state.hit_areas;
+    let (column, row) = (mouse.column, mouse.row);
+
+    match mouse.kind {
+        MouseEventKind::ScrollDown | MouseEventKind::ScrollUp => {
+            let down = mouse.kind == MouseEventKind::ScrollDown;
+            if contains(areas.list, column, row) {
+                if let Some((list_state, len)) = active_list(state) {
+                    if len == 0 {
+                        return;
+                    }
+                    let selected = list_state.selected().unwrap_or(0);
+                    let new_selected = if down {
+                        (selected + 1).min(len - 1)
+                    } else {
+                        selected.saturating_sub(1)
+                    };
+                    list_state.select(Some(new_selected));
+                }
+            } else if contains(areas.messages, column, row) {
+                state.auto_scroll = false;
+                state.chat_scroll = if down {
+                    state.chat_scroll.saturating_add(3)
+                } else {
+                    state.chat_scroll.saturating_sub(3)
+                };
+            }
+        }
+        MouseEventKind::Down(MouseButton::Left) => {
+            if contains(areas.tabs, column, row) {
+                if !state.prompt_input_mode
+                    && !state.session_picker_open
+                    && !state.variables_editor_open
+                {
+                    if let Some(tab) = tab_at(&state.tabs, areas.tabs, column) {
+                        state.current_tab = tab;
+                    }
+                }
+            } else if contains(areas.list, column, row) {
+                // 列表带边框：跳过上下边框所在的行
+                if row == areas.list.y || row + 1 >= areas.list.y + areas.list.height {
+                    return;
+                }
+                let inner_row = (row - areas.list.y - 1) as usize;
+                // 会话列表的条目占多行
+                let heights: Vec<usize> =
+                    if state.current_tab == Tab::Chat && state.session_picker_open {
+                        state.session_list.iter().map(session_item_height).collect()
+                    } else {
+                        Vec::new()
+                    };
+                if let Some((list_state, len)) = active_list(state) {
+                    let offset = list_state.offset();
+                    let index = if heights.is_empty() {
+                        offset + inner_row
+                    } else {
+                        let mut top = 0;
+                        let mut index = len;
+                        for (i, height) in heights.iter().enumerate().skip(offset) {
+                            if inner_row < top + height {
+                                index = i;
+                                break;
+                            }
+                            top += height;
+                        }
+                        index
+                    };
+                    if index < len {
+                        list_state.select(Some(index));
+                    }
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// 启动托管的 `ollama serve` 并打开日志面板，只适用于本机地址
+fn start_server(state: &mut AppState) -> String {
+    let endpoint = transport::endpoint();
+    if !connection::is_local(&endpoint) {
+        return format!("Ollama at {} is not on this machine", endpoint);
+    }
+    match state.server.start() {
+        Ok(()) => {
+            state.connection.wake();
+            state.server_log_open = true;
+            "Starting `ollama serve`...".to_string()
+        }
+        Err(e) => e.to_string(),
+    }
+}
+
+/// 当前是否在输入框中输入文字（此时字母键不作为快捷键）
+fn is_typing(state: &AppState) -> bool {
+    match state.current_tab {
+        Tab::Chat => {
+            state.chat_mode != ChatMode::Normal || state.variable_editing || state.scratchpad_open
+        }
+        Tab::Search => true,
+        Tab::Prompts => state.prompt_input_mode,
+        Tab::Models | Tab::Downloads | Tab::Memory => false,
+    }
+}
+
+/// 把粘贴（或输入法一次提交）的文本插入当前获得焦点的输入框
+fn handle_paste(state: &mut AppState, text: &str) {
+    // 草稿本保留粘贴内容中的换行
+    if state.current_tab == Tab::Chat && state.scratchpad_open {
+        state
+            .scratchpad
+            .insert_str(&text.replace("\r\n", "\n").replace('\r', "\n"));
+        return;
+    }
+    let text = text.replace("\r\n", "\n").replace(['\r', '\n'], " ");
+    match state.current_tab {
+        Tab::Chat if state.variable_editing => state.variable_input.insert_str(&text),
+        Tab::Chat => match state.chat_mode {
+            ChatMode::Insert => {
+                state.input.insert_str(&text);
+                state.lint_hints.clear();
+            }
+            ChatMode::Search => state.chat_search_query.push_str(&text),
+            ChatMode::Command => state.chat_command.push_str(&text),
+            ChatMode::Normal => {}
+        },
+        Tab::Search if !state.search_browsing => state.search_query.push_str(&text),
+        Tab::Prompts if state.prompt_input_mode => {
+            if state.prompt_editing_name {
+                state.prompt_name_buffer.push_str(&text);
+            } else {
+                state.prompt_edit_buffer.push_str(&text);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn handle_models_input(state: &mut AppState, key: KeyCode, shared_state: &SharedState) {
+    if let Some(names) = state.model_delete_confirm.clone() {
+        match key {
+            KeyCode::Char('y') | KeyCode::Char('Y') => {
+                state.model_delete_confirm = None;
+                delete_models(state, names, shared_state);
+            }
+            KeyCode::Char('n') | KeyCode::Esc => {
+                state.model_delete_confirm = None;
+            }
+            _ => {}
+        }
+        return;
+    }
+
+    if let Some(name) = state.model_info.clone() {
+        handle_model_info_key(state, &name, key, shared_state);
+        return;
+    }
+
+    match key {
+        KeyCode::Char('j') | KeyCode::Down => {
+            if let Some(selected) = state.model_list_state.selected() {
+                if state.models.is_empty() {
+                    return;
+                }
+                let new_selected = (selected + 1).min(state.models.len() - 1);
+                state.model_list_state.select(Some(new_selected));
+            }
+        }
+        KeyCode::Char('k') | KeyCode::Up => {
+            if let Some(selected) = state.model_list_state.selected() {
+                let new_selected = selected.saturating_sub(1);
+                state.model_list_state.select(Some(new_selected));
+            }
+        }
+        KeyCode::Char('G') | KeyCode::End if !state.models.is_empty() => {
+            state.model_list_state.select(Some(state.models.len() - 1));
+        }
+        KeyCode::Char('g') => {
+            state.model_list_state.select(Some(0));
+        }
+        KeyCode::Char(' ') => {
+            if let Some(selected) = state.model_list_state.selected() {
+                if let Some(model) = state.models.get(selected) {
+                    if !state.model_marked.remove(&model.name) {
+                        state.model_marked.insert(model.name.clone());
+                    }
+                    let new_selected = (selected + 1).min(state.models.len() - 1);
+                    state.model_list_state.select(Some(new_selected));
+                }
+            }
+        }
+        KeyCode::Char('u') => {
+            state.model_marked.clear();
+        }
+        KeyCode::Char('i') => {
+            if let Some(model) = state
+                .model_list_state
+                .selected()
+                .and_then(|i| state.models.get(i))
+            {
+                open_model_info(state, model.name.clone(), shared_state);
+            }
+        }
+        KeyCode::Char('s') => {
+            state.model_sort = state.model_sort.next();
+            // 大小和修改时间默认从大到小、从新到旧
+            state.model_sort_desc =
+                matches!(state.model_sort, ModelSort::Size | ModelSort::Modified);
+            sort_models(state);
+        }
+        KeyCode::Char('r') => {
+            state.model_sort_desc = !state.model_sort_desc;
+            sort_models(state);
+        }
+        KeyCode::Char('d') => {
+            // 有标记时删除所有标记的模型，否则删除选中的模型
+            let names: Vec<String> = if state.model_marked.is_empty() {
+                state
+                    .model_list_state
+                    .selected()
+                    .and_then(|i| state.models.get(i))
+                    .map(|m| vec![m.name.clone()])
+                    .unwrap_or_default()
+            } else {
+                state
+                    .models
+                    .iter()
+                    .filter(|m| state.model_marked.contains(&m.name))
+                    .map(|m| m.name.clone())
+                    .collect()
+            };
+            if !names.is_empty() {
+                state.model_delete_confirm = Some(names);
+            }
+        }
+        KeyCode::Enter => {
+            if let Some(selected) = state.model_list_state.selected() {
+                if let Some(model) = state.models.get(selected) {
+                    state.selected_model = Some(model.name.clone());
+                    state.current_tab = Tab::Chat;
+                    if apply_model_defaults(state) {
+                        state.status_message = Some(
+                            " Applied the last settings used with this model (D: reset) "
+                                .to_string(),
+                        );
+                    }
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// 打开模型详情，并在后台读取模型库中的推荐设置
+fn open_model_info(state: &mut AppState, name: String, shared_state: &SharedState) {
+    state.model_info = Some(name.clone());
+    state.recommended = None;
+    state.recommended_error = None;
+    state.recommended_loading = true;
+    state.model_offload = None;
+    state.sample_prompts.clear();
+
+    let size = state
+        .models
+        .iter()
+        .find(|m| m.name == name)
+        .map(|m| m.size.max(0) as u64)
+        .unwrap_or_default();
+    let num_ctx = state.model_settings.get(&name).and_then(|o| o.num_ctx);
+    let s = shared_state.clone();
+    let model = name.clone();
+    std::thread::spawn(move || {
+        let show = OllamaClient::show(&model);
+        let offload = estimate_offload(&show, size, num_ctx);
+        let capabilities = show.map(|info| info.capabilities).unwrap_or_default();
+        let generic = samples::generic(&model, &capabilities);
+        {
+            let mut s = s.blocking_lock();
+            if s.model_info.as_ref() != Some(&model) {
+                return;
+            }
+            s.model_offload = Some(offload);
+            s.sample_prompts = generic.clone();
+        }
+
+        // 模型卡片中的示例排在前面，取不到时只用通用示例
+        let card = ModelSearch::new().card_examples(&model).unwrap_or_default();
+        let mut s = s.blocking_lock();
+        if s.model_info.as_ref() == Some(&model) && !card.is_empty() {
+            s.sample_prompts = samples::merge(card, generic);
+        }
+    });
+
+    let s = shared_state.clone();
+    std::thread::spawn(move || {
+        let result = ModelSearch::new().recommended(&name);
+        let mut s = s.blocking_lock();
+        if s.model_info.as_ref() != Some(&name) {
+            return;
+        }
+        s.recommended_loading = false;
+        match result {
+            Ok(recommended) => s.recommended = Some(recommended),
+            Err(e) => s.recommended_error = Some(format!("Not available: {}", e)),
+        }
+    });
+}
+
+/// 结合检测到的显存和 /api/show 中的层数估算 GPU 上能放下多少层
+fn estimate_offload(
+    show: &anyhow::Result<ollama::ShowResponse>,
+    size: u64,
+    num_ctx: Option<u32>,
+) -> String {
+    let Some(vram) = gpu::detect() else {
+        return "no GPU detected, runs on CPU".to_string();
+    };
+    let show = match show {
+        Ok(show) => show,
+        Err(e) => return format!("unknown ({})", connection::describe_error(e)),
+    };
+    match gpu::Offload::estimate(size, show, num_ctx, &vram) {
+        Some(offload) => format!(
+            "{} · needs ~{}, {} free of {} ({})",
+            offload.label(),
+            gpu::format_gib(offload.vram_used),
+            gpu::format_gib(vram.free),
+            gpu::format_gib(vram.total),
+            vram.name
+        ),
+        None => "unknown (no layer info)".to_string(),
+    }
+}
+
+/// 在临时对话中把示例提示词发给模型，原来的对话暂存起来，`:back` 恢复
+fn try_sample(state: &mut AppState, model: &str, prompt: String, shared_state: &SharedState) {
+    if state.is_loading {
+        state.status_message = Some(" Wait for the current reply to finish ".to_string());
+        return;
+    }
+    if state.stashed_chat.is_none() {
+        state.stashed_chat = Some(StashedChat {
+            messages: std::mem::take(&mut state.messages),
+            conversation: std::mem::take(&mut state.conversation),
+            current_session: state.current_session.take(),
+            selected_model: state.selected_model.clone(),
+        });
+    }
+    state.messages.clear();
+    forget_failed_reply(state);
+    state.conversation = ConversationTree::default();
+    state.current_session = None;
+    state.chat_selected = None;
+    state.selected_model = Some(model.to_string());
+    state.model_info = None;
+    state.current_tab = Tab::Chat;
+    state.chat_mode = ChatMode::Normal;
+
+    state.messages.push(ChatEntry::new("user", prompt));
+    start_generation(state, shared_state, None);
+}
+
+/// 重新连上 Ollama 后：检查当前模型是否还在，之前因断线失败的回复提示重新发送
+fn after_reconnect(state: &mut AppState) {
+    let restarted = state.connection.take_restarted();
+    let missing = state
+        .selected_model
+        .clone()
+        .filter(|model| !state.models.is_empty() && !state.models.iter().any(|m| m.name == *model));
+    if let Some(model) = missing {
+        let message = format!(
+            "Reconnected, but {} is no longer installed — pick another model",
+            model
+        );
+        notify(state, notifications::Kind::Error, message, None);
+    } else if state.failed_reply {
+        state.retry_offer = true;
+        state.status_message = Some(" Reconnected to Ollama ".to_string());
+    } else if restarted {
+        state.status_message = Some(" Ollama restarted, reconnected ".to_string());
+    } else {
+        state.status_message = Some(" Reconnected to Ollama ".to_string());
+    }
+}
+
+/// 换成另一个对话或分支后，之前失败的回复不再属于当前对话，不再提示重发
+fn forget_failed_reply(state: &mut AppState) {
+    state.failed_reply = false;
+    state.retry_offer = false;
+}
+
+/// 重新发送因断线失败的回复，去掉失败时留下的部分回复
+fn resend_failed_reply(state: &mut AppState, shared_state: &SharedState) -> String {
+    state.retry_offer = false;
+    if state.is_loading {
+        return "Wait for the reply to finish".to_string();
+    }
+    if !state.failed_reply {
+        return "No failed request to resend".to_string();
+    }
+    if !state.connection.is_connected() {
+        return "Ollama is still unreachable".to_string();
+    }
+    if state
+        .messages
+        .last()
+        .is_some_and(|m| m.role() == "assistant")
+    {
+        state.messages.pop();
+    }
+    if state.messages.last().map(|m| m.role()) != Some("user") {
+        state.failed_reply = false;
+        return "No failed request to resend".to_string();
+    }
+    start_generation(state, shared_state, None);
+    "Resending the last request".to_string()
+}
+
+/// 结束试用，恢复之前的对话和模型
+fn restore_stashed_chat(state: &mut AppState) -> String {
+    if state.is_loading {
+        return "Wait for the reply to finish".to_string();
+    }
+    let Some(stash) = state.stashed_chat.take() else {
+        return "Not in a throwaway chat".to_string();
+    };
+    state.messages = stash.messages;
+    forget_failed_reply(state);
+    state.conversation = stash.conversation;
+    state.current_session = stash.current_session;
+    state.selected_model = stash.selected_model;
+    state.chat_selected = None;
+    state.auto_scroll = true;
+    "Back to the previous conversation".to_string()
+}
+
+/// 启动时检查守护进程中是否有上次留下的回复
+fn check_detached_jobs(state: &mut AppState) {
+    if !daemon::is_running() {
+        return;
+    }
+    state.detached_jobs = daemon::list().unwrap_or_default();
+    if !state.detached_jobs.is_empty() {
+        state.status_message = Some(format!(
+            " {} detached repl{} in the daemon — :attach to resume ",
+            state.detached_jobs.len(),
+            if state.detached_jobs.len() == 1 {
+                "y"
             } else {
-                ""
+                "ies"
             }
-        } else {
-            ""
-        };
-
-        let preview = Paragraph::new(current_content)
-            .style(Style::default().fg(Color::White))
-            .block(
-                Block::default()
-                    .borders(Borders::ALL)
-                    .title(format!(" Preview{} ", active_indicator)),
-            )
-            .wrap(ratatui::widgets::Wrap { trim: false });
-        frame.render_widget(preview, chunks[1]);
+        ));
     }
 }
 
-fn handle_chat_key(state: &mut AppState, key: KeyCode, shared_state: &SharedState) {
+/// `:attach [id]`：恢复守护进程中任务所属的对话，并继续接收（或直接取回）回复
+fn attach_job(state: &mut AppState, arg: &str, shared_state: &SharedState) -> String {
     if state.is_loading {
-        return;
+        return "Wait for the reply to finish".to_string();
     }
+    state.detached_jobs = match daemon::list() {
+        Ok(jobs) => jobs,
+        Err(e) => return e.to_string(),
+    };
+    let job = if arg.is_empty() {
+        state.detached_jobs.last().cloned()
+    } else {
+        let Ok(id) = arg.parse::<u64>() else {
+            return "Usage: :attach [job id]".to_string();
+        };
+        state.detached_jobs.iter().find(|j| j.id == id).cloned()
+    };
+    let Some(job) = job else {
+        return "No detached replies in the daemon".to_string();
+    };
 
-    match key {
-        KeyCode::Char('j') => {
-            state.auto_scroll = false;
-            let viewport = 5u16;
-            state.chat_scroll = state.chat_scroll.saturating_add(viewport);
+    let history: Vec<ChatEntry> = job
+        .messages
+        .iter()
+        .filter(|m| m.role != "system")
+        .map(|m| ChatEntry::new(&m.role, m.content.clone()))
+        .collect();
+    match job.session.as_deref().and_then(session::load_session) {
+        // 会话保存时可能还没有最后一条提问，以守护进程中的为准
+        Some(session) => {
+            open_session(state, session);
+            if state.messages.len() != history.len() {
+                state.messages = history;
+                state.conversation.sync(&state.messages);
+            }
         }
-        KeyCode::Char('k') => {
-            state.auto_scroll = false;
-            let viewport = 5u16;
-            state.chat_scroll = state.chat_scroll.saturating_sub(viewport);
+        None => {
+            // 没有会话的任务放在临时对话中，原来的对话和试用模型时一样暂存，`:back` 恢复
+            let stashed = state.stashed_chat.is_none() && !state.messages.is_empty();
+            if stashed {
+                state.stashed_chat = Some(StashedChat {
+                    messages: std::mem::take(&mut state.messages),
+                    conversation: std::mem::take(&mut state.conversation),
+                    current_session: state.current_session.take(),
+                    selected_model: state.selected_model.clone(),
+                });
+            }
+            state.messages = history;
+            forget_failed_reply(state);
+            state.conversation = ConversationTree::default();
+            state.current_session = None;
+            state.chat_selected = None;
         }
-        KeyCode::Char('G') => {
-            state.auto_scroll = false;
-            state.chat_scroll = u16::MAX;
+    }
+    state.selected_model = Some(job.model.clone());
+
+    let mut reply = ChatEntry::new("assistant", String::new());
+    reply.meta = Some(GenerationMeta {
+        model: job.model.clone(),
+        mode: "chat".to_string(),
+        ..GenerationMeta::default()
+    });
+    state.messages.push(reply);
+    state.is_loading = true;
+    state.auto_scroll = true;
+
+    state.attached_job = Some(job.id);
+
+    let s_for_callback = shared_state.clone();
+    let s_for_join = shared_state.clone();
+    let job_id = job.id;
+    let handle = daemon::attach(job.id, move |chunk| {
+        let mut s = s_for_callback.blocking_lock();
+        if s.attached_job != Some(job_id) {
+            return false;
         }
-        KeyCode::Char('g') => {
-            state.auto_scroll = false;
-            state.chat_scroll = 0;
+        if let Some(last) = s.messages.last_mut().filter(|m| m.role() == "assistant") {
+            last.message.content = chunk;
         }
-        KeyCode::Char(c) => {
-            state.input_text.push(c);
+        true
+    });
+    let model = job.model.clone();
+    std::thread::spawn(move || {
+        let result = handle.join();
+        let mut s = s_for_join.blocking_lock();
+        s.is_loading = false;
+        s.detached_jobs.retain(|j| j.id != job.id);
+        if s.attached_job == Some(job.id) {
+            s.attached_job = None;
         }
-        KeyCode::Backspace => {
-            state.input_text.pop();
+        let target = s.messages.last().map(|m| Target::Message(m.timestamp));
+        match result {
+            Ok(Ok(completion)) => {
+                if let Some(last) = s.messages.last_mut().filter(|m| m.role() == "assistant") {
+                    last.message.content = completion.content;
+                    last.detect_language();
+                    if let Some(meta) = last.meta.as_mut() {
+                        meta.stats = completion.stats;
+                    }
+                }
+                let (event, message) = if completion.cancelled {
+                    ("stopped", format!("Reply from {} cancelled", model))
+                } else {
+                    (
+                        "finished",
+                        format!("Reply from {} finished (reattached)", model),
+                    )
+                };
+                reply_webhook(&s, &model, event, message.clone());
+                notify(&mut s, notifications::Kind::Generation, message, target);
+            }
+            Ok(Err(e)) => {
+                if s.messages
+                    .last()
+                    .is_some_and(|m| m.role() == "assistant" && m.content().is_empty())
+                {
+                    s.messages.pop();
+                }
+                let message = connection::describe_error(&e);
+                reply_webhook(&s, &model, "failed", message.clone());
+                notify(&mut s, notifications::Kind::Error, message, None);
+            }
+            Err(_) => {}
         }
-        KeyCode::Enter => {
-            if !state.input_text.is_empty() && state.selected_model.is_some() {
-                let user_input = state.input_text.clone();
-                state.input_text.clear();
-
-                state.messages.push(ChatMessage {
-                    role: "user".to_string(),
-                    content: user_input.clone(),
-                });
+    });
+    if state.stashed_chat.is_some() && job.session.is_none() {
+        format!(
+            "Attached to job {} ({}) — :back returns to the previous conversation",
+            job.id, job.model
+        )
+    } else {
+        format!("Attached to job {} ({})", job.id, job.model)
+    }
+}
 
-                state.messages.push(ChatMessage {
-                    role: "assistant".to_string(),
-                    content: String::new(),
-                });
+fn handle_model_info_key(
+    state: &mut AppState,
+    name: &str,
+    key: KeyCode,
+    shared_state: &SharedState,
+) {
+    match key {
+        KeyCode::Char(c @ '1'..='9') => {
+            let index = c as usize - '1' as usize;
+            if let Some(prompt) = state.sample_prompts.get(index).cloned() {
+                try_sample(state, name, prompt, shared_state);
+            }
+        }
+        KeyCode::Char('a') => {
+            let Some(options) = state
+                .recommended
+                .as_ref()
+                .map(|r| r.options.clone())
+                .filter(|o| !o.is_empty())
+            else {
+                state.status_message = Some(" No recommended settings to apply ".to_string());
+                return;
+            };
+            state.model_settings.insert(name.to_string(), options);
+            state.status_message = Some(match model_settings::save(&state.model_settings) {
+                Ok(()) => format!(" Applied recommended settings for {} ", name),
+                Err(e) => format!(" Applied for this run, failed to save: {} ", e),
+            });
+        }
+        KeyCode::Char('c') if state.model_settings.contains_key(name) => {
+            state.model_settings.remove(name);
+            state.status_message = Some(match model_settings::save(&state.model_settings) {
+                Ok(()) => format!(" Cleared saved settings for {} ", name),
+                Err(e) => format!(" Failed to save: {} ", e),
+            });
+        }
+        KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('i') | KeyCode::Enter => {
+            state.model_info = None;
+        }
+        _ => {}
+    }
+}
 
-                let model = state.selected_model.clone().unwrap();
-                let system_prompt = state.system_prompt.clone();
+/// 在后台依次删除模型，完成后刷新列表
+fn delete_models(state: &mut AppState, names: Vec<String>, shared_state: &SharedState) {
+    state.status_message = Some(format!(" Deleting {} models... ", names.len()));
+    let s = shared_state.clone();
 
-                // 构建包含系统提示词的消息列表
-                let mut messages = Vec::new();
-                if !system_prompt.is_empty() {
-                    messages.push(ChatMessage {
-                        role: "system".to_string(),
-                        content: system_prompt,
-                    });
-                }
-                messages.extend(state.messages.clone());
+    std::thread::spawn(move || {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let client = OllamaClient::new(None);
+        let mut deleted = 0;
+        let mut failures = Vec::new();
+        for name in &names {
+            match rt.block_on(client.delete_model(name)) {
+                Ok(()) => deleted += 1,
+                Err(e) => failures.push(format!("{}: {}", name, e)),
+            }
+        }
 
-                let s_for_callback = shared_state.clone();
-                let s_for_join = shared_state.clone();
+        rt.block_on(async {
+            refresh_models(&s).await;
+            let mut s = s.lock().await;
+            for name in &names {
+                s.model_marked.remove(name);
+            }
+            if s.selected_model.as_ref().is_some_and(|m| names.contains(m)) && failures.is_empty() {
+                s.selected_model = None;
+            }
+            s.status_message = Some(if failures.is_empty() {
+                format!(" Deleted {} models ", deleted)
+            } else {
+                format!(
+                    " Deleted {} models, failed: {} ",
+                    deleted,
+                    failures.join("; ")
+                )
+            });
+        });
+    });
+}
 
-                state.is_loading = true;
-                state.auto_scroll = true;
+fn handle_search_input(state: &mut AppState, key: KeyEvent, shared_state: &SharedState) {
+    // AltGr 在 Windows 上表现为 Ctrl+Alt，不应触发快捷键
+    let ctrl =
+        key.modifiers.contains(KeyModifiers::CONTROL) && !key.modifiers.contains(KeyModifiers::ALT);
 
-                let handle = OllamaClient::chat_streaming(model, messages, move |chunk| {
-                    let s = s_for_callback.clone();
-                    let mut s = s.blocking_lock();
-                    if let Some(last) = s.messages.last_mut() {
-                        if last.role == "assistant" {
-                            last.content = chunk;
-                        }
-                    }
-                });
+    if state.tag_picker_open {
+        handle_tag_picker_key(state, key.code);
+        return;
+    }
 
-                std::thread::spawn(move || {
-                    let _ = handle.join();
-                    let s = s_for_join.clone();
-                    let mut s = s.blocking_lock();
-                    s.is_loading = false;
-                });
+    if state.search_browsing {
+        match key.code {
+            KeyCode::Char(c) if !ctrl && c.is_alphanumeric() => {
+                jump_to_letter(state, c);
+                return;
+            }
+            KeyCode::Esc => {
+                state.search_browsing = false;
+                state.search_results.clear();
+                return;
             }
+            // 浏览时查询框隐藏，不再编辑它
+            KeyCode::Char(_) | KeyCode::Backspace if !ctrl => return,
+            _ => {}
         }
-        _ => {}
     }
-}
 
-fn handle_models_input(state: &mut AppState, key: KeyCode, _shared_state: &SharedState) {
-    match key {
-        KeyCode::Char('j') | KeyCode::Down => {
-            if let Some(selected) = state.model_list_state.selected() {
-                if state.models.is_empty() {
-                    return;
-                }
-                let new_selected = (selected + 1).min(state.models.len() - 1);
-                state.model_list_state.select(Some(new_selected));
-            }
-        }
-        KeyCode::Char('k') | KeyCode::Up => {
-            if let Some(selected) = state.model_list_state.selected() {
-                let new_selected = selected.saturating_sub(1);
-                state.model_list_state.select(Some(new_selected));
+    match key.code {
+        KeyCode::Char('b') if ctrl => {
+            // 正在进行的搜索结束后才能进入浏览，否则会显示搜索结果而不是整个模型库
+            if state.is_searching {
+                state.status_message = Some(" Wait for the search to finish ".to_string());
+                return;
             }
+            state.search_browsing = true;
+            start_search(state, shared_state);
         }
-        KeyCode::Char('G') | KeyCode::End => {
-            if !state.models.is_empty() {
-                state.model_list_state.select(Some(state.models.len() - 1));
+        KeyCode::Char('t') if ctrl => {
+            if let Some(model) = state
+                .search_list_state
+                .selected()
+                .and_then(|i| state.search_results.get(i))
+            {
+                open_tag_picker(state, model.name.clone(), shared_state);
             }
         }
-        KeyCode::Char('g') => {
-            state.model_list_state.select(Some(0));
-        }
-        KeyCode::Enter => {
-            if let Some(selected) = state.model_list_state.selected() {
-                if let Some(model) = state.models.get(selected) {
-                    state.selected_model = Some(model.name.clone());
-                    state.current_tab = Tab::Chat;
+        KeyCode::Char('p') if ctrl => {
+            // 将选中的模型加入下载队列
+            if let Some(selected) = state.search_list_state.selected() {
+                if let Some(model) = state.search_results.get(selected) {
+                    state.downloads.enqueue(&model.name);
+                    state.status_message = Some(format!(" Queued pull of {} ", model.name));
                 }
             }
         }
-        _ => {}
-    }
-}
-
-fn handle_search_input(state: &mut AppState, key: KeyCode, shared_state: &SharedState) {
-    match key {
         KeyCode::Char('j') | KeyCode::Down => {
             if let Some(selected) = state.search_list_state.selected() {
                 if state.search_results.is_empty() {
@@ -713,11 +5594,10 @@ fn handle_search_input(state: &mut AppState, key: KeyCode, shared_state: &Shared
             }
         }
         KeyCode::Char('G') | KeyCode::End => {
-            if !state.search_results.is_empty() {
-                state
-                    .search_list_state
-                    .select(Some(state.search_results.len() - 1));
-            }
+            // 没有结果时不选中任何行，也不把 G 输入到查询中
+            state
+                .search_list_state
+                .select(state.search_results.len().checked_sub(1));
         }
         KeyCode::Char('g') => {
             state.search_list_state.select(Some(0));
@@ -729,34 +5609,197 @@ fn handle_search_input(state: &mut AppState, key: KeyCode, shared_state: &Shared
             state.search_query.pop();
         }
         KeyCode::Enter => {
-            if !state.is_searching {
-                let query = state.search_query.clone();
-                let s = shared_state.clone();
-
-                state.is_searching = true;
+            state.search_browsing = false;
+            start_search(state, shared_state);
+        }
+        _ => {}
+    }
+}
 
-                std::thread::spawn(move || {
-                    let rt = tokio::runtime::Runtime::new().unwrap();
-                    let searcher = ModelSearch::new();
+/// 在后台读取模型的标签列表并打开标签选择窗口
+fn open_tag_picker(state: &mut AppState, model: String, shared_state: &SharedState) {
+    state.tag_picker_open = true;
+    state.tag_picker_model = model.clone();
+    state.tag_list.clear();
+    state.tag_marked.clear();
+    state.tag_list_state.select(Some(0));
+    state.tags_loading = true;
+    state.tags_error = None;
 
-                    let results = if query.is_empty() {
-                        searcher.get_popular_models().unwrap_or_default()
-                    } else {
-                        searcher.search_online(&query).unwrap_or_default()
-                    };
+    let s = shared_state.clone();
+    std::thread::spawn(move || {
+        let result = ModelSearch::new().tags(&model);
+        let mut s = s.blocking_lock();
+        if s.tag_picker_model != model {
+            return;
+        }
+        s.tags_loading = false;
+        match result {
+            Ok(tags) => s.tag_list = tags,
+            Err(e) => s.tags_error = Some(e.to_string()),
+        }
+    });
+}
 
-                    rt.block_on(async {
-                        let mut s = s.lock().await;
-                        s.search_results = results;
-                        s.is_searching = false;
-                    });
-                });
+fn handle_tag_picker_key(state: &mut AppState, key: KeyCode) {
+    let len = state.tag_list.len();
+    let selected = state.tag_list_state.selected().unwrap_or(0);
+    match key {
+        KeyCode::Char('j') | KeyCode::Down if len > 0 => {
+            state
+                .tag_list_state
+                .select(Some((selected + 1).min(len - 1)));
+        }
+        KeyCode::Char('k') | KeyCode::Up => {
+            state
+                .tag_list_state
+                .select(Some(selected.saturating_sub(1)));
+        }
+        KeyCode::Char(' ') => {
+            if let Some(tag) = state.tag_list.get(selected).cloned() {
+                if !state.tag_marked.remove(&tag) {
+                    state.tag_marked.insert(tag);
+                }
+                if selected + 1 < len {
+                    state.tag_list_state.select(Some(selected + 1));
+                }
+            }
+        }
+        KeyCode::Enter => {
+            // 保持标签页中的顺序；没有标记时拉取当前选中的标签
+            let mut tags: Vec<String> = state
+                .tag_list
+                .iter()
+                .filter(|t| state.tag_marked.contains(*t))
+                .cloned()
+                .collect();
+            if tags.is_empty() {
+                tags.extend(state.tag_list.get(selected).cloned());
+            }
+            match tags.len() {
+                0 => return,
+                1 => state.downloads.enqueue(&tags[0]),
+                _ => state.downloads.enqueue_group(&tags),
             }
+            state.status_message = Some(format!(" Queued pull of {} ", tags.join(", ")));
+            state.tag_picker_open = false;
+        }
+        KeyCode::Esc | KeyCode::Char('q') => {
+            state.tag_picker_open = false;
         }
         _ => {}
     }
 }
 
+fn render_tag_picker(frame: &mut Frame, state: &mut AppState, area: ratatui::layout::Rect) {
+    let popup = centered_rect(50, 60, area);
+    frame.render_widget(Clear, popup);
+    let title = format!(" {} tags ", state.tag_picker_model);
+
+    let message = if state.tags_loading {
+        Some(("Loading tags...".to_string(), Color::DarkGray))
+    } else {
+        state
+            .tags_error
+            .as_ref()
+            .map(|e| (format!("Failed to load tags: {}", e), Color::Red))
+    };
+    if let Some((message, color)) = message {
+        let paragraph = Paragraph::new(message)
+            .style(Style::default().fg(color))
+            .wrap(ratatui::widgets::Wrap { trim: true })
+            .block(Block::default().borders(Borders::ALL).title(title));
+        frame.render_widget(paragraph, popup);
+        return;
+    }
+
+    let items: Vec<ListItem> = state
+        .tag_list
+        .iter()
+        .map(|tag| {
+            let mark = if state.tag_marked.contains(tag) {
+                "[x] "
+            } else {
+                "[ ] "
+            };
+            ListItem::new(format!("{}{}", mark, tag))
+        })
+        .collect();
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(title)
+                .title_bottom(" Space: mark | Enter: pull | Esc: close "),
+        )
+        .highlight_style(Style::default().fg(Color::Yellow))
+        .highlight_symbol("> ");
+    frame.render_stateful_widget(list, popup, &mut state.tag_list_state);
+}
+
+/// 在后台搜索，浏览模式下加载整个模型库
+fn start_search(state: &mut AppState, shared_state: &SharedState) {
+    if state.is_searching {
+        return;
+    }
+    let query = state.search_query.clone();
+    let browse = state.search_browsing;
+    let limit = state.config.search_limit();
+    let s = shared_state.clone();
+
+    state.is_searching = true;
+
+    std::thread::spawn(move || {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let searcher = ModelSearch::new();
+        let result = if browse {
+            searcher.browse()
+        } else {
+            searcher.search(&query, limit)
+        };
+
+        rt.block_on(async {
+            let mut s = s.lock().await;
+            s.search_backend = ModelSearch::active_backend();
+            s.is_searching = false;
+            // 结果返回前已经退出（或进入）浏览时丢弃
+            if s.search_browsing != browse {
+                return;
+            }
+            match result {
+                Ok(results) => {
+                    s.search_results = results;
+                    s.search_error = None;
+                }
+                Err(e) => {
+                    s.search_results.clear();
+                    s.search_error = Some(format!("{:#}", e));
+                }
+            }
+            s.search_list_state.select(Some(0));
+        });
+    });
+}
+
+/// 跳到下一个以该字母开头的模型，到末尾后从头开始
+fn jump_to_letter(state: &mut AppState, letter: char) {
+    let letter = letter.to_ascii_lowercase();
+    let starts_with = |m: &OnlineModel| {
+        m.name
+            .chars()
+            .next()
+            .is_some_and(|c| c.to_ascii_lowercase() == letter)
+    };
+    let current = state.search_list_state.selected().unwrap_or(0);
+    let len = state.search_results.len();
+    let next = (1..=len)
+        .map(|offset| (current + offset) % len)
+        .find(|&i| starts_with(&state.search_results[i]));
+    if let Some(index) = next {
+        state.search_list_state.select(Some(index));
+    }
+}
+
 fn handle_prompts_input(state: &mut AppState, key: KeyCode) {
     if state.prompt_input_mode {
         // 编辑模式
@@ -779,28 +5822,28 @@ fn handle_prompts_input(state: &mut AppState, key: KeyCode) {
                     state.prompt_edit_buffer.pop();
                 }
             }
-            KeyCode::Enter => {
-                if !state.prompt_name_buffer.is_empty() && !state.prompt_edit_buffer.is_empty() {
-                    if let Some(idx) = state.prompt_editing_index {
-                        // 编辑现有
-                        if let Some(prompt) = state.prompts.get_mut(idx) {
-                            prompt.name = state.prompt_name_buffer.clone();
-                            prompt.content = state.prompt_edit_buffer.clone();
-                        }
-                    } else {
-                        // 新建
-                        state.prompts.push(SavedPrompt {
-                            name: state.prompt_name_buffer.clone(),
-                            content: state.prompt_edit_buffer.clone(),
-                        });
+            KeyCode::Enter
+                if !state.prompt_name_buffer.is_empty() && !state.prompt_edit_buffer.is_empty() =>
+            {
+                if let Some(idx) = state.prompt_editing_index {
+                    // 编辑现有
+                    if let Some(prompt) = state.prompts.get_mut(idx) {
+                        prompt.name = state.prompt_name_buffer.clone();
+                        prompt.content = state.prompt_edit_buffer.clone();
                     }
-                    state.save_prompts();
-                    state.prompt_input_mode = false;
-                    state.prompt_edit_buffer.clear();
-                    state.prompt_name_buffer.clear();
-                    state.prompt_editing_index = None;
-                    state.prompt_editing_name = true;
+                } else {
+                    // 新建
+                    state.prompts.push(SavedPrompt {
+                        name: state.prompt_name_buffer.clone(),
+                        content: state.prompt_edit_buffer.clone(),
+                    });
                 }
+                state.save_prompts();
+                state.prompt_input_mode = false;
+                state.prompt_edit_buffer.clear();
+                state.prompt_name_buffer.clear();
+                state.prompt_editing_index = None;
+                state.prompt_editing_name = true;
             }
             _ => {}
         }
@@ -822,12 +5865,10 @@ fn handle_prompts_input(state: &mut AppState, key: KeyCode) {
                     state.prompts_list_state.select(Some(new_selected));
                 }
             }
-            KeyCode::Char('G') | KeyCode::End => {
-                if !state.prompts.is_empty() {
-                    state
-                        .prompts_list_state
-                        .select(Some(state.prompts.len() - 1));
-                }
+            KeyCode::Char('G') | KeyCode::End if !state.prompts.is_empty() => {
+                state
+                    .prompts_list_state
+                    .select(Some(state.prompts.len() - 1));
             }
             KeyCode::Char('g') => {
                 state.prompts_list_state.select(Some(0));
@@ -880,19 +5921,117 @@ fn handle_prompts_input(state: &mut AppState, key: KeyCode) {
     }
 }
 
+fn handle_memory_input(state: &mut AppState, key: KeyCode) {
+    let entries = state.memory.entries();
+
+    match key {
+        KeyCode::Char('j') | KeyCode::Down => {
+            if let Some(selected) = state.memory_list_state.selected() {
+                if entries.is_empty() {
+                    return;
+                }
+                let new_selected = (selected + 1).min(entries.len() - 1);
+                state.memory_list_state.select(Some(new_selected));
+            }
+        }
+        KeyCode::Char('k') | KeyCode::Up => {
+            if let Some(selected) = state.memory_list_state.selected() {
+                state
+                    .memory_list_state
+                    .select(Some(selected.saturating_sub(1)));
+            }
+        }
+        KeyCode::Char('G') | KeyCode::End if !entries.is_empty() => {
+            state.memory_list_state.select(Some(entries.len() - 1));
+        }
+        KeyCode::Char('g') => {
+            state.memory_list_state.select(Some(0));
+        }
+        KeyCode::Char('d') => {
+            if let Some(selected) = state.memory_list_state.selected() {
+                if let Some(entry) = entries.get(selected) {
+                    if let Err(e) = state.memory.remove(entry.id) {
+                        state.status_message = Some(format!(" Failed to delete: {} ", e));
+                    } else if selected + 1 >= entries.len() {
+                        state
+                            .memory_list_state
+                            .select(Some(selected.saturating_sub(1)));
+                    }
+                }
+            }
+        }
+        KeyCode::Char('m') => {
+            state.memory_enabled = !state.memory_enabled;
+        }
+        _ => {}
+    }
+}
+
+fn handle_downloads_input(state: &mut AppState, key: KeyCode) {
+    let transfers = state.downloads.snapshot();
+    let selected = state
+        .downloads_list_state
+        .selected()
+        .and_then(|i| transfers.get(i));
+
+    match key {
+        KeyCode::Char('j') | KeyCode::Down => {
+            if let Some(selected) = state.downloads_list_state.selected() {
+                if transfers.is_empty() {
+                    return;
+                }
+                let new_selected = (selected + 1).min(transfers.len() - 1);
+                state.downloads_list_state.select(Some(new_selected));
+            }
+        }
+        KeyCode::Char('k') | KeyCode::Up => {
+            if let Some(selected) = state.downloads_list_state.selected() {
+                let new_selected = selected.saturating_sub(1);
+                state.downloads_list_state.select(Some(new_selected));
+            }
+        }
+        KeyCode::Char('G') | KeyCode::End if !transfers.is_empty() => {
+            state.downloads_list_state.select(Some(transfers.len() - 1));
+        }
+        KeyCode::Char('g') => {
+            state.downloads_list_state.select(Some(0));
+        }
+        KeyCode::Char('c') => {
+            if let Some(t) = selected {
+                state.downloads.cancel(t.id);
+            }
+        }
+        KeyCode::Char('r') => {
+            if let Some(t) = selected {
+                state.downloads.retry(t.id);
+            }
+        }
+        KeyCode::Char('x') => {
+            state.downloads.clear_finished();
+            state.downloads_list_state.select(Some(0));
+        }
+        _ => {}
+    }
+}
+
 async fn refresh_models(state: &SharedState) {
     let client = OllamaClient::new(None);
     match client.list_models().await {
         Ok(response) => {
             let mut s = state.lock().await;
             s.models = response.models;
+            sort_models(&mut s);
+            if s.status_error
+                .as_deref()
+                .is_some_and(|e| e.starts_with(" Models: "))
+            {
+                s.status_error = None;
+            }
         }
         Err(e) => {
             let mut s = state.lock().await;
-            s.status_message = Some(format!(
-                "Failed to connect: {}. Make sure Ollama is running.",
-                e
-            ));
+            s.connection.report_failure(&e);
+            s.status_error = Some(format!(" Models: {} ", connection::describe_error(&e)));
         }
     }
 }
@@ -0,0 +1,235 @@
+use crate::config;
+use crate::ollama::ChatMessage;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+/// 从对话中提取、跨会话保留的一条事实
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MemoryEntry {
+    pub id: u64,
+    pub fact: String,
+    pub created_at: i64,
+    #[serde(default)]
+    pub source: Option<String>,
+}
+
+/// 长期记忆的存储后端，实现此 trait 即可替换默认的 JSON 文件存储
+pub trait MemoryStore: Send + Sync {
+    fn entries(&self) -> Vec<MemoryEntry>;
+    /// 添加一条事实，已存在相同内容时返回 false
+    fn add(&self, fact: &str, source: Option<&str>) -> anyhow::Result<bool>;
+    fn remove(&self, id: u64) -> anyhow::Result<()>;
+}
+
+/// 默认存储：~/.config/ollama-cli/memory.json，未指定文件时只保存在内存中
+#[derive(Default)]
+pub struct JsonFileStore {
+    path: Option<PathBuf>,
+    entries: Mutex<Vec<MemoryEntry>>,
+}
+
+impl JsonFileStore {
+    pub fn open(path: PathBuf) -> Self {
+        let entries = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+        Self {
+            path: Some(path),
+            entries: Mutex::new(entries),
+        }
+    }
+
+    fn persist(&self, entries: &[MemoryEntry]) -> anyhow::Result<()> {
+        if let Some(path) = &self.path {
+            std::fs::write(path, serde_json::to_string_pretty(entries)?)?;
+        }
+        Ok(())
+    }
+}
+
+impl MemoryStore for JsonFileStore {
+    fn entries(&self) -> Vec<MemoryEntry> {
+        self.entries.lock().map(|e| e.clone()).unwrap_or_default()
+    }
+
+    fn add(&self, fact: &str, source: Option<&str>) -> anyhow::Result<bool> {
+        let fact = fact.trim();
+        let mut entries = self
+            .entries
+            .lock()
+            .map_err(|_| anyhow::anyhow!("memory lock poisoned"))?;
+        if fact.is_empty() || entries.iter().any(|e| e.fact.eq_ignore_ascii_case(fact)) {
+            return Ok(false);
+        }
+        let id = entries.iter().map(|e| e.id).max().unwrap_or(0) + 1;
+        entries.push(MemoryEntry {
+            id,
+            fact: fact.to_string(),
+            created_at: chrono::Local::now().timestamp(),
+            source: source.map(str::to_string),
+        });
+        self.persist(&entries)?;
+        Ok(true)
+    }
+
+    fn remove(&self, id: u64) -> anyhow::Result<()> {
+        let mut entries = self
+            .entries
+            .lock()
+            .map_err(|_| anyhow::anyhow!("memory lock poisoned"))?;
+        entries.retain(|e| e.id != id);
+        self.persist(&entries)
+    }
+}
+
+#[derive(Clone)]
+pub struct Memory {
+    store: Arc<dyn MemoryStore>,
+}
+
+impl Default for Memory {
+    fn default() -> Self {
+        Self {
+            store: Arc::new(JsonFileStore::default()),
+        }
+    }
+}
+
+/// 读取 ~/.config/ollama-cli/memory.json
+pub fn load() -> Memory {
+    Memory {
+        store: Arc::new(JsonFileStore::open(
+            config::config_dir().join("memory.json"),
+        )),
+    }
+}
+
+impl Memory {
+    pub fn entries(&self) -> Vec<MemoryEntry> {
+        self.store.entries()
+    }
+
+    pub fn add(&self, fact: &str, source: Option<&str>) -> anyhow::Result<bool> {
+        self.store.add(fact, source)
+    }
+
+    pub fn remove(&self, id: u64) -> anyhow::Result<()> {
+        self.store.remove(id)
+    }
+
+    /// 按与 `query` 共有的词数挑选最相关的几条记忆
+    pub fn relevant(&self, query: &str, limit: usize) -> Vec<MemoryEntry> {
+        let query_tokens = tokens(query);
+        if query_tokens.is_empty() {
+            return Vec::new();
+        }
+        let mut scored: Vec<(usize, MemoryEntry)> = self
+            .entries()
+            .into_iter()
+            .map(|entry| {
+                (
+                    tokens(&entry.fact).intersection(&query_tokens).count(),
+                    entry,
+                )
+            })
+            .filter(|(score, _)| *score > 0)
+            .collect();
+        scored.sort_by(|a, b| b.0.cmp(&a.0).then(b.1.created_at.cmp(&a.1.created_at)));
+        scored
+            .into_iter()
+            .take(limit)
+            .map(|(_, entry)| entry)
+            .collect()
+    }
+}
+
+/// 英文按单词（3 个字母以上），中文等按相邻两字切分
+fn tokens(text: &str) -> HashSet<String> {
+    let mut tokens = HashSet::new();
+    for word in text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+    {
+        let word = word.to_lowercase();
+        if word.is_ascii() {
+            if word.len() >= 3 {
+                tokens.insert(word);
+            }
+        } else {
+            let chars: Vec<char> = word.chars().collect();
+            for pair in chars.windows(2) {
+                tokens.insert(pair.iter().collect());
+            }
+        }
+    }
+    tokens
+}
+
+/// 附加到系统提示词中的记忆
+pub fn context_block(entries: &[MemoryEntry]) -> Option<String> {
+    if entries.is_empty() {
+        return None;
+    }
+    let mut block = String::from("Things you remember from earlier conversations:");
+    for entry in entries {
+        block.push_str(&format!("\n- {}", entry.fact));
+    }
+    Some(block)
+}
+
+/// 请模型从最近一轮对话中提取值得长期记住的事实
+pub fn extraction_prompt(messages: &[ChatMessage]) -> Vec<ChatMessage> {
+    let mut transcript = String::new();
+    let recent = messages.len().saturating_sub(2);
+    for msg in messages[recent..].iter().filter(|m| m.role != "system") {
+        transcript.push_str(&format!("{}: {}\n\n", msg.role, msg.content));
+    }
+
+    vec![
+        ChatMessage {
+            role: "system".to_string(),
+            content: "Extract durable facts about the user, their projects and preferences \
+                      from the conversation (e.g. \"The user works on a Rust CLI called ollama-cli\"). \
+                      Ignore small talk and anything only relevant to this exchange. \
+                      Each fact is one short sentence. Return an empty list if there is nothing worth remembering."
+                .to_string(),
+        },
+        ChatMessage {
+            role: "user".to_string(),
+            content: transcript,
+        },
+    ]
+}
+
+/// 结构化输出的 JSON schema：{"facts": ["..."]}
+pub fn extraction_schema() -> serde_json::Value {
+    serde_json::json!({
+        "type": "object",
+        "properties": {
+            "facts": {
+                "type": "array",
+                "items": { "type": "string" }
+            }
+        },
+        "required": ["facts"]
+    })
+}
+
+#[derive(Debug, Deserialize)]
+struct Extraction {
+    #[serde(default)]
+    facts: Vec<String>,
+}
+
+pub fn parse_facts(reply: &str) -> Vec<String> {
+    serde_json::from_str::<Extraction>(reply.trim())
+        .map(|e| e.facts)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|f| f.trim().to_string())
+        .filter(|f| !f.is_empty())
+        .collect()
+}
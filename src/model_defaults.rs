@@ -0,0 +1,36 @@
+use crate::config;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+/// 某个模型最近一次对话使用的设置，新对话中选择该模型时自动应用
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModelDefaults {
+    #[serde(default)]
+    pub system_prompt: String,
+    /// 回复长度预设：`short`、`normal` 或 `detailed`
+    #[serde(default)]
+    pub length: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_reply_seconds: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_reply_tokens: Option<u64>,
+}
+
+pub type ModelDefaultsMap = BTreeMap<String, ModelDefaults>;
+
+fn path() -> PathBuf {
+    config::config_dir().join("model_defaults.json")
+}
+
+pub fn load() -> ModelDefaultsMap {
+    std::fs::read_to_string(path())
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+pub fn save(defaults: &ModelDefaultsMap) -> anyhow::Result<()> {
+    std::fs::write(path(), serde_json::to_string_pretty(defaults)?)?;
+    Ok(())
+}
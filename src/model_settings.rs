@@ -0,0 +1,23 @@
+use crate::config;
+use crate::ollama::ModelOptions;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+/// 按模型保存的运行参数（如从模型库应用的推荐设置），发送请求时自动带上
+pub type ModelSettings = BTreeMap<String, ModelOptions>;
+
+fn path() -> PathBuf {
+    config::config_dir().join("model_settings.json")
+}
+
+pub fn load() -> ModelSettings {
+    std::fs::read_to_string(path())
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+pub fn save(settings: &ModelSettings) -> anyhow::Result<()> {
+    std::fs::write(path(), serde_json::to_string_pretty(settings)?)?;
+    Ok(())
+}
@@ -0,0 +1,76 @@
+use std::collections::VecDeque;
+
+const MAX_NOTIFICATIONS: usize = 200;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Error,
+    Download,
+    Generation,
+}
+
+impl Kind {
+    pub fn label(self) -> &'static str {
+        match self {
+            Kind::Error => "error",
+            Kind::Download => "pull",
+            Kind::Generation => "reply",
+        }
+    }
+}
+
+/// 通知对应的位置，在通知列表中按 Enter 跳转
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Target {
+    /// 按时间戳查找的消息；切换对话或分支后消息的位置会变，找不到时不跳转
+    Message(i64),
+    /// 下载队列中的传输
+    Transfer(u64),
+}
+
+#[derive(Debug, Clone)]
+pub struct Notification {
+    pub time: chrono::DateTime<chrono::Local>,
+    pub kind: Kind,
+    pub message: String,
+    pub target: Option<Target>,
+}
+
+/// 本次运行中的通知历史，最新的在前
+#[derive(Debug, Clone, Default)]
+pub struct Notifications {
+    entries: VecDeque<Notification>,
+    unread_errors: usize,
+}
+
+impl Notifications {
+    pub fn push(&mut self, kind: Kind, message: String, target: Option<Target>) {
+        if kind == Kind::Error {
+            self.unread_errors += 1;
+        }
+        self.entries.push_front(Notification {
+            time: chrono::Local::now(),
+            kind,
+            message,
+            target,
+        });
+        self.entries.truncate(MAX_NOTIFICATIONS);
+    }
+
+    pub fn entries(&self) -> &VecDeque<Notification> {
+        &self.entries
+    }
+
+    pub fn unread_errors(&self) -> usize {
+        self.unread_errors
+    }
+
+    pub fn mark_read(&mut self) {
+        self.unread_errors = 0;
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.unread_errors = 0;
+    }
+}
@@ -1,5 +1,8 @@
+use crate::transport::{self, Endpoint, Response};
+use reqwest::Method;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
-use std::io::{BufRead, BufReader};
+use std::time::Instant;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Model {
@@ -8,6 +11,18 @@ pub struct Model {
     pub size: i64,
     pub digest: String,
     pub modified_at: Option<String>,
+    #[serde(default)]
+    pub details: Option<ModelDetails>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ModelDetails {
+    #[serde(default)]
+    pub family: String,
+    #[serde(default)]
+    pub parameter_size: String,
+    #[serde(default)]
+    pub quantization_level: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -21,11 +36,86 @@ pub struct ChatMessage {
     pub content: String,
 }
 
+/// 模型运行参数，对应 Ollama 请求中的 `options`，未设置的字段不会发送
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ModelOptions {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub num_predict: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_k: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub num_ctx: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repeat_penalty: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stop: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<i64>,
+}
+
+impl ModelOptions {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// 已设置的参数，用于界面显示，如 `temperature=0.6`
+    pub fn entries(&self) -> Vec<(&'static str, String)> {
+        let mut entries = Vec::new();
+        let mut push = |name, value: Option<String>| {
+            if let Some(value) = value {
+                entries.push((name, value));
+            }
+        };
+        push("temperature", self.temperature.map(|v| v.to_string()));
+        push("top_p", self.top_p.map(|v| v.to_string()));
+        push("top_k", self.top_k.map(|v| v.to_string()));
+        push("num_ctx", self.num_ctx.map(|v| v.to_string()));
+        push("num_predict", self.num_predict.map(|v| v.to_string()));
+        push("repeat_penalty", self.repeat_penalty.map(|v| v.to_string()));
+        push("stop", self.stop.as_ref().map(|stop| format!("{:?}", stop)));
+        push("seed", self.seed.map(|v| v.to_string()));
+        entries
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChatRequest {
     pub model: String,
     pub messages: Vec<ChatMessage>,
     pub stream: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub options: Option<ModelOptions>,
+    /// 结构化输出：`"json"` 或 JSON schema
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub format: Option<serde_json::Value>,
+}
+
+/// 流式响应最后一个分块中附带的统计信息，时长单位为纳秒
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct GenerationStats {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total_duration: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub load_duration: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt_eval_count: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub eval_count: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub eval_duration: Option<u64>,
+}
+
+impl GenerationStats {
+    /// 生成速度（token/s）
+    pub fn tokens_per_second(&self) -> Option<f64> {
+        let count = self.eval_count?;
+        let duration = self.eval_duration.filter(|d| *d > 0)?;
+        Some(count as f64 / (duration as f64 / 1e9))
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -33,96 +123,389 @@ pub struct ChatResponse {
     pub model: String,
     pub message: ChatMessage,
     pub done: bool,
+    #[serde(flatten)]
+    pub stats: GenerationStats,
+}
+
+/// 流式生成的最终结果
+#[derive(Debug, Clone, Default)]
+pub struct Completion {
+    pub content: String,
+    pub stats: GenerationStats,
+    /// 回调返回 false 提前结束，`content` 为已生成的部分
+    pub cancelled: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GenerateRequest {
+    pub model: String,
+    pub prompt: String,
+    pub stream: bool,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub raw: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub system: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub template: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub suffix: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub options: Option<ModelOptions>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GenerateResponse {
+    pub model: String,
+    #[serde(default)]
+    pub response: String,
+    pub done: bool,
+    #[serde(flatten)]
+    pub stats: GenerationStats,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VersionResponse {
+    pub version: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PullRequest {
+    pub model: String,
+    pub stream: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PullProgress {
+    #[serde(default)]
+    pub status: String,
+    pub digest: Option<String>,
+    pub total: Option<u64>,
+    pub completed: Option<u64>,
+    pub error: Option<String>,
+}
+
+/// Ollama 服务端返回的错误（如模型不存在），重试也无法恢复
+#[derive(Debug)]
+pub struct ApiError(pub String);
+
+impl std::fmt::Display for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShowRequest {
+    pub model: String,
+}
+
+/// /api/show 的响应，只保留用到的字段
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ShowResponse {
+    #[serde(default)]
+    pub details: ModelDetails,
+    /// 如 `completion`、`vision`、`tools`、`insert`、`embedding`
+    #[serde(default)]
+    pub capabilities: Vec<String>,
+    /// GGUF 元数据，键带有架构前缀，如 `llama.block_count`
+    #[serde(default)]
+    pub model_info: serde_json::Map<String, serde_json::Value>,
+}
+
+impl ShowResponse {
+    /// 按不带架构前缀的键读取数值，如 `block_count`、`attention.head_count_kv`
+    pub fn info_u64(&self, key: &str) -> Option<u64> {
+        let arch = self.model_info.get("general.architecture")?.as_str()?;
+        self.model_info.get(&format!("{}.{}", arch, key))?.as_u64()
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-#[allow(dead_code)]
 pub struct DeleteRequest {
     pub name: String,
 }
 
 pub struct OllamaClient {
-    base_url: String,
-    client: reqwest::Client,
+    endpoint: Endpoint,
 }
 
 impl OllamaClient {
     pub fn new(base_url: Option<String>) -> Self {
-        let base_url = base_url.unwrap_or_else(|| "http://localhost:11434".to_string());
-        Self {
-            base_url,
-            client: reqwest::Client::new(),
-        }
+        let endpoint = base_url
+            .map(|url| Endpoint::parse(&url, None))
+            .unwrap_or_else(transport::endpoint);
+        Self { endpoint }
     }
 
     pub async fn list_models(&self) -> anyhow::Result<ListResponse> {
-        let url = format!("{}/api/tags", self.base_url);
-        let response = self.client.get(&url).send().await?;
-        let models: ListResponse = response.json().await?;
-        Ok(models)
+        let endpoint = self.endpoint.clone();
+        tokio::task::spawn_blocking(move || -> anyhow::Result<ListResponse> {
+            let response = transport::request::<()>(&endpoint, Method::GET, "/api/tags", None)?;
+            check_status(response)?.json::<ListResponse>()
+        })
+        .await?
     }
 
-    #[allow(dead_code)]
     pub async fn delete_model(&self, name: &str) -> anyhow::Result<()> {
-        let url = format!("{}/api/delete", self.base_url);
+        let endpoint = self.endpoint.clone();
         let request = DeleteRequest {
             name: name.to_string(),
         };
-        self.client.delete(&url).json(&request).send().await?;
-        Ok(())
+        tokio::task::spawn_blocking(move || -> anyhow::Result<()> {
+            let response =
+                transport::request(&endpoint, Method::DELETE, "/api/delete", Some(&request))?;
+            check_status(response)?;
+            Ok(())
+        })
+        .await?
     }
 
-    #[allow(dead_code)]
     pub async fn chat(&self, model: &str, messages: Vec<ChatMessage>) -> anyhow::Result<String> {
-        let url = format!("{}/api/chat", self.base_url);
+        self.chat_with_format(model, messages, None).await
+    }
+
+    /// 非流式对话，`format` 为 JSON schema 时模型按结构化输出回复
+    pub async fn chat_with_format(
+        &self,
+        model: &str,
+        messages: Vec<ChatMessage>,
+        format: Option<serde_json::Value>,
+    ) -> anyhow::Result<String> {
+        let endpoint = self.endpoint.clone();
         let request = ChatRequest {
             model: model.to_string(),
             messages,
             stream: false,
+            options: None,
+            format,
         };
-        let response = self.client.post(&url).json(&request).send().await?;
-        let chat_response: ChatResponse = response.json().await?;
-        Ok(chat_response.message.content)
+        tokio::task::spawn_blocking(move || -> anyhow::Result<String> {
+            let response =
+                transport::request(&endpoint, Method::POST, "/api/chat", Some(&request))?;
+            let chat_response: ChatResponse = check_status(response)?.json()?;
+            Ok(chat_response.message.content)
+        })
+        .await?
     }
 
-    pub fn chat_streaming<F>(model: String, messages: Vec<ChatMessage>, callback: F) -> std::thread::JoinHandle<anyhow::Result<String>>
+    /// 流式对话，`callback` 收到目前为止的完整回复，返回 false 时停止生成；
+    /// 到 `deadline` 时断开连接，返回已生成的部分
+    pub fn chat_streaming<F>(
+        model: String,
+        messages: Vec<ChatMessage>,
+        options: Option<ModelOptions>,
+        deadline: Option<Instant>,
+        callback: F,
+    ) -> std::thread::JoinHandle<anyhow::Result<Completion>>
     where
-        F: Fn(String) + Send + 'static,
+        F: Fn(String) -> bool + Send + 'static,
     {
-        let base_url = "http://localhost:11434".to_string();
-        
+        let endpoint = transport::endpoint();
+
         std::thread::spawn(move || {
-            let client = reqwest::blocking::Client::new();
-            let url = format!("{}/api/chat", base_url);
-            
             let request = ChatRequest {
                 model,
                 messages,
                 stream: true,
+                options,
+                format: None,
             };
-            
-            let response = client.post(&url).json(&request).send()?;
-            
-            let reader = BufReader::new(response);
-            let mut content = String::new();
-            
-            for line in reader.lines() {
-                let line = line?;
-                if line.trim().is_empty() {
-                    continue;
+
+            let response = transport::request_until(
+                &endpoint,
+                Method::POST,
+                "/api/chat",
+                Some(&request),
+                deadline,
+            );
+            read_stream::<ChatResponse>(response, deadline, callback)
+        })
+    }
+
+    /// 通过 /api/generate 流式生成，适用于 raw prompt 和 FIM（suffix）等不需要聊天模板的场景
+    pub fn generate_streaming<F>(
+        request: GenerateRequest,
+        deadline: Option<Instant>,
+        callback: F,
+    ) -> std::thread::JoinHandle<anyhow::Result<Completion>>
+    where
+        F: Fn(String) -> bool + Send + 'static,
+    {
+        let endpoint = transport::endpoint();
+
+        std::thread::spawn(move || {
+            let request = GenerateRequest {
+                stream: true,
+                ..request
+            };
+
+            let response = transport::request_until(
+                &endpoint,
+                Method::POST,
+                "/api/generate",
+                Some(&request),
+                deadline,
+            );
+            read_stream::<GenerateResponse>(response, deadline, callback)
+        })
+    }
+
+    /// 读取已安装模型的详细信息
+    pub fn show(model: &str) -> anyhow::Result<ShowResponse> {
+        let request = ShowRequest {
+            model: model.to_string(),
+        };
+        let response = transport::request(
+            &transport::endpoint(),
+            Method::POST,
+            "/api/show",
+            Some(&request),
+        )?;
+        check_status(response)?.json()
+    }
+
+    /// 查询服务端版本，用于检测连接状态
+    pub fn version() -> anyhow::Result<String> {
+        let response =
+            transport::request::<()>(&transport::endpoint(), Method::GET, "/api/version", None)?;
+        Ok(check_status(response)?.json::<VersionResponse>()?.version)
+    }
+
+    /// 流式拉取模型，`on_progress` 返回 false 时中止；服务端重新拉取会续传未完成的层
+    pub fn pull_model<F>(model: &str, mut on_progress: F) -> anyhow::Result<()>
+    where
+        F: FnMut(&PullProgress) -> bool,
+    {
+        let request = PullRequest {
+            model: model.to_string(),
+            stream: true,
+        };
+
+        let response = transport::request(
+            &transport::endpoint(),
+            Method::POST,
+            "/api/pull",
+            Some(&request),
+        )?;
+        let response = check_status(response)?;
+
+        for line in response.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            if let Ok(progress) = serde_json::from_str::<PullProgress>(&line) {
+                if let Some(error) = progress.error {
+                    return Err(ApiError(error).into());
                 }
-                
-                if let Ok(resp) = serde_json::from_str::<ChatResponse>(&line) {
-                    content.push_str(&resp.message.content);
-                    callback(content.clone());
-                    
-                    if resp.done {
-                        break;
-                    }
+                if !on_progress(&progress) || progress.status == "success" {
+                    return Ok(());
                 }
             }
-            
-            Ok(content)
-        })
+        }
+
+        anyhow::bail!("pull stream ended before completion")
+    }
+}
+
+/// `/api/chat` 和 `/api/generate` 流式返回的一行：新增的文本、是否结束和结束时的统计
+trait StreamChunk: DeserializeOwned {
+    fn into_parts(self) -> (String, bool, GenerationStats);
+}
+
+impl StreamChunk for ChatResponse {
+    fn into_parts(self) -> (String, bool, GenerationStats) {
+        (self.message.content, self.done, self.stats)
+    }
+}
+
+impl StreamChunk for GenerateResponse {
+    fn into_parts(self) -> (String, bool, GenerationStats) {
+        (self.response, self.done, self.stats)
+    }
+}
+
+/// 逐行读取流式响应，`callback` 收到目前为止的完整回复，返回 false 时断开连接（Ollama 随之停止生成）。
+/// 过了 `deadline` 后连接超时断开，不算作错误，返回已生成的部分
+fn read_stream<T: StreamChunk>(
+    response: anyhow::Result<Response>,
+    deadline: Option<Instant>,
+    callback: impl Fn(String) -> bool,
+) -> anyhow::Result<Completion> {
+    let expired = || deadline.is_some_and(|d| Instant::now() >= d);
+    let mut content = String::new();
+    let stopped = |content: String| Completion {
+        content,
+        cancelled: true,
+        ..Completion::default()
+    };
+
+    let response = match response {
+        Err(_) if expired() => return Ok(stopped(content)),
+        response => check_status(response?)?,
+    };
+    for line in response.lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) if expired() => return Ok(stopped(content)),
+            Err(e) => return Err(e.into()),
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        // 生成中途出错（如显存不足）时服务端仍返回 200，错误写在单独的一行里
+        if let Some(error) = stream_error(&line) {
+            return Err(error.into());
+        }
+
+        if let Ok(chunk) = serde_json::from_str::<T>(&line) {
+            let (text, done, stats) = chunk.into_parts();
+            content.push_str(&text);
+            if !callback(content.clone()) {
+                return Ok(stopped(content));
+            }
+
+            if done {
+                return Ok(Completion {
+                    content,
+                    stats,
+                    cancelled: false,
+                });
+            }
+        }
+    }
+
+    Ok(Completion {
+        content,
+        ..Completion::default()
+    })
+}
+
+/// 流式响应中的 `{"error": "..."}` 行
+fn stream_error(line: &str) -> Option<ApiError> {
+    #[derive(Deserialize)]
+    struct StreamError {
+        error: String,
+    }
+    serde_json::from_str::<StreamError>(line)
+        .ok()
+        .map(|e| ApiError(e.error))
+}
+
+/// 非 2xx 响应转换为 ApiError，错误信息取自 Ollama 返回的 `error` 字段
+fn check_status(response: Response) -> anyhow::Result<Response> {
+    if response.is_success() {
+        return Ok(response);
     }
+    let status = response.status;
+    let body = response.text().unwrap_or_default();
+    let message = serde_json::from_str::<serde_json::Value>(&body)
+        .ok()
+        .and_then(|v| v.get("error").and_then(|e| e.as_str()).map(str::to_string))
+        .unwrap_or_else(|| format!("HTTP {}", status));
+    Err(ApiError(message).into())
 }
@@ -0,0 +1,285 @@
+use crate::session::{ChatEntry, ConversationTree, GenerationMeta, MessageNode, Session};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::path::Path;
+
+/// Open WebUI 导出的一条对话（“Export chat (.json)” 得到的是它们的数组）
+#[derive(Debug, Serialize, Deserialize)]
+struct WebUiChat {
+    #[serde(default)]
+    id: String,
+    #[serde(default)]
+    title: String,
+    chat: ChatBody,
+    #[serde(default)]
+    created_at: i64,
+    #[serde(default)]
+    updated_at: i64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ChatBody {
+    #[serde(default)]
+    title: String,
+    #[serde(default)]
+    models: Vec<String>,
+    #[serde(default)]
+    history: History,
+    /// 当前分支，旧版本的导出只有这个字段
+    #[serde(default)]
+    messages: Vec<WebUiMessage>,
+    /// 毫秒
+    #[serde(default)]
+    timestamp: i64,
+}
+
+/// 所有消息按 parentId / childrenIds 组成树，currentId 为当前分支的最后一条
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct History {
+    #[serde(default)]
+    messages: BTreeMap<String, WebUiMessage>,
+    #[serde(default, rename = "currentId")]
+    current_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct WebUiMessage {
+    id: String,
+    #[serde(default, rename = "parentId")]
+    parent_id: Option<String>,
+    #[serde(default, rename = "childrenIds")]
+    children_ids: Vec<String>,
+    role: String,
+    #[serde(default)]
+    content: String,
+    /// 秒
+    #[serde(default)]
+    timestamp: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    model: Option<String>,
+}
+
+/// 导出为 Open WebUI 可导入的 JSON，保留所有分支
+pub fn export_session(session: &Session, path: &Path) -> anyhow::Result<()> {
+    let tree = session.conversation();
+    let nodes = tree.nodes();
+    let id = |i: usize| format!("{}-{}", session.id, i);
+
+    let mut children: Vec<Vec<String>> = vec![Vec::new(); nodes.len()];
+    for (i, node) in nodes.iter().enumerate() {
+        if let Some(parent) = node.parent {
+            children[parent].push(id(i));
+        }
+    }
+    let message = |i: usize| {
+        let node = &nodes[i];
+        WebUiMessage {
+            id: id(i),
+            parent_id: node.parent.map(id),
+            children_ids: children[i].clone(),
+            role: node.entry.role().to_string(),
+            content: node.entry.content().to_string(),
+            timestamp: node.entry.timestamp,
+            model: node.entry.meta.as_ref().map(|m| m.model.clone()),
+        }
+    };
+
+    let mut current = Vec::new();
+    let mut cursor = tree.head();
+    while let Some(i) = cursor {
+        current.push(message(i));
+        cursor = nodes[i].parent;
+    }
+    current.reverse();
+
+    let title = session.display_title();
+    let chat = WebUiChat {
+        id: session.id.clone(),
+        title: title.clone(),
+        chat: ChatBody {
+            title,
+            models: session.model.clone().into_iter().collect(),
+            history: History {
+                messages: (0..nodes.len()).map(|i| (id(i), message(i))).collect(),
+                current_id: tree.head().map(id),
+            },
+            messages: current,
+            timestamp: session.created_at * 1000,
+        },
+        created_at: session.created_at,
+        updated_at: session.updated_at,
+    };
+    std::fs::write(path, serde_json::to_string_pretty(&[chat])?)?;
+    Ok(())
+}
+
+/// 读取 Open WebUI 导出的文件（数组或单个对话），每条对话转换为一个会话
+pub fn import_file(path: &Path) -> anyhow::Result<Vec<Session>> {
+    let content = std::fs::read_to_string(path)?;
+    let value: serde_json::Value = serde_json::from_str(&content)?;
+    let chats: Vec<WebUiChat> = if value.is_array() {
+        serde_json::from_value(value)?
+    } else {
+        vec![serde_json::from_value(value)?]
+    };
+    if chats.is_empty() {
+        anyhow::bail!("no conversations in {}", path.display());
+    }
+    Ok(chats.into_iter().map(to_session).collect())
+}
+
+fn to_session(chat: WebUiChat) -> Session {
+    let model = chat.chat.models.first().cloned();
+    let tree = if chat.chat.history.messages.is_empty() {
+        let entries: Vec<ChatEntry> = chat
+            .chat
+            .messages
+            .iter()
+            .map(|m| to_entry(m, model.as_deref()))
+            .collect();
+        ConversationTree::from_messages(&entries)
+    } else {
+        history_tree(&chat.chat.history, model.as_deref())
+    };
+
+    let created_at = if chat.created_at > 0 {
+        chat.created_at
+    } else {
+        chat.chat.timestamp / 1000
+    };
+    let title = [chat.title, chat.chat.title]
+        .into_iter()
+        .find(|t| !t.trim().is_empty());
+    // 文件名只保留安全字符，避免与本地会话冲突
+    let id: String = chat
+        .id
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '-')
+        .collect();
+
+    let mut session = Session::new(model, tree.messages());
+    if !id.is_empty() {
+        session.id = format!("webui-{}", id);
+    }
+    if created_at > 0 {
+        session.created_at = created_at;
+    }
+    session.title = title;
+    session.tree = tree;
+    session
+}
+
+/// 按父子关系逐层展开，保证父节点排在子节点之前
+fn history_tree(history: &History, model: Option<&str>) -> ConversationTree {
+    let mut children: HashMap<&str, Vec<&WebUiMessage>> = HashMap::new();
+    let mut queue: VecDeque<(&WebUiMessage, Option<usize>)> = VecDeque::new();
+    for message in history.messages.values() {
+        match message
+            .parent_id
+            .as_deref()
+            .filter(|p| history.messages.contains_key(*p))
+        {
+            Some(parent) => children.entry(parent).or_default().push(message),
+            None => queue.push_back((message, None)),
+        }
+    }
+    for list in children.values_mut() {
+        list.sort_by_key(|m| m.timestamp);
+    }
+
+    let mut nodes = Vec::new();
+    let mut index: HashMap<&str, usize> = HashMap::new();
+    while let Some((message, parent)) = queue.pop_front() {
+        index.insert(message.id.as_str(), nodes.len());
+        let current = nodes.len();
+        nodes.push(MessageNode {
+            entry: to_entry(message, model),
+            parent,
+        });
+        for child in children.get(message.id.as_str()).into_iter().flatten() {
+            queue.push_back((child, Some(current)));
+        }
+    }
+
+    let head = history
+        .current_id
+        .as_deref()
+        .and_then(|id| index.get(id).copied());
+    ConversationTree::from_nodes(nodes, head)
+}
+
+fn to_entry(message: &WebUiMessage, default_model: Option<&str>) -> ChatEntry {
+    let mut entry = ChatEntry::new(&message.role, message.content.clone());
+    entry.timestamp = message.timestamp;
+    if message.role == "assistant" {
+        if let Some(model) = message.model.as_deref().or(default_model) {
+            entry.meta = Some(GenerationMeta {
+                model: model.to_string(),
+                mode: "chat".to_string(),
+                ..GenerationMeta::default()
+            });
+        }
+    }
+    entry
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(role: &str, content: &str, timestamp: i64) -> ChatEntry {
+        let mut entry = ChatEntry::new(role, content.to_string());
+        entry.timestamp = timestamp;
+        entry
+    }
+
+    #[test]
+    fn export_and_import_keep_all_branches() {
+        let mut tree = ConversationTree::from_messages(&[
+            entry("user", "question", 1),
+            entry("assistant", "first answer", 2),
+        ]);
+        let mut retry = tree.fork(0);
+        retry.push(entry("assistant", "second answer", 3));
+        tree.sync(&retry);
+
+        let mut session = Session::new(Some("llama3".to_string()), tree.messages());
+        session.id = "roundtrip".to_string();
+        session.title = Some("Round trip".to_string());
+        session.tree = tree;
+
+        let path =
+            std::env::temp_dir().join(format!("ollama-cli-webui-{}.json", std::process::id()));
+        export_session(&session, &path).unwrap();
+        let imported = import_file(&path);
+        let _ = std::fs::remove_file(&path);
+        let imported = imported.unwrap();
+
+        assert_eq!(imported.len(), 1);
+        let restored = &imported[0];
+        assert_eq!(restored.id, "webui-roundtrip");
+        assert_eq!(restored.title.as_deref(), Some("Round trip"));
+        assert_eq!(restored.model.as_deref(), Some("llama3"));
+
+        let current: Vec<&str> = restored.messages.iter().map(|m| m.content()).collect();
+        assert_eq!(current, ["question", "second answer"]);
+        let branches = restored.tree.branches();
+        assert_eq!(branches.len(), 2);
+        assert!(branches
+            .iter()
+            .any(|b| !b.current && b.preview == "first answer"));
+        let meta = restored.messages[1].meta.as_ref().unwrap();
+        assert_eq!(meta.model, "llama3");
+    }
+
+    #[test]
+    fn import_reads_the_flat_message_list() {
+        let json = r#"{"id": "x", "title": "", "chat": {"title": "Flat", "models": ["m"],
+            "messages": [{"id": "1", "role": "user", "content": "hi", "timestamp": 5}]}}"#;
+        let chat: WebUiChat = serde_json::from_str(json).unwrap();
+        let session = to_session(chat);
+        assert_eq!(session.title.as_deref(), Some("Flat"));
+        assert_eq!(session.messages.len(), 1);
+        assert_eq!(session.messages[0].timestamp, 5);
+    }
+}
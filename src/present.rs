@@ -0,0 +1,294 @@
+//! `ollama-cli show`：不进入 TUI，把保存的会话排版后输出到分页器，便于通过 SSH 快速查看
+
+use crate::channels::{self, LineKind, TagRule};
+use crate::config::Config;
+use crate::session::{self, ChatEntry, Session};
+use crate::timefmt;
+use std::io::{BufRead, IsTerminal, Write};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+const BOLD: &str = "\x1b[1m";
+const DIM: &str = "\x1b[2m";
+const CYAN: &str = "\x1b[36m";
+const GREEN: &str = "\x1b[32m";
+const YELLOW: &str = "\x1b[33m";
+const RESET: &str = "\x1b[0m";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Style {
+    /// 整个对话连续输出
+    #[default]
+    Plain,
+    /// 每轮问答一页，页面高度与终端一致
+    Slides,
+}
+
+impl Style {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "plain" => Some(Style::Plain),
+            "slides" => Some(Style::Slides),
+            _ => None,
+        }
+    }
+}
+
+/// 按会话 ID、`last`、标题（不区分大小写的部分匹配）或 JSON 文件路径查找会话
+pub fn find_session(query: &str) -> Option<Session> {
+    let path = std::path::Path::new(query);
+    if path.extension().is_some_and(|ext| ext == "json") && path.exists() {
+        let content = std::fs::read_to_string(path).ok()?;
+        return serde_json::from_str(&content).ok();
+    }
+    if query == "last" || query == "latest" {
+        return session::list_sessions().into_iter().next();
+    }
+    if let Some(session) = session::load_session(query) {
+        return Some(session);
+    }
+    let needle = query.to_lowercase();
+    session::list_sessions().into_iter().find(|s| {
+        s.title
+            .as_deref()
+            .is_some_and(|t| t.to_lowercase().contains(&needle))
+    })
+}
+
+/// 排版后的页面；`color` 为 false 时不输出 ANSI 转义
+pub fn render(session: &Session, style: Style, width: usize, color: bool) -> Vec<Vec<String>> {
+    let rules = Config::load()
+        .reasoning_tags
+        .unwrap_or_else(channels::default_rules);
+    let paint = |codes: &str, text: &str| {
+        if color {
+            format!("{}{}{}", codes, text, RESET)
+        } else {
+            text.to_string()
+        }
+    };
+    let width = width.max(20);
+    let title = session.display_title();
+
+    // 每页从一条用户消息开始
+    let mut groups: Vec<Vec<&ChatEntry>> = Vec::new();
+    for entry in session.messages.iter().filter(|e| e.role() != "system") {
+        match groups.last_mut() {
+            Some(group) if style == Style::Slides && entry.role() != "user" => group.push(entry),
+            Some(group) if style == Style::Plain => group.push(entry),
+            _ => groups.push(vec![entry]),
+        }
+    }
+
+    let total = groups.len();
+    groups
+        .into_iter()
+        .enumerate()
+        .map(|(i, group)| {
+            let mut lines = Vec::new();
+            let heading = match style {
+                Style::Slides => format!("{} · {}/{}", title, i + 1, total),
+                Style::Plain => title.clone(),
+            };
+            lines.push(paint(BOLD, &heading));
+            lines.push(paint(DIM, &"─".repeat(width.min(heading.width().max(40)))));
+            if style == Style::Plain || i == 0 {
+                let mut info = Vec::new();
+                if let Some(model) = &session.model {
+                    info.push(model.clone());
+                }
+                info.push(timefmt::datetime(session.created_at));
+                lines.push(paint(DIM, &info.join(" · ")));
+                if let Some(summary) = session.summary.as_deref().filter(|s| !s.is_empty()) {
+                    lines.extend(wrap(summary, width).into_iter().map(|l| paint(DIM, &l)));
+                }
+            }
+            for entry in group {
+                lines.push(String::new());
+                lines.extend(render_entry(entry, &rules, width, &paint));
+            }
+            lines
+        })
+        .collect()
+}
+
+fn render_entry(
+    entry: &ChatEntry,
+    rules: &[TagRule],
+    width: usize,
+    paint: &dyn Fn(&str, &str) -> String,
+) -> Vec<String> {
+    let mut lines = Vec::new();
+    let (label, codes) = match entry.role() {
+        "user" => ("You".to_string(), format!("{}{}", BOLD, CYAN)),
+        "assistant" => (
+            match entry.meta.as_ref() {
+                Some(meta) => format!("AI ({})", meta.model),
+                None => "AI".to_string(),
+            },
+            format!("{}{}", BOLD, GREEN),
+        ),
+        role => (role.to_string(), BOLD.to_string()),
+    };
+    let time = timefmt::custom(entry.timestamp, "%H:%M");
+    lines.push(format!(
+        "{} {}",
+        paint(&codes, &label),
+        paint(
+            DIM,
+            if entry.timestamp > 0 {
+                time.as_str()
+            } else {
+                ""
+            }
+        )
+    ));
+
+    let rules = if entry.role() == "assistant" {
+        rules
+    } else {
+        &[]
+    };
+    let mut in_code = false;
+    for line in channels::display_lines(entry.content(), rules, Some(false)) {
+        if line.kind == LineKind::Header {
+            lines.push(format!("  {}", paint(DIM, &line.text)));
+            continue;
+        }
+        if line.text.trim_start().starts_with("```") {
+            in_code = !in_code;
+            lines.push(format!("  {}", paint(DIM, &line.text)));
+            continue;
+        }
+        // 代码块不折行，保持原样
+        if in_code {
+            lines.push(format!("  {}", paint(YELLOW, &line.text)));
+            continue;
+        }
+        for wrapped in wrap(&line.text, width.saturating_sub(2)) {
+            lines.push(format!("  {}", wrapped));
+        }
+    }
+    if let Some(reason) = entry.meta.as_ref().and_then(|m| m.stopped.as_deref()) {
+        lines.push(paint(DIM, &format!("  (stopped at the {})", reason)));
+    }
+    lines
+}
+
+/// 按显示宽度在空格处折行，超长的词（包括没有空格的中文）按字符断开
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let width = width.max(1);
+    let mut lines = Vec::new();
+    let mut current = String::new();
+    let mut used = 0;
+    for word in text.split_inclusive(' ') {
+        let word_width = word.trim_end().width();
+        if used + word_width > width && !current.is_empty() {
+            lines.push(current.trim_end().to_string());
+            current.clear();
+            used = 0;
+        }
+        for c in word.chars() {
+            let w = c.width().unwrap_or(0);
+            if used + w > width && !current.is_empty() {
+                lines.push(current.trim_end().to_string());
+                current.clear();
+                used = 0;
+            }
+            if used == 0 && c == ' ' {
+                continue;
+            }
+            current.push(c);
+            used += w;
+        }
+    }
+    lines.push(current.trim_end().to_string());
+    lines
+}
+
+/// 输出到终端时用 `$PAGER`（默认 `less -R`），分页器不可用时使用内置的逐页查看；
+/// 幻灯片模式下每页补足到终端高度，分页器中按空格正好翻一页
+pub fn display(
+    pages: Vec<Vec<String>>,
+    style: Style,
+    height: usize,
+    use_pager: bool,
+) -> anyhow::Result<()> {
+    let stdout = std::io::stdout();
+    if !use_pager || !stdout.is_terminal() {
+        let mut out = stdout.lock();
+        for page in &pages {
+            for line in page {
+                writeln!(out, "{}", line)?;
+            }
+            writeln!(out)?;
+        }
+        return Ok(());
+    }
+
+    let mut text = String::new();
+    for page in &pages {
+        let mut page = page.clone();
+        if style == Style::Slides && page.len() < height {
+            page.resize(height, String::new());
+        }
+        for line in page {
+            text.push_str(&line);
+            text.push('\n');
+        }
+    }
+    if pager(&text).is_ok() {
+        return Ok(());
+    }
+    builtin_viewer(&pages)
+}
+
+fn pager(text: &str) -> anyhow::Result<()> {
+    let command = std::env::var("PAGER")
+        .ok()
+        .filter(|p| !p.trim().is_empty())
+        .unwrap_or_else(|| "less -R".to_string());
+    let mut parts = command.split_whitespace();
+    let program = parts.next().unwrap_or("less");
+    let mut child = std::process::Command::new(program)
+        .args(parts)
+        .stdin(std::process::Stdio::piped())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // 用户提前退出分页器时写入会失败，不算错误
+        let _ = stdin.write_all(text.as_bytes());
+    }
+    child.wait()?;
+    Ok(())
+}
+
+/// 没有分页器时：一次显示一页，Enter 下一页，`b` 上一页，`q` 退出
+fn builtin_viewer(pages: &[Vec<String>]) -> anyhow::Result<()> {
+    let stdin = std::io::stdin();
+    let mut out = std::io::stdout();
+    let mut current = 0;
+    while current < pages.len() {
+        write!(out, "\x1b[2J\x1b[H")?;
+        for line in &pages[current] {
+            writeln!(out, "{}", line)?;
+        }
+        write!(
+            out,
+            "\n{}-- {}/{} · Enter: next | b: back | q: quit --{} ",
+            DIM,
+            current + 1,
+            pages.len(),
+            RESET
+        )?;
+        out.flush()?;
+        let mut input = String::new();
+        if stdin.lock().read_line(&mut input)? == 0 {
+            break;
+        }
+        match input.trim() {
+            "q" => break,
+            "b" => current = current.saturating_sub(1),
+            _ => current += 1,
+        }
+    }
+    Ok(())
+}
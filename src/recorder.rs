@@ -0,0 +1,242 @@
+use crate::config;
+use crate::timefmt;
+use once_cell::sync::Lazy;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// 录制 Ollama API 的请求和响应（含流式分块），用于向上游报告可复现的问题。
+/// 每个事件写为一行 JSON：request / response / chunk / end / error
+struct Recording {
+    path: PathBuf,
+    file: File,
+    next_id: u64,
+}
+
+static RECORDING: Lazy<Mutex<Option<Recording>>> = Lazy::new(|| Mutex::new(None));
+
+// 字段名包含这些词时值会被替换为 "<redacted>"
+const SENSITIVE_KEYS: [&str; 5] = ["authorization", "token", "password", "secret", "api_key"];
+
+pub fn recordings_dir() -> PathBuf {
+    let dir = config::config_dir().join("recordings");
+    let _ = std::fs::create_dir_all(&dir);
+    dir
+}
+
+/// 开始录制，返回录制文件路径；已在录制时返回当前文件
+pub fn start() -> anyhow::Result<PathBuf> {
+    let mut recording = RECORDING
+        .lock()
+        .map_err(|_| anyhow::anyhow!("recorder lock poisoned"))?;
+    if let Some(current) = recording.as_ref() {
+        return Ok(current.path.clone());
+    }
+    let path = recordings_dir().join(format!(
+        "ollama-{}.jsonl",
+        chrono::Local::now().format("%Y%m%d-%H%M%S")
+    ));
+    let file = File::create(&path)?;
+    *recording = Some(Recording {
+        path: path.clone(),
+        file,
+        next_id: 1,
+    });
+    Ok(path)
+}
+
+/// 停止录制，返回录制文件路径
+pub fn stop() -> Option<PathBuf> {
+    RECORDING.lock().ok()?.take().map(|r| r.path)
+}
+
+pub fn is_recording() -> bool {
+    RECORDING.lock().map(|r| r.is_some()).unwrap_or(false)
+}
+
+/// 记录一次请求，返回用于关联响应的编号；未录制时返回 None
+pub fn record_request(endpoint: &str, method: &str, path: &str, body: &[u8]) -> Option<u64> {
+    // 连接状态轮询每隔几秒一次，不记录
+    if path == "/api/version" {
+        return None;
+    }
+    let mut recording = RECORDING.lock().ok()?;
+    let recording = recording.as_mut()?;
+    let id = recording.next_id;
+    recording.next_id += 1;
+    let body = if body.is_empty() {
+        serde_json::Value::Null
+    } else {
+        sanitize(parse_payload(body))
+    };
+    write_event(
+        recording,
+        serde_json::json!({
+            "id": id,
+            "event": "request",
+            "time": timefmt::now_rfc3339(),
+            "endpoint": sanitize_url(endpoint),
+            "method": method,
+            "path": path,
+            "body": body,
+        }),
+    );
+    Some(id)
+}
+
+pub fn record_response(id: u64, status: u16) {
+    record(serde_json::json!({
+        "id": id,
+        "event": "response",
+        "time": timefmt::now_rfc3339(),
+        "status": status,
+    }));
+}
+
+/// 请求没有得到响应（连接被拒绝、DNS 失败等）或读取响应体时出错，错误信息中的 URL 同样去掉凭据
+pub fn record_error(id: u64, err: &dyn std::fmt::Display) {
+    record(serde_json::json!({
+        "id": id,
+        "event": "error",
+        "time": timefmt::now_rfc3339(),
+        "error": err
+            .to_string()
+            .split(' ')
+            .map(sanitize_url)
+            .collect::<Vec<_>>()
+            .join(" "),
+    }));
+}
+
+/// 包装响应体，读取时按行记录每个分块
+pub fn wrap_body(id: u64, body: Box<dyn Read + Send>) -> Box<dyn Read + Send> {
+    Box::new(RecordingReader {
+        id,
+        inner: body,
+        pending: Vec::new(),
+        finished: false,
+    })
+}
+
+fn record(event: serde_json::Value) {
+    if let Ok(mut recording) = RECORDING.lock() {
+        if let Some(recording) = recording.as_mut() {
+            write_event(recording, event);
+        }
+    }
+}
+
+fn write_event(recording: &mut Recording, event: serde_json::Value) {
+    let _ = writeln!(recording.file, "{}", event);
+    let _ = recording.file.flush();
+}
+
+fn parse_payload(bytes: &[u8]) -> serde_json::Value {
+    serde_json::from_slice(bytes)
+        .unwrap_or_else(|_| serde_json::Value::String(String::from_utf8_lossy(bytes).into_owned()))
+}
+
+/// 去掉敏感字段的值，其余内容原样保留以便复现
+fn sanitize(value: serde_json::Value) -> serde_json::Value {
+    match value {
+        serde_json::Value::Object(map) => map
+            .into_iter()
+            .map(|(key, value)| {
+                let lower = key.to_ascii_lowercase();
+                if SENSITIVE_KEYS.iter().any(|k| lower.contains(k)) {
+                    (key, serde_json::Value::String("<redacted>".to_string()))
+                } else {
+                    (key, sanitize(value))
+                }
+            })
+            .collect(),
+        serde_json::Value::Array(items) => items.into_iter().map(sanitize).collect(),
+        other => other,
+    }
+}
+
+/// 去掉 URL 中的用户名和密码，并把家目录替换为 ~
+fn sanitize_url(url: &str) -> String {
+    let mut url = url.to_string();
+    if let Some(scheme_end) = url.find("://") {
+        let authority_start = scheme_end + 3;
+        let authority_end = url[authority_start..]
+            .find('/')
+            .map(|i| i + authority_start)
+            .unwrap_or(url.len());
+        if let Some(at) = url[authority_start..authority_end].rfind('@') {
+            url.replace_range(authority_start..authority_start + at + 1, "");
+        }
+    }
+    if let Some(home) = dirs::home_dir() {
+        let home = home.to_string_lossy().into_owned();
+        if !home.is_empty() {
+            url = url.replace(&home, "~");
+        }
+    }
+    url
+}
+
+struct RecordingReader {
+    id: u64,
+    inner: Box<dyn Read + Send>,
+    pending: Vec<u8>,
+    finished: bool,
+}
+
+impl RecordingReader {
+    fn flush_line(&mut self, line: &[u8]) {
+        if line.iter().all(u8::is_ascii_whitespace) {
+            return;
+        }
+        record(serde_json::json!({
+            "id": self.id,
+            "event": "chunk",
+            "data": sanitize(parse_payload(line)),
+        }));
+    }
+
+    fn finish(&mut self) {
+        if self.finished {
+            return;
+        }
+        self.finished = true;
+        let rest = std::mem::take(&mut self.pending);
+        self.flush_line(&rest);
+        record(serde_json::json!({
+            "id": self.id,
+            "event": "end",
+            "time": timefmt::now_rfc3339(),
+        }));
+    }
+}
+
+impl Read for RecordingReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = match self.inner.read(buf) {
+            Ok(n) => n,
+            Err(e) => {
+                record_error(self.id, &e);
+                return Err(e);
+            }
+        };
+        if n == 0 {
+            self.finish();
+            return Ok(0);
+        }
+        self.pending.extend_from_slice(&buf[..n]);
+        while let Some(pos) = self.pending.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = self.pending.drain(..=pos).collect();
+            self.flush_line(&line);
+        }
+        Ok(n)
+    }
+}
+
+impl Drop for RecordingReader {
+    // 调用方提前停止读取（如流式结束标记后 break）时也写出剩余内容
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
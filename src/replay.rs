@@ -0,0 +1,208 @@
+use crate::session::ChatEntry;
+use std::io::Write;
+use std::path::Path;
+use std::time::Instant;
+
+/// 两条消息之间最长的停顿（秒），超出的部分压缩掉
+const MAX_PAUSE: f64 = 2.0;
+const MIN_PAUSE: f64 = 0.4;
+/// 回复逐字显示的最短、最长时间（秒）
+const MIN_TYPING: f64 = 0.5;
+const MAX_TYPING: f64 = 8.0;
+/// 没有统计信息时按这个速度（字符/秒）显示
+const FALLBACK_CHARS_PER_SECOND: f64 = 80.0;
+/// 导出的 asciicast 中每秒的帧数
+const CAST_FPS: f64 = 20.0;
+
+/// 每条消息在回放中出现的时间和逐字显示的时长（秒，一倍速）
+#[derive(Debug, Clone, Copy)]
+struct Step {
+    start: f64,
+    duration: f64,
+}
+
+/// 按消息的发送时间和生成耗时排出的回放时间线
+#[derive(Debug, Clone)]
+pub struct Timeline {
+    entries: Vec<ChatEntry>,
+    steps: Vec<Step>,
+}
+
+impl Timeline {
+    pub fn new(entries: Vec<ChatEntry>) -> Self {
+        let mut steps: Vec<Step> = Vec::with_capacity(entries.len());
+        let mut at = 0.0;
+        for (i, entry) in entries.iter().enumerate() {
+            let duration = typing_duration(entry);
+            if i > 0 {
+                let previous = &entries[i - 1];
+                let gap = if entry.timestamp > 0 && previous.timestamp > 0 {
+                    (entry.timestamp - previous.timestamp) as f64 - steps[i - 1].duration
+                } else {
+                    MIN_PAUSE
+                };
+                at += gap.clamp(MIN_PAUSE, MAX_PAUSE);
+            }
+            steps.push(Step {
+                start: at,
+                duration,
+            });
+            at += duration;
+        }
+        Self { entries, steps }
+    }
+
+    /// 总时长（秒，一倍速）
+    pub fn duration(&self) -> f64 {
+        self.steps.last().map_or(0.0, |s| s.start + s.duration)
+    }
+
+    /// `position` 秒时可见的消息，正在显示的回复只保留已“生成”的部分
+    pub fn frame(&self, position: f64) -> Vec<ChatEntry> {
+        let mut visible = Vec::new();
+        for (entry, step) in self.entries.iter().zip(&self.steps) {
+            if step.start > position {
+                break;
+            }
+            let mut entry = entry.clone();
+            if step.duration > 0.0 && position < step.start + step.duration {
+                let shown = visible_chars(&entry, (position - step.start) / step.duration);
+                entry.message.content = entry.content().chars().take(shown).collect();
+            }
+            visible.push(entry);
+        }
+        visible
+    }
+
+    /// 导出为 asciicast v2（asciinema 的录制格式），可用 `asciinema play` 播放或转成 GIF
+    pub fn write_cast(
+        &self,
+        path: &Path,
+        width: u16,
+        height: u16,
+        title: &str,
+    ) -> anyhow::Result<()> {
+        let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        let header = serde_json::json!({
+            "version": 2,
+            "width": width,
+            "height": height,
+            "timestamp": chrono::Local::now().timestamp(),
+            "title": title,
+        });
+        writeln!(file, "{}", header)?;
+
+        let mut event = |time: f64, text: &str| -> std::io::Result<()> {
+            if text.is_empty() {
+                return Ok(());
+            }
+            let text = text.replace('\n', "\r\n");
+            writeln!(file, "{}", serde_json::json!([time, "o", text]))
+        };
+        for (entry, step) in self.entries.iter().zip(&self.steps) {
+            let color = if entry.role() == "user" { 36 } else { 32 };
+            let label = match entry.role() {
+                "user" => "You",
+                "assistant" => "AI",
+                role => role,
+            };
+            event(step.start, &format!("\x1b[1;{}m{}:\x1b[0m ", color, label))?;
+
+            let chars: Vec<char> = entry.content().chars().collect();
+            let frames = (step.duration * CAST_FPS).ceil().max(1.0) as usize;
+            let mut shown = 0;
+            for frame in 1..=frames {
+                let fraction = frame as f64 / frames as f64;
+                let target = visible_chars(entry, fraction).min(chars.len());
+                let chunk: String = chars[shown..target].iter().collect();
+                event(step.start + step.duration * fraction, &chunk)?;
+                shown = target;
+            }
+            event(step.start + step.duration, "\n\n")?;
+        }
+        file.flush()?;
+        Ok(())
+    }
+}
+
+/// 回复按生成耗时逐字显示，用户消息直接出现
+fn typing_duration(entry: &ChatEntry) -> f64 {
+    if entry.role() != "assistant" || entry.content().is_empty() {
+        return 0.0;
+    }
+    let recorded = entry
+        .meta
+        .as_ref()
+        .and_then(|m| m.stats.eval_duration.or(m.stats.total_duration))
+        .map(|ns| ns as f64 / 1e9);
+    let estimated = entry.content().chars().count() as f64 / FALLBACK_CHARS_PER_SECOND;
+    recorded.unwrap_or(estimated).clamp(MIN_TYPING, MAX_TYPING)
+}
+
+fn visible_chars(entry: &ChatEntry, fraction: f64) -> usize {
+    let total = entry.content().chars().count();
+    ((total as f64) * fraction.clamp(0.0, 1.0)).round() as usize
+}
+
+/// TUI 中正在进行的回放，可暂停和调整速度
+#[derive(Debug, Clone)]
+pub struct Player {
+    pub timeline: Timeline,
+    /// 回放前的对话，结束后恢复
+    pub original: Vec<ChatEntry>,
+    pub speed: f64,
+    /// 上次暂停或调速时的位置（秒，一倍速）
+    offset: f64,
+    resumed: Option<Instant>,
+}
+
+impl Player {
+    pub fn new(original: Vec<ChatEntry>, speed: f64) -> Self {
+        Self {
+            timeline: Timeline::new(original.clone()),
+            original,
+            speed,
+            offset: 0.0,
+            resumed: Some(Instant::now()),
+        }
+    }
+
+    pub fn position(&self) -> f64 {
+        let running = self
+            .resumed
+            .map_or(0.0, |at| at.elapsed().as_secs_f64() * self.speed);
+        (self.offset + running).min(self.timeline.duration())
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.resumed.is_none()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.position() >= self.timeline.duration()
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.offset = self.position();
+        self.resumed = match self.resumed {
+            Some(_) => None,
+            None => Some(Instant::now()),
+        };
+    }
+
+    pub fn set_speed(&mut self, speed: f64) {
+        self.offset = self.position();
+        if self.resumed.is_some() {
+            self.resumed = Some(Instant::now());
+        }
+        self.speed = speed.clamp(0.25, 16.0);
+    }
+
+    /// 前后跳转（秒，一倍速）
+    pub fn seek(&mut self, delta: f64) {
+        self.offset = (self.position() + delta).clamp(0.0, self.timeline.duration());
+        if self.resumed.is_some() {
+            self.resumed = Some(Instant::now());
+        }
+    }
+}
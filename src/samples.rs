@@ -0,0 +1,53 @@
+/// 模型详情中可直接试用的示例提示词数量上限
+pub const MAX_SAMPLES: usize = 5;
+
+/// 按模型能力（/api/show 的 `capabilities`）和名称给出的通用示例，用于安装后快速检查效果
+pub fn generic(model: &str, capabilities: &[String]) -> Vec<String> {
+    let has = |capability: &str| capabilities.iter().any(|c| c == capability);
+    let name = model.to_lowercase();
+    let mut samples: Vec<&str> = Vec::new();
+
+    if has("embedding") && !has("completion") {
+        return Vec::new();
+    }
+    if has("insert")
+        || ["code", "coder", "starcoder", "codestral"]
+            .iter()
+            .any(|k| name.contains(k))
+    {
+        samples.push("Write a function that checks whether a string is a palindrome, ignoring case and punctuation, with a few tests.");
+        samples.push("Explain what this does and suggest a fix: for (i = 0; i <= arr.length; i++) sum += arr[i];");
+    }
+    if has("thinking") {
+        samples.push("A bat and a ball cost $1.10 in total. The bat costs $1.00 more than the ball. How much does the ball cost? Explain your reasoning.");
+    }
+    if has("tools") {
+        samples.push("I need the weather in Paris and Tokyo tomorrow. Which tools would you call, and with what arguments?");
+    }
+    if has("vision") {
+        samples.push("What kinds of questions can you answer about an image? Give three examples.");
+    }
+    samples.extend([
+        "用三句话解释什么是大语言模型。",
+        "Summarize the plot of Romeo and Juliet in two sentences.",
+        "Write a haiku about a terminal window at night.",
+    ]);
+
+    samples
+        .into_iter()
+        .take(MAX_SAMPLES)
+        .map(str::to_string)
+        .collect()
+}
+
+/// 合并模型卡片中的示例和通用示例，去重后保留前几条
+pub fn merge(card: Vec<String>, generic: Vec<String>) -> Vec<String> {
+    let mut samples: Vec<String> = Vec::new();
+    for sample in card.into_iter().chain(generic) {
+        if !sample.trim().is_empty() && !samples.contains(&sample) {
+            samples.push(sample);
+        }
+    }
+    samples.truncate(MAX_SAMPLES);
+    samples
+}
@@ -0,0 +1,16 @@
+use crate::config;
+use std::path::PathBuf;
+
+/// 草稿本：不属于任何会话的笔记，保存在 ~/.config/ollama-cli/scratchpad.md
+fn path() -> PathBuf {
+    config::config_dir().join("scratchpad.md")
+}
+
+pub fn load() -> String {
+    std::fs::read_to_string(path()).unwrap_or_default()
+}
+
+pub fn save(text: &str) -> anyhow::Result<()> {
+    std::fs::write(path(), text)?;
+    Ok(())
+}
@@ -1,4 +1,8 @@
+use crate::ollama::ModelOptions;
+use anyhow::Context;
+use reqwest::blocking::Client;
 use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OnlineModel {
@@ -7,25 +11,156 @@ pub struct OnlineModel {
     pub url: String,
 }
 
-pub struct ModelSearch {
-    client: reqwest::blocking::Client,
+#[derive(Debug, Clone, Deserialize)]
+struct ManifestLayer {
+    #[serde(default, rename = "mediaType")]
+    media_type: String,
+    #[serde(default)]
+    digest: String,
+    size: u64,
 }
 
-impl ModelSearch {
-    pub fn new() -> Self {
-        Self {
-            client: reqwest::blocking::Client::builder()
-                .user_agent("ollama-cli/0.1.0")
-                .build()
-                .unwrap_or_default(),
+/// 模型发布者在 registry 中附带的推荐参数、模板和系统提示词
+#[derive(Debug, Clone, Default)]
+pub struct Recommended {
+    pub options: ModelOptions,
+    pub template: Option<String>,
+    pub system: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct Manifest {
+    config: Option<ManifestLayer>,
+    #[serde(default)]
+    layers: Vec<ManifestLayer>,
+}
+
+/// 在线模型目录的数据来源
+pub trait SearchBackend: Send + Sync {
+    /// 搜索页状态栏中显示的名称，便于排查失效的来源
+    fn name(&self) -> &'static str;
+    /// 空查询返回热门模型，结果数量由调用方截断
+    fn search(&self, client: &Client, query: &str) -> anyhow::Result<Vec<OnlineModel>>;
+    /// 整个模型库
+    fn browse(&self, client: &Client) -> anyhow::Result<Vec<OnlineModel>>;
+}
+
+/// ollama.com 的结构化搜索接口
+struct JsonApi;
+
+/// 抓取 ollama.com/library 页面，结构化接口不可用时使用
+struct Scraper;
+
+static JSON_API: JsonApi = JsonApi;
+static SCRAPER: Scraper = Scraper;
+
+/// 运行时探测到的来源，首次搜索时确定
+static ACTIVE: Mutex<Option<&'static dyn SearchBackend>> = Mutex::new(None);
+
+const SEARCH_API_URL: &str = "https://ollama.com/api/search";
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum SearchReply {
+    List(Vec<ApiModel>),
+    Wrapped {
+        #[serde(alias = "results")]
+        models: Vec<ApiModel>,
+    },
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiModel {
+    #[serde(alias = "model")]
+    name: String,
+    #[serde(default)]
+    description: Option<String>,
+}
+
+impl JsonApi {
+    /// 接口返回可解析的 JSON 时可用；网络错误返回 None，下次搜索时重新探测
+    fn probe(&self, client: &Client) -> Option<bool> {
+        let response = client
+            .get(SEARCH_API_URL)
+            .query(&[("q", "llama")])
+            .header("Accept", "application/json")
+            .send()
+            .ok()?;
+        let is_json = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.contains("json"));
+        if !response.status().is_success() || !is_json {
+            return Some(false);
+        }
+        Some(response.json::<SearchReply>().is_ok())
+    }
+}
+
+impl SearchBackend for JsonApi {
+    fn name(&self) -> &'static str {
+        "ollama.com API"
+    }
+
+    fn search(&self, client: &Client, query: &str) -> anyhow::Result<Vec<OnlineModel>> {
+        let mut params = vec![("q", query)];
+        if query.is_empty() {
+            params.push(("sort", "popular"));
         }
+        self.fetch(client, &params)
+    }
+
+    fn browse(&self, client: &Client) -> anyhow::Result<Vec<OnlineModel>> {
+        self.fetch(client, &[("q", "")])
+    }
+}
+
+impl JsonApi {
+    fn fetch(&self, client: &Client, params: &[(&str, &str)]) -> anyhow::Result<Vec<OnlineModel>> {
+        let reply: SearchReply = client
+            .get(SEARCH_API_URL)
+            .query(params)
+            .header("Accept", "application/json")
+            .send()?
+            .error_for_status()?
+            .json()?;
+        let models = match reply {
+            SearchReply::List(models) | SearchReply::Wrapped { models } => models,
+        };
+        Ok(models
+            .into_iter()
+            .map(|m| OnlineModel {
+                url: format!("https://ollama.com/library/{}", m.name),
+                name: m.name,
+                description: m.description.filter(|d| !d.is_empty()),
+            })
+            .collect())
+    }
+}
+
+impl SearchBackend for Scraper {
+    fn name(&self) -> &'static str {
+        "ollama.com web page"
     }
 
-    pub fn search_online(&self, query: &str) -> anyhow::Result<Vec<OnlineModel>> {
-        let url = "https://ollama.com/library";
-        let response = self.client.get(url).send()?;
+    fn search(&self, client: &Client, query: &str) -> anyhow::Result<Vec<OnlineModel>> {
+        let url = if query.is_empty() {
+            "https://ollama.com/library?sort=popular"
+        } else {
+            "https://ollama.com/library"
+        };
+        self.scrape(client, url, query)
+    }
+
+    fn browse(&self, client: &Client) -> anyhow::Result<Vec<OnlineModel>> {
+        self.scrape(client, "https://ollama.com/library", "")
+    }
+}
 
-        let body = response.text()?;
+impl Scraper {
+    fn scrape(&self, client: &Client, url: &str, query: &str) -> anyhow::Result<Vec<OnlineModel>> {
+        let body = client.get(url).send()?.error_for_status()?.text()?;
 
         let mut models = Vec::new();
         let pattern = format!("{}/library/", "https://ollama.com");
@@ -48,39 +183,212 @@ impl ModelSearch {
         let mut unique: std::collections::HashSet<String> = std::collections::HashSet::new();
         models.retain(|m| unique.insert(m.name.clone()));
 
-        models.truncate(50);
+        Ok(models)
+    }
+}
+
+pub struct ModelSearch {
+    client: Client,
+}
+
+impl ModelSearch {
+    pub fn new() -> Self {
+        Self {
+            client: Client::builder()
+                .user_agent("ollama-cli/0.1.0")
+                .build()
+                .unwrap_or_default(),
+        }
+    }
+
+    /// 当前使用的来源名称，尚未探测时为 None
+    pub fn active_backend() -> Option<&'static str> {
+        ACTIVE
+            .lock()
+            .ok()
+            .and_then(|active| active.map(|b| b.name()))
+    }
+
+    fn backend(&self) -> &'static dyn SearchBackend {
+        if let Some(backend) = ACTIVE.lock().ok().and_then(|active| *active) {
+            return backend;
+        }
+        let detected = JSON_API.probe(&self.client);
+        let backend: &'static dyn SearchBackend = if detected == Some(true) {
+            &JSON_API
+        } else {
+            &SCRAPER
+        };
+        if detected.is_some() {
+            set_active(backend);
+        }
+        backend
+    }
 
+    /// 搜索在线模型，空查询返回热门模型，最多返回 `limit` 个
+    pub fn search(&self, query: &str, limit: usize) -> anyhow::Result<Vec<OnlineModel>> {
+        let mut models = self.with_fallback(|backend, client| backend.search(client, query))?;
+        models.truncate(limit);
         Ok(models)
     }
 
-    pub fn get_popular_models(&self) -> anyhow::Result<Vec<OnlineModel>> {
-        let url = "https://ollama.com/library?sort=popular";
-        let response = self.client.get(url).send()?;
+    /// 整个模型库，按名称排序
+    pub fn browse(&self) -> anyhow::Result<Vec<OnlineModel>> {
+        let mut models = self.with_fallback(|backend, client| backend.browse(client))?;
+        models.sort_by_key(|m| m.name.to_lowercase());
+        Ok(models)
+    }
 
-        let body = response.text()?;
+    /// 结构化接口出错时这一次改用抓取网页；只有接口返回 404（已下线）时才一直使用网页，
+    /// 超时等临时错误不影响之后的搜索
+    fn with_fallback<F>(&self, run: F) -> anyhow::Result<Vec<OnlineModel>>
+    where
+        F: Fn(&dyn SearchBackend, &Client) -> anyhow::Result<Vec<OnlineModel>>,
+    {
+        let backend = self.backend();
+        match run(backend, &self.client) {
+            Err(e) if backend.name() != SCRAPER.name() => {
+                if is_not_found(&e) {
+                    set_active(&SCRAPER);
+                }
+                run(&SCRAPER, &self.client)
+                    .with_context(|| format!("{} failed ({})", backend.name(), e))
+            }
+            result => result,
+        }
+    }
 
-        let mut models = Vec::new();
-        let pattern = format!("{}/library/", "https://ollama.com");
+    /// 模型在 ollama.com 上的所有标签（如 `llama3.2:3b`），从标签页面抓取
+    pub fn tags(&self, model: &str) -> anyhow::Result<Vec<String>> {
+        let name = model.split(':').next().unwrap_or(model);
+        let url = format!("https://ollama.com/library/{}/tags", name);
+        let body = self.client.get(&url).send()?.error_for_status()?.text()?;
 
-        for line in body.lines() {
-            if line.contains("/library/") && line.contains("<a ") {
-                if let Some(name) = extract_model_name(line, &pattern) {
-                    let model_url = format!("{}/library/{}", "https://ollama.com", name);
-                    models.push(OnlineModel {
-                        name: name.clone(),
-                        description: None,
-                        url: model_url,
-                    });
+        let prefix = format!("/library/{}:", name);
+        let mut tags: Vec<String> = Vec::new();
+        for part in body.split("href=\"").skip(1) {
+            let href = part.split('"').next().unwrap_or_default();
+            let Some(tag) = href.strip_prefix(&prefix) else {
+                continue;
+            };
+            let full = format!("{}:{}", name, tag);
+            if !tag.is_empty() && !tag.contains(['/', '?']) && !tags.contains(&full) {
+                tags.push(full);
+            }
+        }
+        if tags.is_empty() {
+            anyhow::bail!("no tags found for {}", name);
+        }
+        Ok(tags)
+    }
+
+    /// 模型卡片（ollama.com/library 页面）中 `ollama run 模型 "..."` 形式的示例提示词
+    pub fn card_examples(&self, model: &str) -> anyhow::Result<Vec<String>> {
+        let name = model.split(':').next().unwrap_or(model);
+        let url = format!("https://ollama.com/library/{}", name);
+        let body = self.client.get(&url).send()?.error_for_status()?.text()?;
+        let body = unescape_html(&body);
+
+        let mut examples = Vec::new();
+        for part in body.split("ollama run ").skip(1) {
+            let line = part.split(['\n', '<']).next().unwrap_or_default();
+            let Some((_, rest)) = line.split_once(char::is_whitespace) else {
+                continue;
+            };
+            let Some(quoted) = rest.trim_start().strip_prefix('"') else {
+                continue;
+            };
+            if let Some((prompt, _)) = quoted.split_once('"') {
+                let prompt = prompt.trim().to_string();
+                if !prompt.is_empty() && !examples.contains(&prompt) {
+                    examples.push(prompt);
                 }
             }
         }
+        Ok(examples)
+    }
 
-        let mut unique: std::collections::HashSet<String> = std::collections::HashSet::new();
-        models.retain(|m| unique.insert(m.name.clone()));
+    /// 通过 registry manifest 获取模型的所有层（含 config）的 digest 和大小（字节）
+    pub fn manifest_blobs(&self, model: &str) -> anyhow::Result<Vec<(String, u64)>> {
+        let (_, manifest) = self.manifest(model)?;
+        Ok(manifest
+            .config
+            .into_iter()
+            .chain(manifest.layers)
+            .map(|l| (l.digest, l.size))
+            .collect())
+    }
+
+    /// 从 manifest 的 params、template 和 system 层读取推荐设置
+    pub fn recommended(&self, model: &str) -> anyhow::Result<Recommended> {
+        let (name, manifest) = self.manifest(model)?;
+        let mut recommended = Recommended::default();
+        for layer in &manifest.layers {
+            let kind = layer.media_type.rsplit('.').next().unwrap_or_default();
+            if !matches!(kind, "params" | "template" | "system") {
+                continue;
+            }
+            let url = format!(
+                "https://registry.ollama.ai/v2/{}/blobs/{}",
+                name, layer.digest
+            );
+            let body = self.client.get(&url).send()?.error_for_status()?.text()?;
+            match kind {
+                "params" => {
+                    recommended.options = serde_json::from_str(&body)
+                        .with_context(|| format!("invalid params layer for {}", model))?;
+                }
+                "template" => recommended.template = Some(body),
+                _ => recommended.system = Some(body),
+            }
+        }
+        Ok(recommended)
+    }
 
-        models.truncate(30);
+    /// 返回 registry 中的仓库名（如 `library/llama3.2`）和 manifest
+    fn manifest(&self, model: &str) -> anyhow::Result<(String, Manifest)> {
+        let (name, tag) = model.split_once(':').unwrap_or((model, "latest"));
+        let name = if name.contains('/') {
+            name.to_string()
+        } else {
+            format!("library/{}", name)
+        };
+        let url = format!("https://registry.ollama.ai/v2/{}/manifests/{}", name, tag);
+        let response = self
+            .client
+            .get(&url)
+            .header(
+                "Accept",
+                "application/vnd.docker.distribution.manifest.v2+json",
+            )
+            .send()?
+            .error_for_status()?;
 
-        Ok(models)
+        Ok((name, response.json()?))
+    }
+}
+
+fn unescape_html(text: &str) -> String {
+    text.replace("&#34;", "\"")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+fn is_not_found(err: &anyhow::Error) -> bool {
+    err.chain().any(|cause| {
+        cause
+            .downcast_ref::<reqwest::Error>()
+            .and_then(|e| e.status())
+            == Some(reqwest::StatusCode::NOT_FOUND)
+    })
+}
+
+fn set_active(backend: &'static dyn SearchBackend) {
+    if let Ok(mut active) = ACTIVE.lock() {
+        *active = Some(backend);
     }
 }
 
@@ -0,0 +1,104 @@
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader};
+use std::process::{Child, Command, Stdio};
+use std::sync::{Arc, Mutex};
+
+const MAX_LOG_LINES: usize = 500;
+
+/// 由 TUI 启动并管理的 `ollama serve` 子进程，输出保存在日志面板中
+#[derive(Clone, Default)]
+pub struct ServerManager {
+    child: Arc<Mutex<Option<Child>>>,
+    log: Arc<Mutex<VecDeque<String>>>,
+}
+
+impl ServerManager {
+    pub fn start(&self) -> anyhow::Result<()> {
+        if self.is_running() {
+            anyhow::bail!("`ollama serve` is already running");
+        }
+
+        let mut child = Command::new("ollama")
+            .arg("serve")
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| anyhow::anyhow!("failed to run `ollama serve`: {}", e))?;
+
+        // ollama serve 的日志主要写在 stderr，stdout 一并收集
+        if let Some(stderr) = child.stderr.take() {
+            self.collect(stderr);
+        }
+        if let Some(stdout) = child.stdout.take() {
+            self.collect(stdout);
+        }
+
+        self.push_log(format!("$ ollama serve (pid {})", child.id()));
+        if let Ok(mut current) = self.child.lock() {
+            *current = Some(child);
+        }
+        Ok(())
+    }
+
+    /// 结束子进程；不是由本程序启动的服务不受影响
+    pub fn stop(&self) -> bool {
+        let Ok(mut current) = self.child.lock() else {
+            return false;
+        };
+        match current.take() {
+            Some(mut child) => {
+                let _ = child.kill();
+                let _ = child.wait();
+                self.push_log("server stopped".to_string());
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        let Ok(mut current) = self.child.lock() else {
+            return false;
+        };
+        let exited = match current.as_mut() {
+            Some(child) => match child.try_wait() {
+                Ok(Some(status)) => Some(status),
+                Ok(None) => return true,
+                Err(_) => None,
+            },
+            None => return false,
+        };
+        *current = None;
+        if let Some(status) = exited {
+            self.push_log(format!("server exited ({})", status));
+        }
+        false
+    }
+
+    pub fn log_lines(&self) -> Vec<String> {
+        self.log
+            .lock()
+            .map(|log| log.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    fn collect<R: std::io::Read + Send + 'static>(&self, output: R) {
+        let manager = self.clone();
+        std::thread::spawn(move || {
+            for line in BufReader::new(output).lines() {
+                let Ok(line) = line else { break };
+                manager.push_log(line);
+            }
+        });
+    }
+
+    fn push_log(&self, line: String) {
+        if let Ok(mut log) = self.log.lock() {
+            if log.len() >= MAX_LOG_LINES {
+                log.pop_front();
+            }
+            log.push_back(line);
+        }
+    }
+}
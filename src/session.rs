@@ -0,0 +1,519 @@
+use crate::config;
+use crate::lang::{self, Language};
+use crate::ollama::{ChatMessage, GenerationStats, ModelOptions};
+use crate::timefmt;
+use crate::variables::Variables;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// 本进程中已分配的会话 ID，还没保存的会话不会出现在磁盘上
+static ISSUED_IDS: Lazy<Mutex<HashSet<String>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+
+/// 对话中的一条消息，附带发送时间和（AI 回复的）生成信息。
+/// 旧版本保存的会话只有 role/content，缺省字段按默认值读取
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatEntry {
+    #[serde(flatten)]
+    pub message: ChatMessage,
+    #[serde(default)]
+    pub timestamp: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub meta: Option<GenerationMeta>,
+    /// 回复因包含此禁用短语被中止
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub banned_phrase: Option<String>,
+    /// AI 回复结束时检测到的语言
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<Language>,
+}
+
+impl ChatEntry {
+    pub fn new(role: &str, content: String) -> Self {
+        Self {
+            message: ChatMessage {
+                role: role.to_string(),
+                content,
+            },
+            timestamp: chrono::Local::now().timestamp(),
+            meta: None,
+            banned_phrase: None,
+            language: None,
+        }
+    }
+
+    /// 检测 AI 回复的语言；回复结束或读取旧会话时调用，不在每次绘制时检测
+    pub fn detect_language(&mut self) {
+        if self.role() == "assistant" {
+            self.language = lang::detect(self.content());
+        }
+    }
+
+    pub fn role(&self) -> &str {
+        &self.message.role
+    }
+
+    pub fn content(&self) -> &str {
+        &self.message.content
+    }
+}
+
+/// 生成一条回复时使用的模型、参数和服务端返回的统计
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GenerationMeta {
+    pub model: String,
+    /// `chat`、`generate` 或 `generate raw`
+    pub mode: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub options: Option<ModelOptions>,
+    #[serde(default)]
+    pub stats: GenerationStats,
+    /// 超出时间或长度预算而提前停止时的原因
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stopped: Option<String>,
+}
+
+/// 去掉元数据，得到发送给 API 的消息列表
+pub fn to_messages(entries: &[ChatEntry]) -> Vec<ChatMessage> {
+    entries.iter().map(|e| e.message.clone()).collect()
+}
+
+/// 对话树中的一条消息，`parent` 为它前一条消息在树中的下标
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MessageNode {
+    #[serde(flatten)]
+    pub entry: ChatEntry,
+    #[serde(default)]
+    pub parent: Option<usize>,
+}
+
+/// 分支选择器中显示的一个分支
+#[derive(Debug, Clone)]
+pub struct BranchInfo {
+    /// 分支最后一条消息的下标
+    pub leaf: usize,
+    pub len: usize,
+    /// 与当前分支共有的消息数
+    pub shared: usize,
+    pub preview: String,
+    pub updated_at: i64,
+    pub current: bool,
+}
+
+/// 树状对话：可以从任意消息分叉，`head` 指向当前分支的最后一条消息
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(from = "StoredTree")]
+pub struct ConversationTree {
+    nodes: Vec<MessageNode>,
+    head: Option<usize>,
+}
+
+/// 文件中读出的对话树，经 `from_nodes` 检查后才使用：手动编辑或损坏的父节点下标会导致越界或死循环
+#[derive(Deserialize)]
+struct StoredTree {
+    #[serde(default)]
+    nodes: Vec<MessageNode>,
+    #[serde(default)]
+    head: Option<usize>,
+}
+
+impl From<StoredTree> for ConversationTree {
+    fn from(stored: StoredTree) -> Self {
+        Self::from_nodes(stored.nodes, stored.head)
+    }
+}
+
+impl ConversationTree {
+    pub fn from_messages(messages: &[ChatEntry]) -> Self {
+        let mut tree = Self::default();
+        tree.sync(messages);
+        tree
+    }
+
+    /// 由外部导入的节点构建，父节点必须排在子节点之前
+    pub fn from_nodes(nodes: Vec<MessageNode>, head: Option<usize>) -> Self {
+        let valid = nodes
+            .iter()
+            .enumerate()
+            .all(|(i, node)| node.parent.is_none_or(|p| p < i));
+        if !valid {
+            return Self::default();
+        }
+        let head = head
+            .filter(|&h| h < nodes.len())
+            .or(nodes.len().checked_sub(1));
+        Self { nodes, head }
+    }
+
+    pub fn nodes(&self) -> &[MessageNode] {
+        &self.nodes
+    }
+
+    pub fn head(&self) -> Option<usize> {
+        self.head
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// 为还没有语言信息的回复（旧版本保存的会话）检测语言
+    pub fn detect_languages(&mut self) {
+        for node in &mut self.nodes {
+            if node.entry.language.is_none() {
+                node.entry.detect_language();
+            }
+        }
+    }
+
+    /// 从根到 `leaf` 的节点下标
+    fn path(&self, leaf: Option<usize>) -> Vec<usize> {
+        let mut path = Vec::new();
+        let mut current = leaf;
+        while let Some(index) = current {
+            path.push(index);
+            current = self.nodes[index].parent;
+        }
+        path.reverse();
+        path
+    }
+
+    /// 当前分支上的消息
+    pub fn messages(&self) -> Vec<ChatEntry> {
+        self.path(self.head)
+            .into_iter()
+            .map(|i| self.nodes[i].entry.clone())
+            .collect()
+    }
+
+    /// 把界面上的消息列表记录为当前分支：相同的前缀沿用已有节点，其余作为新节点追加
+    pub fn sync(&mut self, messages: &[ChatEntry]) {
+        let path = self.path(self.head);
+        let mut parent = None;
+        let mut shared = 0;
+        for (&index, entry) in path.iter().zip(messages) {
+            let node = &mut self.nodes[index];
+            if node.entry.role() != entry.role()
+                || node.entry.content() != entry.content()
+                || node.entry.timestamp != entry.timestamp
+            {
+                break;
+            }
+            // 生成统计等信息在回复结束后才写入
+            node.entry = entry.clone();
+            parent = Some(index);
+            shared += 1;
+        }
+        for entry in &messages[shared..] {
+            self.nodes.push(MessageNode {
+                entry: entry.clone(),
+                parent,
+            });
+            parent = Some(self.nodes.len() - 1);
+        }
+        self.head = parent;
+    }
+
+    /// 从当前分支的第 `index` 条消息分叉，返回新分支的消息；原来的后续消息保留为另一个分支
+    pub fn fork(&mut self, index: usize) -> Vec<ChatEntry> {
+        let path = self.path(self.head);
+        if let Some(&node) = path.get(index) {
+            self.head = Some(node);
+        }
+        self.messages()
+    }
+
+    /// 切换到以 `leaf` 结尾的分支
+    pub fn switch(&mut self, leaf: usize) -> Vec<ChatEntry> {
+        if leaf < self.nodes.len() {
+            self.head = Some(leaf);
+        }
+        self.messages()
+    }
+
+    /// 所有分支（叶子节点），当前分支即使刚分叉还没有新消息也会列出
+    pub fn branches(&self) -> Vec<BranchInfo> {
+        let mut has_children = vec![false; self.nodes.len()];
+        for node in &self.nodes {
+            if let Some(parent) = node.parent {
+                has_children[parent] = true;
+            }
+        }
+        let current_path = self.path(self.head);
+
+        (0..self.nodes.len())
+            .filter(|&i| !has_children[i] || Some(i) == self.head)
+            .map(|leaf| {
+                let path = self.path(Some(leaf));
+                let shared = path
+                    .iter()
+                    .zip(&current_path)
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                // 用分叉后的第一条消息区分分支，没有时退回到最后一条
+                let preview_index = path.get(shared).copied().unwrap_or(leaf);
+                BranchInfo {
+                    leaf,
+                    len: path.len(),
+                    shared,
+                    preview: self.nodes[preview_index].entry.content().to_string(),
+                    updated_at: self.nodes[leaf].entry.timestamp,
+                    current: Some(leaf) == self.head,
+                }
+            })
+            .collect()
+    }
+}
+
+/// 保存的对话，存放在 ~/.config/ollama-cli/sessions/<id>.json
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Session {
+    pub id: String,
+    pub created_at: i64,
+    pub updated_at: i64,
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub summary: Option<String>,
+    #[serde(default)]
+    pub variables: Variables,
+    /// 回复中出现时中止生成的短语
+    #[serde(default)]
+    pub banned_phrases: Vec<String>,
+    /// 当前分支，便于旧版本和其他工具直接读取
+    pub messages: Vec<ChatEntry>,
+    /// 包含所有分支的完整对话；旧版本保存的会话没有此字段，由 `messages` 生成
+    #[serde(default, skip_serializing_if = "ConversationTree::is_empty")]
+    pub tree: ConversationTree,
+}
+
+impl Session {
+    pub fn new(model: Option<String>, messages: Vec<ChatEntry>) -> Self {
+        let now = chrono::Local::now();
+        Self {
+            id: unique_id(&now.format("%Y%m%d-%H%M%S").to_string()),
+            created_at: now.timestamp(),
+            updated_at: now.timestamp(),
+            model,
+            title: None,
+            summary: None,
+            variables: Variables::default(),
+            banned_phrases: Vec::new(),
+            messages,
+            tree: ConversationTree::default(),
+        }
+    }
+
+    /// 完整的对话树
+    pub fn conversation(&self) -> ConversationTree {
+        if self.tree.is_empty() {
+            ConversationTree::from_messages(&self.messages)
+        } else {
+            self.tree.clone()
+        }
+    }
+
+    /// 会话列表中显示的名称，没有标题时退回到创建时间
+    pub fn display_title(&self) -> String {
+        self.title.clone().unwrap_or_else(|| {
+            Some(timefmt::datetime(self.created_at))
+                .filter(|t| !t.is_empty())
+                .unwrap_or_else(|| self.id.clone())
+        })
+    }
+
+    pub fn save(&mut self) -> anyhow::Result<()> {
+        self.updated_at = chrono::Local::now().timestamp();
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(session_path(&self.id), json)?;
+        Ok(())
+    }
+}
+
+pub fn sessions_dir() -> PathBuf {
+    let dir = config::config_dir().join("sessions");
+    let _ = std::fs::create_dir_all(&dir);
+    dir
+}
+
+fn session_path(id: &str) -> PathBuf {
+    sessions_dir().join(format!("{}.json", id))
+}
+
+/// 同一秒内创建的会话加上 `-2`、`-3` 后缀，避免后保存的覆盖先保存的
+fn unique_id(base: &str) -> String {
+    let mut issued = ISSUED_IDS.lock().unwrap_or_else(|e| e.into_inner());
+    let mut id = base.to_string();
+    let mut n = 2;
+    while issued.contains(&id) || session_path(&id).exists() {
+        id = format!("{}-{}", base, n);
+        n += 1;
+    }
+    issued.insert(id.clone());
+    id
+}
+
+/// 读取全部会话，最近更新的排在前面
+pub fn list_sessions() -> Vec<Session> {
+    let mut sessions: Vec<Session> = std::fs::read_dir(sessions_dir())
+        .map(|entries| {
+            entries
+                .filter_map(|e| e.ok())
+                .filter(|e| e.path().extension().is_some_and(|ext| ext == "json"))
+                .filter_map(|e| std::fs::read_to_string(e.path()).ok())
+                .filter_map(|content| serde_json::from_str(&content).ok())
+                .collect()
+        })
+        .unwrap_or_default();
+    sessions.sort_by_key(|s| std::cmp::Reverse(s.updated_at));
+    sessions
+}
+
+pub fn load_session(id: &str) -> Option<Session> {
+    let content = std::fs::read_to_string(session_path(id)).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+pub fn delete_session(id: &str) -> anyhow::Result<()> {
+    std::fs::remove_file(session_path(id))?;
+    Ok(())
+}
+
+#[derive(Debug, Deserialize)]
+struct TitleSummary {
+    title: String,
+    #[serde(default)]
+    summary: String,
+}
+
+/// 请模型根据对话内容生成简短标题和一句话摘要
+pub fn title_prompt(messages: &[ChatMessage]) -> Vec<ChatMessage> {
+    let mut transcript = String::new();
+    for msg in messages.iter().filter(|m| m.role != "system") {
+        transcript.push_str(&format!("{}: {}\n\n", msg.role, msg.content));
+    }
+    // 长对话只取开头部分，标题通常由最初的问题决定
+    let transcript: String = transcript.chars().take(4000).collect();
+
+    vec![
+        ChatMessage {
+            role: "system".to_string(),
+            content: "You name chat conversations. Reply only with JSON of the form \
+                      {\"title\": \"...\", \"summary\": \"...\"}. The title has at most 6 words, \
+                      the summary is one sentence. Use the language of the conversation."
+                .to_string(),
+        },
+        ChatMessage {
+            role: "user".to_string(),
+            content: transcript,
+        },
+    ]
+}
+
+/// 解析模型返回的标题和摘要，不是合法 JSON 时把第一行当作标题
+pub fn parse_title_reply(reply: &str) -> (String, Option<String>) {
+    let trimmed = reply.trim();
+    let json = trimmed
+        .find('{')
+        .zip(trimmed.rfind('}'))
+        .and_then(|(start, end)| trimmed.get(start..=end));
+    if let Some(parsed) = json.and_then(|j| serde_json::from_str::<TitleSummary>(j).ok()) {
+        let summary = Some(parsed.summary.trim().to_string()).filter(|s| !s.is_empty());
+        return (clean_title(&parsed.title), summary);
+    }
+    (
+        clean_title(trimmed.lines().next().unwrap_or_default()),
+        None,
+    )
+}
+
+fn clean_title(title: &str) -> String {
+    title
+        .trim()
+        .trim_matches(|c| c == '"' || c == '#' || c == '*')
+        .trim()
+        .chars()
+        .take(60)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(role: &str, content: &str, timestamp: i64) -> ChatEntry {
+        let mut entry = ChatEntry::new(role, content.to_string());
+        entry.timestamp = timestamp;
+        entry
+    }
+
+    fn contents(messages: &[ChatEntry]) -> Vec<&str> {
+        messages.iter().map(|m| m.content()).collect()
+    }
+
+    #[test]
+    fn sync_reuses_the_shared_prefix() {
+        let first = vec![entry("user", "hi", 1), entry("assistant", "hello", 2)];
+        let mut tree = ConversationTree::from_messages(&first);
+        assert_eq!(tree.nodes().len(), 2);
+
+        let mut longer = first.clone();
+        longer.push(entry("user", "again", 3));
+        tree.sync(&longer);
+        assert_eq!(tree.nodes().len(), 3);
+        assert_eq!(tree.head(), Some(2));
+
+        // 内容不同的消息作为新分支追加，原来的节点保留
+        let edited = vec![entry("user", "hi", 1), entry("assistant", "hey", 4)];
+        tree.sync(&edited);
+        assert_eq!(tree.nodes().len(), 4);
+        assert_eq!(tree.nodes()[3].parent, Some(0));
+        assert_eq!(contents(&tree.messages()), ["hi", "hey"]);
+        assert_eq!(tree.branches().len(), 2);
+    }
+
+    #[test]
+    fn fork_keeps_the_old_branch() {
+        let messages = vec![
+            entry("user", "a", 1),
+            entry("assistant", "b", 2),
+            entry("user", "c", 3),
+        ];
+        let mut tree = ConversationTree::from_messages(&messages);
+        let forked = tree.fork(1);
+        assert_eq!(contents(&forked), ["a", "b"]);
+
+        let mut retry = forked;
+        retry.push(entry("user", "d", 4));
+        tree.sync(&retry);
+        assert_eq!(contents(&tree.messages()), ["a", "b", "d"]);
+
+        let old = tree
+            .branches()
+            .into_iter()
+            .find(|b| !b.current)
+            .expect("old branch");
+        assert_eq!(contents(&tree.switch(old.leaf)), ["a", "b", "c"]);
+
+        // 超出范围时保持当前分支
+        assert_eq!(tree.fork(10).len(), 3);
+    }
+
+    #[test]
+    fn from_nodes_rejects_bad_parents() {
+        let node = |parent| MessageNode {
+            entry: entry("user", "x", 0),
+            parent,
+        };
+        let tree = ConversationTree::from_nodes(vec![node(None), node(Some(0))], Some(7));
+        assert_eq!(tree.head(), Some(1));
+
+        // 父节点排在后面或指向自己都视为损坏
+        assert!(ConversationTree::from_nodes(vec![node(Some(1)), node(None)], None).is_empty());
+        assert!(ConversationTree::from_nodes(vec![node(Some(0))], None).is_empty());
+    }
+}
@@ -0,0 +1,216 @@
+use std::ops::Range;
+
+/// `:%s/old/new/[gc]` 形式的替换命令，`/` 可用 `\/` 转义
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Substitution {
+    pub pattern: String,
+    pub replacement: String,
+    /// 替换每行的所有匹配，否则只替换每行第一个
+    pub global: bool,
+    /// 逐个确认
+    pub confirm: bool,
+}
+
+impl Substitution {
+    /// 不是替换命令时返回 None，格式错误时返回 Err
+    pub fn parse(command: &str) -> Option<Result<Self, String>> {
+        let rest = command
+            .strip_prefix("%s")
+            .or_else(|| command.strip_prefix('s'))?;
+        let delimiter = rest.chars().next().filter(|c| *c == '/')?;
+        Some(Self::parse_parts(&rest[delimiter.len_utf8()..]))
+    }
+
+    fn parse_parts(text: &str) -> Result<Self, String> {
+        let mut parts = split_unescaped(text);
+        let pattern = parts.next().unwrap_or_default();
+        if pattern.is_empty() {
+            return Err("Empty search pattern".to_string());
+        }
+        let replacement = parts.next().unwrap_or_default();
+        let flags = parts.next().unwrap_or_default();
+        if parts.next().is_some() {
+            return Err("Too many '/' (escape it as \\/)".to_string());
+        }
+        let mut substitution = Self {
+            pattern,
+            replacement,
+            global: false,
+            confirm: false,
+        };
+        for flag in flags.chars() {
+            match flag {
+                'g' => substitution.global = true,
+                'c' => substitution.confirm = true,
+                other => return Err(format!("Unknown flag: {}", other)),
+            }
+        }
+        Ok(substitution)
+    }
+
+    /// 文本中所有要替换的位置（字节范围）
+    pub fn matches(&self, text: &str) -> Vec<Range<usize>> {
+        let mut ranges = Vec::new();
+        let mut line_start = 0;
+        for line in text.split_inclusive('\n') {
+            for (idx, _) in line.match_indices(&self.pattern) {
+                let start = line_start + idx;
+                ranges.push(start..start + self.pattern.len());
+                if !self.global {
+                    break;
+                }
+            }
+            line_start += line.len();
+        }
+        ranges
+    }
+
+    /// 只替换 `accepted` 中的位置，范围需按顺序且互不重叠
+    pub fn apply(&self, text: &str, accepted: &[Range<usize>]) -> String {
+        let mut result = String::with_capacity(text.len());
+        let mut last = 0;
+        for range in accepted {
+            result.push_str(&text[last..range.start]);
+            result.push_str(&self.replacement);
+            last = range.end;
+        }
+        result.push_str(&text[last..]);
+        result
+    }
+}
+
+/// 按未转义的 `/` 分割，`\/` 还原为 `/`，其他转义原样保留
+fn split_unescaped(text: &str) -> impl Iterator<Item = String> {
+    let mut parts = Vec::new();
+    let mut part = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('/') => part.push('/'),
+                Some(next) => {
+                    part.push('\\');
+                    part.push(next);
+                }
+                None => part.push('\\'),
+            },
+            '/' => parts.push(std::mem::take(&mut part)),
+            c => part.push(c),
+        }
+    }
+    // 末尾的 `/` 可省略也可保留
+    if !part.is_empty() || parts.is_empty() {
+        parts.push(part);
+    }
+    parts.into_iter()
+}
+
+/// 逐个确认替换时的进度
+#[derive(Debug, Clone)]
+pub struct Confirmation {
+    pub substitution: Substitution,
+    pub text: String,
+    pub matches: Vec<Range<usize>>,
+    /// 当前待确认的匹配
+    pub current: usize,
+    pub accepted: Vec<Range<usize>>,
+}
+
+impl Confirmation {
+    pub fn new(substitution: Substitution, text: String) -> Self {
+        let matches = substitution.matches(&text);
+        Self {
+            substitution,
+            text,
+            matches,
+            current: 0,
+            accepted: Vec::new(),
+        }
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.current >= self.matches.len()
+    }
+
+    pub fn accept(&mut self) {
+        if let Some(range) = self.matches.get(self.current) {
+            self.accepted.push(range.clone());
+            self.current += 1;
+        }
+    }
+
+    pub fn skip(&mut self) {
+        self.current += 1;
+    }
+
+    pub fn accept_all(&mut self) {
+        while !self.is_done() {
+            self.accept();
+        }
+    }
+
+    pub fn finish(&self) -> String {
+        self.substitution.apply(&self.text, &self.accepted)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(command: &str) -> Result<Substitution, String> {
+        Substitution::parse(command).expect("is a substitution")
+    }
+
+    #[test]
+    fn parse_requires_slash_delimiter() {
+        assert!(Substitution::parse("set foo").is_none());
+        assert!(Substitution::parse("s|a|b|").is_none());
+        assert!(Substitution::parse("%s/a/b/").is_some());
+        assert!(Substitution::parse("s/a/b").is_some());
+    }
+
+    #[test]
+    fn parse_flags() {
+        let sub = parse("%s/a/b/gc").unwrap();
+        assert_eq!(sub.pattern, "a");
+        assert_eq!(sub.replacement, "b");
+        assert!(sub.global && sub.confirm);
+
+        let sub = parse("s/a/b").unwrap();
+        assert!(!sub.global && !sub.confirm);
+
+        assert_eq!(parse("s/a/b/x").unwrap_err(), "Unknown flag: x");
+        assert!(parse("s/a/b/g/extra").is_err());
+    }
+
+    #[test]
+    fn parse_escapes() {
+        let sub = parse(r"s/a\/b/c\/d/").unwrap();
+        assert_eq!(sub.pattern, "a/b");
+        assert_eq!(sub.replacement, "c/d");
+
+        // 其他转义原样保留
+        let sub = parse(r"s/a\nb/c/").unwrap();
+        assert_eq!(sub.pattern, r"a\nb");
+    }
+
+    #[test]
+    fn parse_empty_pattern() {
+        assert_eq!(parse("s//b/").unwrap_err(), "Empty search pattern");
+        assert_eq!(parse("s/").unwrap_err(), "Empty search pattern");
+        // 替换文本可以为空
+        assert_eq!(parse("s/a//").unwrap().replacement, "");
+    }
+
+    #[test]
+    fn matches_first_per_line_unless_global() {
+        let text = "aa\naxa";
+        let first = parse("s/a/b/").unwrap();
+        assert_eq!(first.matches(text), vec![0..1, 3..4]);
+
+        let global = parse("s/a/b/g").unwrap();
+        assert_eq!(global.matches(text), vec![0..1, 1..2, 3..4, 5..6]);
+        assert_eq!(global.apply(text, &global.matches(text)), "bb\nbxb");
+    }
+}
@@ -0,0 +1,150 @@
+use crate::{AppState, SharedState};
+use crossterm::event::KeyEvent;
+use ratatui::layout::Rect;
+use ratatui::Frame;
+
+/// 所有可用的标签页；显示哪些、按什么顺序由配置中的 `tabs` 决定，见 `REGISTRY`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Tab {
+    #[default]
+    Chat,
+    Models,
+    Search,
+    Prompts,
+    Downloads,
+    Memory,
+}
+
+/// 标签页的描述：配置中使用的名称、标签栏标题，以及绘制和按键处理函数
+pub struct TabInfo {
+    pub tab: Tab,
+    /// 配置文件中使用的名称
+    pub id: &'static str,
+    pub title: &'static str,
+    pub render: fn(&mut Frame, &mut AppState, Rect),
+    pub handle_key: fn(&mut AppState, KeyEvent, &SharedState),
+}
+
+/// 标签页注册表，顺序即默认顺序；新增标签页时只需在这里登记
+pub const REGISTRY: &[TabInfo] = &[
+    TabInfo {
+        tab: Tab::Chat,
+        id: "chat",
+        title: "Chat",
+        render: crate::render_chat,
+        handle_key: |s, key, shared| crate::handle_chat_key(s, key.code, shared),
+    },
+    TabInfo {
+        tab: Tab::Models,
+        id: "models",
+        title: "Models",
+        render: crate::render_models,
+        handle_key: |s, key, shared| crate::handle_models_input(s, key.code, shared),
+    },
+    TabInfo {
+        tab: Tab::Search,
+        id: "search",
+        title: "Search",
+        render: crate::render_search,
+        handle_key: crate::handle_search_input,
+    },
+    TabInfo {
+        tab: Tab::Prompts,
+        id: "prompts",
+        title: "Prompts",
+        render: crate::render_prompts,
+        handle_key: |s, key, _| crate::handle_prompts_input(s, key.code),
+    },
+    TabInfo {
+        tab: Tab::Downloads,
+        id: "downloads",
+        title: "Downloads",
+        render: crate::render_downloads,
+        handle_key: |s, key, _| crate::handle_downloads_input(s, key.code),
+    },
+    TabInfo {
+        tab: Tab::Memory,
+        id: "memory",
+        title: "Memory",
+        render: crate::render_memory,
+        handle_key: |s, key, _| crate::handle_memory_input(s, key.code),
+    },
+];
+
+impl Tab {
+    pub fn info(self) -> Option<&'static TabInfo> {
+        REGISTRY.iter().find(|info| info.tab == self)
+    }
+
+    pub fn title(self) -> &'static str {
+        self.info().map(|info| info.title).unwrap_or_default()
+    }
+
+    pub fn from_id(id: &str) -> Option<Tab> {
+        REGISTRY
+            .iter()
+            .find(|info| info.id.eq_ignore_ascii_case(id.trim()))
+            .map(|info| info.tab)
+    }
+}
+
+/// 根据配置得到标签页顺序：忽略未知和重复的名称，Chat 总是保留
+pub fn tab_order(ids: Option<&[String]>) -> Vec<Tab> {
+    let Some(ids) = ids else {
+        return REGISTRY.iter().map(|info| info.tab).collect();
+    };
+    let mut tabs: Vec<Tab> = Vec::new();
+    for tab in ids.iter().filter_map(|id| Tab::from_id(id)) {
+        if !tabs.contains(&tab) {
+            tabs.push(tab);
+        }
+    }
+    if !tabs.contains(&Tab::Chat) {
+        tabs.insert(0, Tab::Chat);
+    }
+    tabs
+}
+
+/// 标签栏中显示的标题，前 9 个带数字键提示
+pub fn tab_labels(tabs: &[Tab]) -> Vec<String> {
+    tabs.iter()
+        .enumerate()
+        .map(|(i, tab)| {
+            if i < 9 {
+                format!(" {} {} ", i + 1, tab.title())
+            } else {
+                format!(" {} ", tab.title())
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids(ids: &[&str]) -> Vec<String> {
+        ids.iter().map(|id| id.to_string()).collect()
+    }
+
+    #[test]
+    fn default_order_follows_registry() {
+        let order = tab_order(None);
+        assert_eq!(order.len(), REGISTRY.len());
+        assert_eq!(order[0], Tab::Chat);
+    }
+
+    #[test]
+    fn configured_order_skips_unknown_and_duplicates() {
+        let order = tab_order(Some(&ids(&[
+            "Models", " chat ", "nope", "models", "memory",
+        ])));
+        assert_eq!(order, [Tab::Models, Tab::Chat, Tab::Memory]);
+    }
+
+    #[test]
+    fn chat_is_always_kept() {
+        assert_eq!(tab_order(Some(&ids(&["search"]))), [Tab::Chat, Tab::Search]);
+        assert_eq!(tab_order(Some(&[])), [Tab::Chat]);
+    }
+}
@@ -0,0 +1,154 @@
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, FixedOffset, Local, Offset, Utc};
+use once_cell::sync::Lazy;
+use std::sync::RwLock;
+
+/// 界面和日志中时间的显示方式，由配置中的 `time_format`、`date_format` 和 `timezone` 决定
+#[derive(Debug, Clone)]
+struct TimeFormat {
+    zone: Zone,
+    datetime: String,
+    date: String,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Zone {
+    Local,
+    Fixed(FixedOffset),
+}
+
+impl Default for TimeFormat {
+    fn default() -> Self {
+        Self {
+            zone: Zone::Local,
+            datetime: "%Y-%m-%d %H:%M:%S".to_string(),
+            date: "%Y-%m-%d".to_string(),
+        }
+    }
+}
+
+static FORMAT: Lazy<RwLock<TimeFormat>> = Lazy::new(|| RwLock::new(TimeFormat::default()));
+
+/// 按配置设置全局格式；`time_format` 为 `iso` 时使用 RFC 3339。
+/// 无法识别的格式或时区会被忽略（保留默认值）并返回错误说明
+pub fn configure(
+    time_format: Option<&str>,
+    date_format: Option<&str>,
+    timezone: Option<&str>,
+) -> Result<(), String> {
+    let mut format = TimeFormat::default();
+    let mut errors = Vec::new();
+    match time_format {
+        Some("iso") => format.datetime = "%Y-%m-%dT%H:%M:%S%:z".to_string(),
+        Some(pattern) if !pattern.is_empty() => match validate(pattern) {
+            Ok(()) => format.datetime = pattern.to_string(),
+            Err(e) => errors.push(e),
+        },
+        _ => {}
+    }
+    if let Some(pattern) = date_format.filter(|d| !d.is_empty()) {
+        match validate(pattern) {
+            Ok(()) => format.date = pattern.to_string(),
+            Err(e) => errors.push(e),
+        }
+    }
+    if let Some(timezone) = timezone {
+        match parse_zone(timezone) {
+            Ok(zone) => format.zone = zone,
+            Err(e) => errors.push(e),
+        }
+    }
+    if let Ok(mut current) = FORMAT.write() {
+        *current = format;
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors.join("; "))
+    }
+}
+
+// 格式串有误时 chrono 在格式化时会 panic，需要提前检查
+fn validate(pattern: &str) -> Result<(), String> {
+    if StrftimeItems::new(pattern).any(|item| matches!(item, Item::Error)) {
+        Err(format!("invalid time format {:?}", pattern))
+    } else {
+        Ok(())
+    }
+}
+
+/// `local`、`utc` 或 `+08:00` 形式的固定偏移
+fn parse_zone(text: &str) -> Result<Zone, String> {
+    match text.trim() {
+        "" | "local" => Ok(Zone::Local),
+        "utc" | "UTC" | "Z" => Ok(Zone::Fixed(Utc.fix())),
+        offset => offset
+            .parse::<FixedOffset>()
+            .map(Zone::Fixed)
+            .map_err(|_| format!("unknown timezone {:?}, use local, utc or +08:00", offset)),
+    }
+}
+
+fn with_format<T>(f: impl FnOnce(&TimeFormat) -> T) -> T {
+    match FORMAT.read() {
+        Ok(format) => f(&format),
+        Err(_) => f(&TimeFormat::default()),
+    }
+}
+
+fn format_in_zone(timestamp: i64, pattern: &str, zone: Zone) -> Option<String> {
+    let utc = DateTime::from_timestamp(timestamp, 0)?;
+    Some(match zone {
+        Zone::Local => utc.with_timezone(&Local).format(pattern).to_string(),
+        Zone::Fixed(offset) => utc.with_timezone(&offset).format(pattern).to_string(),
+    })
+}
+
+/// 完整的日期和时间，用于消息详情、会话列表等
+pub fn datetime(timestamp: i64) -> String {
+    with_format(|f| format_in_zone(timestamp, &f.datetime, f.zone)).unwrap_or_default()
+}
+
+/// 只有日期，用于模型修改时间等列
+pub fn date(timestamp: i64) -> String {
+    with_format(|f| format_in_zone(timestamp, &f.date, f.zone)).unwrap_or_default()
+}
+
+/// 按配置的时区格式化，格式固定（如列表中的时分秒）
+pub fn custom(timestamp: i64, pattern: &str) -> String {
+    with_format(|f| format_in_zone(timestamp, pattern, f.zone)).unwrap_or_default()
+}
+
+/// 日志中使用的当前时间（RFC 3339，按配置的时区）
+pub fn now_rfc3339() -> String {
+    let now = Utc::now();
+    with_format(|f| match f.zone {
+        Zone::Local => now.with_timezone(&Local).to_rfc3339(),
+        Zone::Fixed(offset) => now.with_timezone(&offset).to_rfc3339(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 格式是全局的，放在一个测试里避免并行测试互相覆盖
+    #[test]
+    fn configure_rejects_invalid_settings() {
+        let error = configure(Some("%Y-%Q"), Some("%"), Some("mars")).unwrap_err();
+        assert!(error.contains("invalid time format \"%Y-%Q\""), "{}", error);
+        assert!(error.contains("invalid time format \"%\""), "{}", error);
+        assert!(error.contains("unknown timezone \"mars\""), "{}", error);
+        // 无效的设置保留默认值，格式化不会 panic
+        assert_eq!(datetime(0).len(), "1970-01-01 00:00:00".len());
+
+        configure(Some("%d/%m/%Y %H:%M"), Some("%d/%m"), Some("+08:00")).unwrap();
+        assert_eq!(datetime(0), "01/01/1970 08:00");
+        assert_eq!(date(0), "01/01");
+
+        configure(Some("iso"), None, Some("utc")).unwrap();
+        assert_eq!(datetime(0), "1970-01-01T00:00:00+00:00");
+
+        configure(None, None, None).unwrap();
+    }
+}
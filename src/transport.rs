@@ -0,0 +1,374 @@
+use crate::recorder;
+use anyhow::Context;
+use once_cell::sync::Lazy;
+use reqwest::Method;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::io::{BufRead, BufReader, Read};
+use std::path::PathBuf;
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_HOST: &str = "http://localhost:11434";
+
+// 流式响应（拉取校验、模型加载）可能长时间没有数据，按单次读取计时
+const READ_TIMEOUT: Duration = Duration::from_secs(300);
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Ollama 服务地址：TCP（可带路径前缀）或 Unix domain socket
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Endpoint {
+    Http(String),
+    Unix { socket: PathBuf, base_path: String },
+}
+
+impl Default for Endpoint {
+    fn default() -> Self {
+        Endpoint::Http(DEFAULT_HOST.to_string())
+    }
+}
+
+impl std::fmt::Display for Endpoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Endpoint::Http(url) => write!(f, "{}", url),
+            Endpoint::Unix { socket, base_path } => {
+                write!(f, "unix://{}{}", socket.display(), base_path)
+            }
+        }
+    }
+}
+
+impl Endpoint {
+    /// 解析 `host:port`、`http(s)://host[:port][/base]` 或 `unix:///path/to/ollama.sock`，
+    /// `base_path` 附加在所有 API 路径之前（反向代理或共享 socket 的场景）
+    pub fn parse(host: &str, base_path: Option<&str>) -> Self {
+        let host = host.trim();
+        let base_path = normalize_base_path(base_path.unwrap_or_default());
+
+        if let Some(socket) = host
+            .strip_prefix("unix://")
+            .or_else(|| host.strip_prefix("unix:"))
+        {
+            return Endpoint::Unix {
+                socket: PathBuf::from(socket),
+                base_path,
+            };
+        }
+
+        let mut url = if host.contains("://") {
+            host.to_string()
+        } else {
+            format!("http://{}", host)
+        };
+
+        // 与 ollama 命令行一致：http 未指定端口时使用 11434
+        if url.starts_with("http://") {
+            let authority_start = "http://".len();
+            let authority_end = url[authority_start..]
+                .find('/')
+                .map(|i| i + authority_start)
+                .unwrap_or(url.len());
+            if !url[authority_start..authority_end].contains(':') {
+                url.insert_str(authority_end, ":11434");
+            }
+        }
+
+        let url = url.trim_end_matches('/').to_string();
+        Endpoint::Http(format!("{}{}", url, base_path))
+    }
+}
+
+fn normalize_base_path(path: &str) -> String {
+    let path = path.trim().trim_matches('/');
+    if path.is_empty() {
+        String::new()
+    } else {
+        format!("/{}", path)
+    }
+}
+
+static ENDPOINT: Lazy<RwLock<Endpoint>> = Lazy::new(|| RwLock::new(Endpoint::default()));
+
+/// 设置全局使用的 Ollama 地址（启动时根据配置调用）
+pub fn set_endpoint(endpoint: Endpoint) {
+    if let Ok(mut current) = ENDPOINT.write() {
+        *current = endpoint;
+    }
+}
+
+pub fn endpoint() -> Endpoint {
+    ENDPOINT.read().map(|e| e.clone()).unwrap_or_default()
+}
+
+pub struct Response {
+    pub status: u16,
+    body: Box<dyn Read + Send>,
+}
+
+impl Response {
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+
+    pub fn json<T: DeserializeOwned>(self) -> anyhow::Result<T> {
+        Ok(serde_json::from_reader(self.body)?)
+    }
+
+    pub fn text(mut self) -> anyhow::Result<String> {
+        let mut text = String::new();
+        self.body.read_to_string(&mut text)?;
+        Ok(text)
+    }
+
+    pub fn lines(self) -> std::io::Lines<BufReader<Box<dyn Read + Send>>> {
+        BufReader::new(self.body).lines()
+    }
+}
+
+/// 发送请求，返回状态码和可流式读取的响应体
+pub fn request<T: Serialize + ?Sized>(
+    endpoint: &Endpoint,
+    method: Method,
+    path: &str,
+    body: Option<&T>,
+) -> anyhow::Result<Response> {
+    request_until(endpoint, method, path, body, None)
+}
+
+/// 与 `request` 相同，但到 `deadline` 时断开连接，之后读取响应体会返回超时错误
+pub fn request_until<T: Serialize + ?Sized>(
+    endpoint: &Endpoint,
+    method: Method,
+    path: &str,
+    body: Option<&T>,
+    deadline: Option<Instant>,
+) -> anyhow::Result<Response> {
+    let body = match body {
+        Some(body) => serde_json::to_vec(body)?,
+        None => Vec::new(),
+    };
+    let record_id = recorder::record_request(&endpoint.to_string(), method.as_str(), path, &body);
+
+    let sent = send(endpoint, method, path, body, deadline);
+    // 连接被拒绝、DNS 失败等没有响应的请求也要留在录制中
+    let response = match (sent, record_id) {
+        (Ok(response), _) => response,
+        (Err(e), Some(id)) => {
+            recorder::record_error(id, &e);
+            return Err(e);
+        }
+        (Err(e), None) => return Err(e),
+    };
+
+    Ok(match record_id {
+        Some(id) => {
+            recorder::record_response(id, response.status);
+            Response {
+                status: response.status,
+                body: recorder::wrap_body(id, response.body),
+            }
+        }
+        None => response,
+    })
+}
+
+fn send(
+    endpoint: &Endpoint,
+    method: Method,
+    path: &str,
+    body: Vec<u8>,
+    deadline: Option<Instant>,
+) -> anyhow::Result<Response> {
+    Ok(match endpoint {
+        Endpoint::Http(base_url) => {
+            let client = reqwest::blocking::Client::builder()
+                .timeout(READ_TIMEOUT)
+                .connect_timeout(CONNECT_TIMEOUT)
+                .build()?;
+            let mut builder = client.request(method, format!("{}{}", base_url, path));
+            // 请求级的超时覆盖到响应体读完为止
+            if let Some(deadline) = deadline {
+                builder = builder.timeout(deadline.saturating_duration_since(Instant::now()));
+            }
+            if !body.is_empty() {
+                builder = builder
+                    .header("Content-Type", "application/json")
+                    .body(body);
+            }
+            let response = builder.send()?;
+            Response {
+                status: response.status().as_u16(),
+                body: Box::new(response),
+            }
+        }
+        Endpoint::Unix { socket, base_path } => unix_request(
+            socket,
+            &method,
+            &format!("{}{}", base_path, path),
+            &body,
+            deadline,
+        )?,
+    })
+}
+
+/// 通过 Unix socket 发送 HTTP/1.0 请求：服务端不会使用 chunked 编码，
+/// 响应体直接流式写出直到关闭连接
+#[cfg(unix)]
+fn unix_request(
+    socket: &std::path::Path,
+    method: &Method,
+    path: &str,
+    body: &[u8],
+    deadline: Option<Instant>,
+) -> anyhow::Result<Response> {
+    use std::io::Write;
+
+    let mut stream = std::os::unix::net::UnixStream::connect(socket)
+        .with_context(|| format!("cannot connect to {}", socket.display()))?;
+    stream.set_read_timeout(Some(read_timeout(deadline)?))?;
+
+    write!(
+        stream,
+        "{} {} HTTP/1.0\r\nHost: localhost\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n",
+        method.as_str(),
+        path,
+        body.len()
+    )?;
+    stream.write_all(body)?;
+
+    let mut reader = BufReader::new(stream);
+    let mut status_line = String::new();
+    reader.read_line(&mut status_line)?;
+    let status = status_line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|s| s.parse::<u16>().ok())
+        .ok_or_else(|| anyhow::anyhow!("invalid HTTP response: {}", status_line.trim()))?;
+
+    // 跳过响应头
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+            break;
+        }
+    }
+
+    Ok(Response {
+        status,
+        body: match deadline {
+            Some(deadline) => Box::new(DeadlineReader { reader, deadline }),
+            None => Box::new(reader),
+        },
+    })
+}
+
+/// 单次读取的超时，不超过到 `deadline` 的剩余时间；已经到期时返回超时错误
+#[cfg(unix)]
+fn read_timeout(deadline: Option<Instant>) -> std::io::Result<Duration> {
+    let Some(deadline) = deadline else {
+        return Ok(READ_TIMEOUT);
+    };
+    let remaining = deadline.saturating_duration_since(Instant::now());
+    if remaining.is_zero() {
+        return Err(std::io::ErrorKind::TimedOut.into());
+    }
+    Ok(remaining.min(READ_TIMEOUT))
+}
+
+/// Unix socket 的响应体，每次读取前按剩余时间重新设置超时
+#[cfg(unix)]
+struct DeadlineReader {
+    reader: BufReader<std::os::unix::net::UnixStream>,
+    deadline: Instant,
+}
+
+#[cfg(unix)]
+impl Read for DeadlineReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let timeout = read_timeout(Some(self.deadline))?;
+        self.reader.get_ref().set_read_timeout(Some(timeout))?;
+        self.reader.read(buf)
+    }
+}
+
+#[cfg(not(unix))]
+fn unix_request(
+    _socket: &std::path::Path,
+    _method: &Method,
+    _path: &str,
+    _body: &[u8],
+    _deadline: Option<Instant>,
+) -> anyhow::Result<Response> {
+    anyhow::bail!("Unix domain sockets are not supported on this platform")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn http(url: &str) -> Endpoint {
+        Endpoint::Http(url.to_string())
+    }
+
+    #[test]
+    fn parse_adds_scheme_and_default_port() {
+        assert_eq!(
+            Endpoint::parse("localhost", None),
+            http("http://localhost:11434")
+        );
+        assert_eq!(
+            Endpoint::parse(" 10.0.0.2:8080 ", None),
+            http("http://10.0.0.2:8080")
+        );
+        assert_eq!(
+            Endpoint::parse("http://example.com/", None),
+            http("http://example.com:11434")
+        );
+        // https 使用默认端口 443
+        assert_eq!(
+            Endpoint::parse("https://example.com", None),
+            http("https://example.com")
+        );
+    }
+
+    #[test]
+    fn parse_joins_base_path() {
+        assert_eq!(
+            Endpoint::parse("http://example.com/ollama/", None),
+            http("http://example.com:11434/ollama")
+        );
+        assert_eq!(
+            Endpoint::parse("https://example.com/", Some("/proxy/ollama/")),
+            http("https://example.com/proxy/ollama")
+        );
+        assert_eq!(
+            Endpoint::parse("host:1/a", Some("b")),
+            http("http://host:1/a/b")
+        );
+        assert_eq!(
+            Endpoint::parse("host:1", Some(" / ")),
+            http("http://host:1")
+        );
+    }
+
+    #[test]
+    fn parse_unix_socket() {
+        let expected = Endpoint::Unix {
+            socket: PathBuf::from("/run/ollama.sock"),
+            base_path: String::new(),
+        };
+        assert_eq!(Endpoint::parse("unix:///run/ollama.sock", None), expected);
+        assert_eq!(Endpoint::parse("unix:/run/ollama.sock", None), expected);
+
+        let endpoint = Endpoint::parse("unix:///run/ollama.sock", Some("api-proxy/"));
+        assert_eq!(
+            endpoint,
+            Endpoint::Unix {
+                socket: PathBuf::from("/run/ollama.sock"),
+                base_path: "/api-proxy".to_string(),
+            }
+        );
+        assert_eq!(endpoint.to_string(), "unix:///run/ollama.sock/api-proxy");
+    }
+}
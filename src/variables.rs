@@ -0,0 +1,107 @@
+use std::collections::BTreeMap;
+
+/// 对话级变量（如 `project=ollama-cli`），随会话保存
+pub type Variables = BTreeMap<String, String>;
+
+/// 解析 `key=value`，key 去掉首尾空白后不能为空
+pub fn parse_assignment(text: &str) -> Option<(String, String)> {
+    let (key, value) = text.split_once('=')?;
+    let key = key.trim();
+    if key.is_empty() || key.contains(char::is_whitespace) {
+        return None;
+    }
+    Some((key.to_string(), value.trim().to_string()))
+}
+
+/// 把文本中的 `{{key}}` 替换为变量值，未定义的变量原样保留；
+/// 多余的花括号（如 `{{{key}}}`）按普通字符保留，只替换最内层的一对
+pub fn expand(text: &str, variables: &Variables) -> String {
+    if variables.is_empty() {
+        return text.to_string();
+    }
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(end) = rest.find("}}") {
+        match rest[..end].rfind("{{") {
+            Some(start) => {
+                result.push_str(&rest[..start]);
+                match variables.get(rest[start + 2..end].trim()) {
+                    Some(value) => result.push_str(value),
+                    None => result.push_str(&rest[start..end + 2]),
+                }
+            }
+            None => result.push_str(&rest[..end + 2]),
+        }
+        rest = &rest[end + 2..];
+    }
+    result.push_str(rest);
+    result
+}
+
+/// 附加到系统提示词中的变量说明
+pub fn context_block(variables: &Variables) -> Option<String> {
+    if variables.is_empty() {
+        return None;
+    }
+    let mut block = String::from("Context for this conversation:");
+    for (key, value) in variables {
+        block.push_str(&format!("\n- {}: {}", key, value));
+    }
+    Some(block)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars() -> Variables {
+        Variables::from([
+            ("project".to_string(), "ollama-cli".to_string()),
+            ("lang".to_string(), "Rust".to_string()),
+        ])
+    }
+
+    #[test]
+    fn expands_known_variables() {
+        assert_eq!(
+            expand("{{project}} in {{ lang }}", &vars()),
+            "ollama-cli in Rust"
+        );
+        assert_eq!(expand("{{project}}", &Variables::new()), "{{project}}");
+    }
+
+    #[test]
+    fn keeps_unknown_variables() {
+        assert_eq!(
+            expand("{{name}} / {{project}}", &vars()),
+            "{{name}} / ollama-cli"
+        );
+        assert_eq!(expand("{{}}", &vars()), "{{}}");
+    }
+
+    #[test]
+    fn leaves_other_braces_alone() {
+        let json = r#"{"a": {"b": 1}}"#;
+        assert_eq!(expand(json, &vars()), json);
+        assert_eq!(expand("{{project", &vars()), "{{project");
+        assert_eq!(expand("lang}} {{lang}}", &vars()), "lang}} Rust");
+    }
+
+    #[test]
+    fn nested_braces_expand_the_innermost_pair() {
+        assert_eq!(expand("{{{lang}}}", &vars()), "{Rust}");
+        assert_eq!(expand("{{ {{lang}} }}", &vars()), "{{ Rust }}");
+        assert_eq!(expand("{{a{{lang}}b}}", &vars()), "{{aRustb}}");
+    }
+
+    #[test]
+    fn parses_assignments() {
+        assert_eq!(
+            parse_assignment(" key = a = b "),
+            Some(("key".to_string(), "a = b".to_string()))
+        );
+        assert_eq!(parse_assignment("two words=x"), None);
+        assert_eq!(parse_assignment("=x"), None);
+        assert_eq!(parse_assignment("novalue"), None);
+    }
+}
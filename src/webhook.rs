@@ -0,0 +1,70 @@
+use serde::Serialize;
+use std::time::Duration;
+
+const ATTEMPTS: u32 = 3;
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 回复完成或模型下载结束时 POST 到 `webhook_url` 的 JSON
+#[derive(Debug, Clone, Serialize)]
+pub struct Payload {
+    /// `generation` 或 `pull`
+    pub event: &'static str,
+    /// `finished`、`stopped` 或 `failed`
+    pub status: &'static str,
+    pub model: String,
+    /// 一句话说明，适合直接作为手机通知的正文
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session: Option<String>,
+    /// 回复全文
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub eval_count: Option<u64>,
+    pub time: String,
+}
+
+impl Payload {
+    pub fn new(event: &'static str, status: &'static str, model: &str, message: String) -> Self {
+        Self {
+            event,
+            status,
+            model: model.to_string(),
+            message,
+            session: None,
+            content: None,
+            eval_count: None,
+            time: crate::timefmt::now_rfc3339(),
+        }
+    }
+}
+
+/// 在后台线程中发送，失败时按 1、2 秒退避重试，结果不影响界面
+pub fn send(url: &str, payload: Payload) {
+    let url = url.to_string();
+    std::thread::spawn(move || {
+        let Ok(client) = reqwest::blocking::Client::builder()
+            .user_agent("ollama-cli/0.1.0")
+            .timeout(TIMEOUT)
+            .build()
+        else {
+            return;
+        };
+        for attempt in 0..ATTEMPTS {
+            if attempt > 0 {
+                std::thread::sleep(Duration::from_secs(1 << (attempt - 1)));
+            }
+            let sent = client
+                .post(&url)
+                .header("Title", format!("ollama-cli: {}", payload.model))
+                .json(&payload)
+                .send();
+            match sent {
+                Ok(response) if response.status().is_success() => return,
+                // 4xx 重试也不会成功
+                Ok(response) if response.status().is_client_error() => return,
+                _ => {}
+            }
+        }
+    });
+}